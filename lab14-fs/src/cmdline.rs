@@ -0,0 +1,12 @@
+//! Parameters on the kernel command line, e.g., `root=vda1 rootfstype=ext2`.
+
+/// Returns the value of the last `key=value` parameter named `key`.
+pub fn get(key: &str) -> Option<&'static str> {
+    ostd::boot::boot_info()
+        .kernel_cmdline
+        .split_whitespace()
+        .rev()
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| *name == key)
+        .map(|(_, value)| value)
+}
@@ -1,7 +1,10 @@
-use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+
+use alloc::{string::String, vec::Vec};
 use ostd::{
-    mm::{DmaStream, FrameAllocOptions, VmIo, VmWriter},
-    sync::Mutex,
+    mm::{DmaStream, FallibleVmRead, FallibleVmWrite, FrameAllocOptions, VmIo, VmReader, VmWriter},
+    sync::{LocalIrqDisabled, Mutex, SpinLock},
 };
 use spin::Once;
 
@@ -9,12 +12,39 @@ use crate::drivers::utils::{DmaSlice, DmaSliceAlloc};
 
 pub const SECTOR_SIZE: usize = 512;
 
+/// The id of the next block device created.
+static NEXT_DEVICE_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Allocates the id of a new block device.
+pub fn alloc_device_id() -> usize {
+    NEXT_DEVICE_ID.fetch_add(1, Ordering::Relaxed)
+}
+
 static DMA_ALLOCATOR: Once<Mutex<DmaSliceAlloc<[u8; SECTOR_SIZE], DmaStream>>> = Once::new();
 
 pub trait BlockDevice: Send + Sync {
+    /// The id of the device, from [`alloc_device_id`], which is never reused.
+    fn id(&self) -> usize;
+
     fn read_block(&self, req: &mut BioRequest);
 
-    fn write_block(&self, req: &BioRequest);
+    fn write_block(&self, req: &mut BioRequest);
+
+    /// Makes sure the completed writes reach persistent storage.
+    fn flush(&self) {}
+
+    /// The serial number reported by the device, if any.
+    fn serial(&self) -> Option<String> {
+        None
+    }
+
+    /// The capacity of the device in sectors.
+    fn num_sectors(&self) -> usize;
+
+    /// The I/O statistics, if the device keeps them.
+    fn stats(&self) -> Option<BlockStats> {
+        None
+    }
 }
 
 impl dyn BlockDevice {
@@ -22,49 +52,67 @@ impl dyn BlockDevice {
         let mut request = BioRequest::new(index, num_sectors);
         self.read_block(&mut request);
 
+        let mut sector = [0u8; SECTOR_SIZE];
         for data in request.data.iter() {
-            data.read(0, &mut *writer).unwrap();
+            data.read_bytes(0, &mut sector).unwrap();
+            writer
+                .write_fallible(&mut VmReader::from(sector.as_slice()))
+                .unwrap();
         }
     }
 
+    pub fn write_from_vm_reader(&self, index: usize, num_sectors: usize, reader: &mut VmReader) {
+        let mut request = BioRequest::new(index, num_sectors);
+
+        let mut sector = [0u8; SECTOR_SIZE];
+        for data in request.data.iter() {
+            let len = reader
+                .read_fallible(&mut VmWriter::from(sector.as_mut_slice()))
+                .unwrap();
+            sector[len..].fill(0);
+            data.write_bytes(0, &sector).unwrap();
+        }
+        self.write_block(&mut request);
+    }
+
     pub fn read_val_offset<T: ostd::Pod>(&self, index: usize, offset: usize) -> T {
         assert!(core::mem::size_of::<T>() + offset <= SECTOR_SIZE);
         let mut request = BioRequest::new(index, 1);
         self.read_block(&mut request);
-        request.data.pop().unwrap().read_val(offset).unwrap()
+        request.data[0].read_val(offset).unwrap()
     }
 
     pub fn write_val_offset<T: ostd::Pod>(&self, index: usize, offset: usize, val: &T) {
         assert!(core::mem::size_of::<T>() + offset <= SECTOR_SIZE);
-        let request = BioRequest::new(index, 1);
+        let mut request = BioRequest::new(index, 1);
         request.data[0].write_val(offset, val).unwrap();
-        self.write_block(&request);
+        self.write_block(&mut request);
     }
 
-    pub fn read_one(&self, index: usize) -> DmaSlice<DmaStream> {
+    pub fn read_one(&self, index: usize) -> [u8; SECTOR_SIZE] {
         let mut request = BioRequest::new(index, 1);
         self.read_block(&mut request);
-        request.data.pop().unwrap()
+        request.data[0].read_val(0).unwrap()
     }
 
     pub fn write_one(&self, index: usize, data: &[u8; SECTOR_SIZE]) {
-        let request = BioRequest::new(index, 1);
+        let mut request = BioRequest::new(index, 1);
         request.data[0].write_bytes(0, &data.as_ref()).unwrap();
-        self.write_block(&request);
+        self.write_block(&mut request);
     }
 
     pub fn read_val<T: ostd::Pod>(&self, index: usize) -> T {
         assert!(core::mem::size_of::<T>() <= SECTOR_SIZE);
         let mut request = BioRequest::new(index, 1);
         self.read_block(&mut request);
-        request.data.pop().unwrap().read_val(0).unwrap()
+        request.data[0].read_val(0).unwrap()
     }
 
     pub fn write_val<T: ostd::Pod>(&self, index: usize, val: &T) {
         assert!(core::mem::size_of::<T>() <= SECTOR_SIZE);
-        let request = BioRequest::new(index, 1);
+        let mut request = BioRequest::new(index, 1);
         request.data[0].write_val(0, val).unwrap();
-        self.write_block(&request);
+        self.write_block(&mut request);
     }
 }
 
@@ -92,11 +140,130 @@ impl BioRequest {
         self.index
     }
 
+    pub fn set_index(&mut self, index: usize) {
+        self.index = index;
+    }
+
     pub fn num_sectors(&self) -> usize {
         self.data.len()
     }
 }
 
+/// The I/O statistics of a block device, with the fields of
+/// `/sys/block/<dev>/stat` that are accounted.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BlockStats {
+    pub read_ios: u64,
+    pub read_sectors: u64,
+    /// The total time the reads took, from submission to completion.
+    pub read_time: Duration,
+    pub write_ios: u64,
+    pub write_sectors: u64,
+    pub write_time: Duration,
+    /// The reads and the writes submitted but not completed yet.
+    pub in_flight: [u64; 2],
+    /// The time during which requests were in flight.
+    pub io_time: Duration,
+    /// The total time the requests took, which counts overlapping ones twice.
+    pub queue_time: Duration,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum IoDirection {
+    Read = 0,
+    Write = 1,
+}
+
+/// Accounts the requests to a block device into [`BlockStats`].
+pub struct IoAccounting {
+    inner: SpinLock<IoAccountingInner, LocalIrqDisabled>,
+}
+
+#[derive(Default)]
+struct IoAccountingInner {
+    stats: BlockStats,
+    /// When the device last turned from idle to busy.
+    busy_since: Duration,
+}
+
+impl IoAccounting {
+    pub fn new() -> Self {
+        Self {
+            inner: SpinLock::new(IoAccountingInner::default()),
+        }
+    }
+
+    /// Accounts a request of `direction` being submitted, returning the time
+    /// to be passed back to [`Self::end`].
+    pub fn start(&self, direction: IoDirection) -> Duration {
+        let now = now();
+        let mut inner = self.inner.lock();
+        if inner.stats.in_flight.iter().all(|&n| n == 0) {
+            inner.busy_since = now;
+        }
+        inner.stats.in_flight[direction as usize] += 1;
+        now
+    }
+
+    /// Accounts a request of `direction` on `num_sectors`, submitted at
+    /// `start`, being completed.
+    pub fn end(&self, direction: IoDirection, num_sectors: usize, start: Duration) {
+        let now = now();
+        let elapsed = now.saturating_sub(start);
+        let mut inner = self.inner.lock();
+        let stats = &mut inner.stats;
+        let (ios, sectors, time) = match direction {
+            IoDirection::Read => (
+                &mut stats.read_ios,
+                &mut stats.read_sectors,
+                &mut stats.read_time,
+            ),
+            IoDirection::Write => (
+                &mut stats.write_ios,
+                &mut stats.write_sectors,
+                &mut stats.write_time,
+            ),
+        };
+        *ios += 1;
+        *sectors += num_sectors as u64;
+        *time += elapsed;
+        stats.queue_time += elapsed;
+        stats.in_flight[direction as usize] -= 1;
+        if stats.in_flight.iter().all(|&n| n == 0) {
+            let busy = now.saturating_sub(inner.busy_since);
+            inner.stats.io_time += busy;
+        }
+    }
+
+    /// The statistics, with the time requests in flight have been so far.
+    pub fn stats(&self) -> BlockStats {
+        let now = now();
+        let inner = self.inner.lock();
+        let mut stats = inner.stats;
+        if stats.in_flight.iter().any(|&n| n != 0) {
+            stats.io_time += now.saturating_sub(inner.busy_since);
+        }
+        stats
+    }
+}
+
+/// The time since boot, precise enough to time a single request.
+fn now() -> Duration {
+    let ticks = ostd::arch::read_tsc() as u128;
+    let nanos = ticks * 1_000_000_000 / ostd::arch::tsc_freq() as u128;
+    Duration::from_nanos(nanos as u64)
+}
+
+impl Drop for BioRequest {
+    fn drop(&mut self) {
+        // Return the sector slices to the pool so that the DMA region can be reused.
+        let mut dma_allocator = DMA_ALLOCATOR.get().unwrap().lock();
+        for data in self.data.drain(..) {
+            dma_allocator.dealloc(data);
+        }
+    }
+}
+
 pub(super) fn init() {
     const POOL_SIZE: usize = 128;
     let segment = FrameAllocOptions::new().alloc_segment(POOL_SIZE).unwrap();
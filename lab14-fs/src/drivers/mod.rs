@@ -1,14 +1,16 @@
 #![expect(dead_code)]
 #![expect(unused_variables)]
 
-use alloc::{sync::Arc, vec::Vec};
+use alloc::{format, string::String, sync::Arc, vec::Vec};
 use core::ffi::CStr;
 use ostd::early_println;
 use spin::{Mutex, Once};
 
 use crate::drivers::blk::{BlockDevice, SECTOR_SIZE};
+use crate::drivers::partition::PARTITIONS;
 
 pub mod blk;
+pub mod partition;
 pub mod utils;
 pub mod virtio;
 
@@ -16,11 +18,65 @@ pub static BLOCK_DEVICES: Once<Mutex<Vec<Arc<dyn BlockDevice>>>> = Once::new();
 
 pub fn init() {
     BLOCK_DEVICES.call_once(|| Mutex::new(Vec::new()));
-    virtio::init();
+    // The devices use the DMA pool of the block layer when probed.
     blk::init();
+    virtio::init();
+    partition::init();
     // test_blk_device_read();
 }
 
+/// The name of the `idx`-th block device, e.g., `vda` for the first one.
+pub fn block_device_name(idx: usize) -> String {
+    format!("vd{}", (b'a' + idx as u8) as char)
+}
+
+/// Resolves the name of a block device to its canonical form, e.g., `vda1`.
+///
+/// Besides the canonical names, a device can be named after its serial number,
+/// either bare or as `virtio-<serial>` like in `/dev/disk/by-id`, followed by
+/// `-part<N>` for a partition. Names may be prefixed with `/dev/` or
+/// `/dev/disk/by-id/`.
+pub fn canonical_block_device_name(name: &str) -> Option<String> {
+    let name = name
+        .strip_prefix("/dev/disk/by-id/")
+        .or_else(|| name.strip_prefix("/dev/"))
+        .unwrap_or(name);
+    if lookup_canonical(name).is_some() {
+        return Some(String::from(name));
+    }
+
+    let id = name.strip_prefix("virtio-").unwrap_or(name);
+    let (serial, partition) = match id.rsplit_once("-part") {
+        Some((serial, partition)) => (serial, partition),
+        None => (id, ""),
+    };
+    let disks = BLOCK_DEVICES.get().unwrap().lock().clone();
+    let idx = disks
+        .iter()
+        .position(|disk| disk.serial().as_deref() == Some(serial))?;
+    let canonical = format!("{}{}", block_device_name(idx), partition);
+    lookup_canonical(&canonical).map(|_| canonical)
+}
+
+/// Finds a block device by any of its names.
+pub fn lookup_block_device(name: &str) -> Option<Arc<dyn BlockDevice>> {
+    lookup_canonical(&canonical_block_device_name(name)?)
+}
+
+fn lookup_canonical(name: &str) -> Option<Arc<dyn BlockDevice>> {
+    let block_devices = BLOCK_DEVICES.get().unwrap().lock();
+    if let Some(idx) = (0..block_devices.len()).find(|&idx| block_device_name(idx) == name) {
+        return Some(block_devices[idx].clone());
+    }
+    PARTITIONS
+        .get()
+        .unwrap()
+        .lock()
+        .iter()
+        .find(|partition| partition.name() == name)
+        .map(|partition| partition.clone() as Arc<dyn BlockDevice>)
+}
+
 fn test_blk_device_read() {
     let block_devices = BLOCK_DEVICES.get().unwrap().lock();
 
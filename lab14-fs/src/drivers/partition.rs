@@ -0,0 +1,194 @@
+//! MBR partitions, exposed as block devices of their own.
+
+use alloc::{format, string::String, sync::Arc, vec::Vec};
+use log::info;
+use ostd::mm::VmIo;
+use spin::{Mutex, Once};
+
+use crate::drivers::blk::{
+    BioRequest, BlockDevice, BlockStats, IoAccounting, IoDirection, SECTOR_SIZE, alloc_device_id,
+};
+
+const MBR_SIGNATURE_OFFSET: usize = 510;
+const MBR_SIGNATURE: u16 = 0xAA55;
+const PARTITION_TABLE_OFFSET: usize = 446;
+const PARTITION_ENTRY_SIZE: usize = 16;
+const NUM_PRIMARY_PARTITIONS: usize = 4;
+
+/// Partition types that describe a container rather than a volume.
+const EXTENDED_TYPES: [u8; 3] = [0x05, 0x0F, 0x85];
+/// The protective partition covering a GPT disk.
+const GPT_PROTECTIVE_TYPE: u8 = 0xEE;
+
+/// The partitions found on the block devices, named like `vda1`.
+pub static PARTITIONS: Once<Mutex<Vec<Arc<Partition>>>> = Once::new();
+
+pub struct Partition {
+    id: usize,
+    name: String,
+    disk: Arc<dyn BlockDevice>,
+    /// The first sector of the partition on the disk.
+    start: usize,
+    num_sectors: usize,
+    accounting: IoAccounting,
+}
+
+impl Partition {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The disk the partition is on.
+    pub fn disk(&self) -> &Arc<dyn BlockDevice> {
+        &self.disk
+    }
+
+    /// The first sector of the partition on the disk.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Translates the request to the disk and back.
+    fn forward(
+        &self,
+        req: &mut BioRequest,
+        direction: IoDirection,
+        submit: impl FnOnce(&mut BioRequest),
+    ) {
+        let index = req.index();
+        let in_range = self
+            .num_sectors
+            .saturating_sub(index)
+            .min(req.num_sectors());
+        if in_range < req.num_sectors() {
+            self.forward_partial(req, in_range, direction, submit);
+            return;
+        }
+
+        req.set_index(self.start + index);
+        let start = self.accounting.start(direction);
+        submit(req);
+        self.accounting.end(direction, req.num_sectors(), start);
+        req.set_index(index);
+    }
+
+    /// Forwards the first `in_range` sectors of a request running past the
+    /// end of the partition, e.g., a read of a whole block by the buffer
+    /// cache when the size is not a multiple of it. The sectors past the end
+    /// read as zeros and writes to them are dropped.
+    fn forward_partial(
+        &self,
+        req: &mut BioRequest,
+        in_range: usize,
+        direction: IoDirection,
+        submit: impl FnOnce(&mut BioRequest),
+    ) {
+        let mut partial = BioRequest::new(req.index(), in_range);
+        let mut sector = [0u8; SECTOR_SIZE];
+        if let IoDirection::Write = direction {
+            for (from, to) in req.data.iter().zip(partial.data.iter()) {
+                from.read_bytes(0, &mut sector).unwrap();
+                to.write_bytes(0, &sector).unwrap();
+            }
+        }
+
+        if in_range > 0 {
+            self.forward(&mut partial, direction, submit);
+        }
+
+        if let IoDirection::Read = direction {
+            for (idx, to) in req.data.iter().enumerate() {
+                match partial.data.get(idx) {
+                    Some(from) => from.read_bytes(0, &mut sector).unwrap(),
+                    None => sector.fill(0),
+                }
+                to.write_bytes(0, &sector).unwrap();
+            }
+        }
+    }
+}
+
+impl BlockDevice for Partition {
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn read_block(&self, req: &mut BioRequest) {
+        self.forward(req, IoDirection::Read, |req| self.disk.read_block(req));
+    }
+
+    fn write_block(&self, req: &mut BioRequest) {
+        self.forward(req, IoDirection::Write, |req| self.disk.write_block(req));
+    }
+
+    fn flush(&self) {
+        self.disk.flush();
+    }
+
+    fn num_sectors(&self) -> usize {
+        self.num_sectors
+    }
+
+    fn stats(&self) -> Option<BlockStats> {
+        Some(self.accounting.stats())
+    }
+}
+
+/// Scans the MBR partition table of every block device.
+pub(super) fn init() {
+    let mut partitions = Vec::new();
+    for (idx, disk) in super::BLOCK_DEVICES
+        .get()
+        .unwrap()
+        .lock()
+        .iter()
+        .enumerate()
+    {
+        let mbr = disk.read_one(0);
+        if u16::from_le_bytes([mbr[MBR_SIGNATURE_OFFSET], mbr[MBR_SIGNATURE_OFFSET + 1]])
+            != MBR_SIGNATURE
+        {
+            continue;
+        }
+
+        for i in 0..NUM_PRIMARY_PARTITIONS {
+            let entry =
+                &mbr[PARTITION_TABLE_OFFSET + i * PARTITION_ENTRY_SIZE..][..PARTITION_ENTRY_SIZE];
+            let status = entry[0];
+            let type_ = entry[4];
+            let start = u32::from_le_bytes(entry[8..12].try_into().unwrap()) as usize;
+            let num_sectors = u32::from_le_bytes(entry[12..16].try_into().unwrap()) as usize;
+            if type_ == 0 || EXTENDED_TYPES.contains(&type_) || type_ == GPT_PROTECTIVE_TYPE {
+                continue;
+            }
+            // The boot sector of a FAT volume carries the same signature, so
+            // the entries are checked to be sane before being trusted.
+            if (status != 0x00 && status != 0x80)
+                || num_sectors == 0
+                || start == 0
+                || start + num_sectors > disk.num_sectors()
+            {
+                continue;
+            }
+
+            let name = format!("{}{}", super::block_device_name(idx), i + 1);
+            info!(
+                "Partition {}: type {:#x}, sectors {}..{}",
+                name,
+                type_,
+                start,
+                start + num_sectors
+            );
+            let partition = Partition {
+                id: alloc_device_id(),
+                name,
+                disk: disk.clone(),
+                start,
+                num_sectors,
+                accounting: IoAccounting::new(),
+            };
+            partitions.push(Arc::new(partition));
+        }
+    }
+    PARTITIONS.call_once(|| Mutex::new(partitions));
+}
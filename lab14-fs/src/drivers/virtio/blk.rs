@@ -1,4 +1,5 @@
 use alloc::boxed::Box;
+use alloc::string::String;
 use alloc::vec::Vec;
 use log::{debug, error};
 use ostd::{
@@ -8,26 +9,37 @@ use ostd::{
 };
 
 use crate::drivers::{
-    blk::BioRequest,
+    blk::{BioRequest, BlockStats, IoAccounting, IoDirection},
     virtio::queue::{VirtqueueCoherentRequest, VirtqueueRequest, VirtqueueStreamRequest},
 };
 use crate::drivers::{
-    blk::BlockDevice,
+    blk::{BlockDevice, alloc_device_id},
     utils::DmaSliceAlloc,
-    virtio::{mmio::VirtioMmioTransport, queue::Virtqueue},
+    virtio::{VirtioDevice, mmio::VirtioMmioTransport, queue::Virtqueue},
 };
 
 pub struct VirtioBlkDevice {
+    id: usize,
     transport: VirtioMmioTransport,
     config: VirtioBlkConfig,
     request_queue: SpinLock<Virtqueue, LocalIrqDisabled>,
 
     request_alloc: SpinLock<DmaSliceAlloc<BlockReq, DmaCoherent>, LocalIrqDisabled>,
     resp_alloc: SpinLock<DmaSliceAlloc<BlockResp, DmaCoherent>, LocalIrqDisabled>,
+    /// The features negotiated with the device.
+    features: u64,
+    accounting: IoAccounting,
+    /// The serial number, asked once at probe time.
+    serial: Option<String>,
 }
 
+/// The device supports the flush command (VIRTIO_BLK_F_FLUSH).
+const VIRTIO_BLK_F_FLUSH: u64 = 1 << 9;
+/// The max length of the serial number returned by the get ID command.
+const VIRTIO_BLK_ID_BYTES: usize = 20;
+
 impl VirtioBlkDevice {
-    pub fn new(transport: VirtioMmioTransport) -> Self {
+    pub fn new(transport: VirtioMmioTransport, features: u64) -> Self {
         let queue = Virtqueue::new(0, &transport).unwrap();
         let request_dma = DmaCoherent::map(
             FrameAllocOptions::new().alloc_segment(1).unwrap().into(),
@@ -44,26 +56,35 @@ impl VirtioBlkDevice {
         let blk_config: VirtioBlkConfig = config_io_mem.read_val(0).unwrap();
 
         debug!("Virtio Block Device config: {:#?}", blk_config);
-
         transport.finish_init();
 
-        Self {
+        let mut device = Self {
+            id: alloc_device_id(),
             transport,
             request_queue: SpinLock::new(queue),
             request_alloc: SpinLock::new(DmaSliceAlloc::new(request_dma)),
             resp_alloc: SpinLock::new(DmaSliceAlloc::new(resp_dma)),
             config: blk_config,
-        }
+            features,
+            accounting: IoAccounting::new(),
+            serial: None,
+        };
+        device.serial = device.get_id();
+        device
     }
 }
 
-impl BlockDevice for VirtioBlkDevice {
-    fn read_block(&self, bio_request: &mut BioRequest) {
+impl VirtioBlkDevice {
+    /// Submits one request to the device and waits for its completion.
+    ///
+    /// `device_writable` tells whether the device writes the data sectors (reads)
+    /// or only reads them (writes). Returns whether the request succeeded.
+    fn submit(&self, type_: ReqType, bio_request: &BioRequest, device_writable: bool) -> bool {
         let req_dma = self.request_alloc.lock().alloc().unwrap();
         let resp_dma = self.resp_alloc.lock().alloc().unwrap();
 
         let req = BlockReq {
-            type_: ReqType::In as _,
+            type_: type_ as _,
             reserved: 0,
             sector: bio_request.index() as u64,
         };
@@ -78,8 +99,8 @@ impl BlockDevice for VirtioBlkDevice {
         requests.push(Box::new(VirtqueueCoherentRequest::from_dma_slice(
             &req_dma, false,
         )));
-        for data in bio_request.data_slices_mut().iter_mut() {
-            let stream_req = VirtqueueStreamRequest::from_dma_slice(data, true);
+        for data in bio_request.data.iter() {
+            let stream_req = VirtqueueStreamRequest::from_dma_slice(data, device_writable);
             requests.push(Box::new(stream_req));
         }
         requests.push(Box::new(VirtqueueCoherentRequest::from_dma_slice(
@@ -102,15 +123,88 @@ impl BlockDevice for VirtioBlkDevice {
         }
 
         queue.pop_finish_request();
+        drop(queue);
+        drop(requests);
 
         // Read response
         let resp_read: BlockResp = resp_dma.read_no_offset_val().unwrap();
-        if resp_read.status != RespStatus::Ok as u8 {
-            error!("Block device read error: {:?}", resp_read.status);
+        let succeeded = resp_read.status == RespStatus::Ok as u8;
+        if !succeeded {
+            error!(
+                "Block device request {:?} error: {:?}",
+                type_, resp_read.status
+            );
+        }
+
+        self.request_alloc.lock().dealloc(req_dma);
+        self.resp_alloc.lock().dealloc(resp_dma);
+        succeeded
+    }
+}
+
+impl BlockDevice for VirtioBlkDevice {
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn read_block(&self, bio_request: &mut BioRequest) {
+        let start = self.accounting.start(IoDirection::Read);
+        self.submit(ReqType::In, bio_request, true);
+        self.accounting
+            .end(IoDirection::Read, bio_request.num_sectors(), start);
+    }
+
+    fn write_block(&self, bio_request: &mut BioRequest) {
+        let start = self.accounting.start(IoDirection::Write);
+        self.submit(ReqType::Out, bio_request, false);
+        self.accounting
+            .end(IoDirection::Write, bio_request.num_sectors(), start);
+    }
+
+    fn flush(&self) {
+        // Without a volatile write cache, there is nothing to flush.
+        if self.features & VIRTIO_BLK_F_FLUSH != 0 {
+            self.submit(ReqType::Flush, &BioRequest::new(0, 0), false);
+        }
+    }
+
+    fn serial(&self) -> Option<String> {
+        self.serial.clone()
+    }
+
+    fn num_sectors(&self) -> usize {
+        self.config.capacity as usize
+    }
+
+    fn stats(&self) -> Option<BlockStats> {
+        Some(self.accounting.stats())
+    }
+}
+
+impl VirtioBlkDevice {
+    /// Asks the device for its serial number.
+    fn get_id(&self) -> Option<String> {
+        // The ID is written to the first 20 bytes of the data sector and is
+        // NUL-terminated only if shorter than that.
+        let bio_request = BioRequest::new(0, 1);
+        if !self.submit(ReqType::GetId, &bio_request, true) {
+            return None;
         }
+        let mut id = [0u8; VIRTIO_BLK_ID_BYTES];
+        bio_request.data[0].read_bytes(0, &mut id).unwrap();
+        let len = id.iter().position(|&c| c == 0).unwrap_or(id.len());
+        core::str::from_utf8(&id[..len]).ok().map(String::from)
+    }
+}
+
+impl VirtioDevice for VirtioBlkDevice {
+    fn transport(&self) -> &VirtioMmioTransport {
+        &self.transport
     }
 
-    fn write_block(&self, bio_request: &BioRequest) {}
+    fn features(&self) -> u64 {
+        self.features
+    }
 }
 
 #[repr(C)]
@@ -89,6 +89,12 @@ impl VirtioMmioTransport {
             .unwrap()
     }
 
+    pub fn vendor_id(&self) -> u32 {
+        self.layout_io_mem
+            .read_once::<u32>(offset_of!(VirtioMmioLayout, vendor_id))
+            .unwrap()
+    }
+
     pub fn layout_io_mem(&self) -> &IoMem {
         &self.layout_io_mem
     }
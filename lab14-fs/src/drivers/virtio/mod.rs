@@ -12,13 +12,27 @@ use ostd::{
     io::IoMem,
     mm::{PodOnce, VmIoOnce},
 };
+use spin::{Mutex, Once};
 
 use crate::drivers::virtio::{
     blk::VirtioBlkDevice,
     mmio::{VirtioMmioLayout, VirtioMmioTransport},
 };
 
+/// The virtio devices that are driven, in the order they were found.
+pub static VIRTIO_DEVICES: Once<Mutex<Vec<Arc<dyn VirtioDevice>>>> = Once::new();
+
+/// A device driven through a virtio transport.
+pub trait VirtioDevice: Send + Sync {
+    fn transport(&self) -> &VirtioMmioTransport;
+
+    /// The features negotiated with the device.
+    fn features(&self) -> u64;
+}
+
 pub fn init() {
+    VIRTIO_DEVICES.call_once(|| Mutex::new(Vec::new()));
+
     // We use device tree to initialize virtio devices.
     let device_tree = DEVICE_TREE.get().unwrap();
     let mmio_virtio_nodes = device_tree.all_nodes().filter(|node| {
@@ -107,13 +121,14 @@ pub fn init() {
 
         match device_id {
             2 => {
-                let blk_device = VirtioBlkDevice::new(transport);
+                let blk_device = Arc::new(VirtioBlkDevice::new(transport, features));
 
-                super::BLOCK_DEVICES
+                VIRTIO_DEVICES
                     .get()
                     .unwrap()
                     .lock()
-                    .push(Arc::new(blk_device));
+                    .push(blk_device.clone());
+                super::BLOCK_DEVICES.get().unwrap().lock().push(blk_device);
             }
             _ => unimplemented!(),
         }
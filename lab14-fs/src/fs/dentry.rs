@@ -0,0 +1,356 @@
+//! The dentry cache, which remembers the results of name lookups.
+
+use alloc::{
+    collections::btree_map::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use ostd::sync::{Mutex, RwMutex};
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::permission::check_sticky;
+use crate::fs::{Inode, InodeType};
+use crate::process::Credentials;
+
+/// The max length of a file name.
+pub const NAME_MAX: usize = 255;
+
+/// The max number of children cached in a directory before the unused
+/// ones are dropped.
+const MAX_CACHED_CHILDREN: usize = 256;
+
+type Children = BTreeMap<String, Option<Arc<Dentry>>>;
+
+/// A name in a directory of a filesystem, bound to its inode.
+///
+/// Every dentry keeps its parent alive, so `..` can be resolved on any
+/// filesystem, even one whose directories have no `..` entries.
+pub struct Dentry {
+    inode: Arc<dyn Inode>,
+    /// The name and the parent directory, which change on `rename`. The
+    /// parent is `None` for the root directory of the filesystem.
+    location: RwMutex<(String, Option<Arc<Dentry>>)>,
+    /// The looked up children. `None` records that the name does not exist.
+    /// Unused entries are dropped once there are `MAX_CACHED_CHILDREN`.
+    children: Mutex<Children>,
+}
+
+impl Dentry {
+    /// Creates the dentry of the root directory of a filesystem.
+    pub fn new_root(inode: Arc<dyn Inode>) -> Arc<Self> {
+        Self::new(String::from("/"), inode, None)
+    }
+
+    fn new(name: String, inode: Arc<dyn Inode>, parent: Option<Arc<Dentry>>) -> Arc<Self> {
+        Arc::new(Self {
+            inode,
+            location: RwMutex::new((name, parent)),
+            children: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    pub fn name(&self) -> String {
+        self.location.read().0.clone()
+    }
+
+    pub fn inode(&self) -> &Arc<dyn Inode> {
+        &self.inode
+    }
+
+    /// The parent directory, `None` for the root directory of the filesystem.
+    pub fn parent(&self) -> Option<Arc<Dentry>> {
+        self.location.read().1.clone()
+    }
+
+    /// Looks up `name` in this directory. `.` and `..` are not handled here.
+    pub fn lookup(self: &Arc<Self>, name: &str) -> Result<Arc<Dentry>> {
+        self.check_dir(name)?;
+        self.lookup_locked(&mut self.children.lock(), name)
+    }
+
+    /// Creates `name` in this directory. Fails with `EEXIST` if it exists.
+    pub fn create(self: &Arc<Self>, name: &str, type_: InodeType) -> Result<Arc<Dentry>> {
+        self.create_with(name, |dir| dir.create(name, type_))
+    }
+
+    /// Creates the device file `name` in this directory. Fails with `EEXIST`
+    /// if it exists.
+    pub fn mknod(self: &Arc<Self>, name: &str, type_: InodeType, rdev: u64) -> Result<Arc<Dentry>> {
+        self.create_with(name, |dir| dir.mknod(name, type_, rdev))
+    }
+
+    /// Creates the symbolic link `name` to `target` in this directory. Fails
+    /// with `EEXIST` if it exists.
+    pub fn symlink(self: &Arc<Self>, name: &str, target: &str) -> Result<Arc<Dentry>> {
+        self.create_with(name, |dir| dir.symlink(name, target))
+    }
+
+    /// Adds `name` to this directory, created in the filesystem by `create`.
+    fn create_with<F>(self: &Arc<Self>, name: &str, create: F) -> Result<Arc<Dentry>>
+    where
+        F: FnOnce(&Arc<dyn Inode>) -> Result<Arc<dyn Inode>>,
+    {
+        self.check_dir(name)?;
+
+        // Hold the lock so that no one else creates the same name meanwhile.
+        let mut children = self.children.lock();
+        if self.exists_locked(&mut children, name)? {
+            return Err(Error::new(Errno::EEXIST));
+        }
+
+        let inode = create(&self.inode)?;
+        let child = Self::new(name.to_string(), inode, Some(self.clone()));
+        // A filesystem may match names loosely (e.g., FAT ignores case), so
+        // the negative entries of this directory may have become stale.
+        children.retain(|_, child| child.is_some());
+        cache(&mut children, name, Some(child.clone()));
+        Ok(child)
+    }
+
+    /// Makes `name` in this directory another link to the inode of `old`.
+    pub fn link(self: &Arc<Self>, old: &Arc<Dentry>, name: &str) -> Result<Arc<Dentry>> {
+        self.check_dir(name)?;
+        if old.inode.typ() == InodeType::Directory {
+            return Err(Error::new(Errno::EPERM));
+        }
+
+        let mut children = self.children.lock();
+        if self.exists_locked(&mut children, name)? {
+            return Err(Error::new(Errno::EEXIST));
+        }
+
+        self.inode.link(&old.inode, name)?;
+        let child = Self::new(name.to_string(), old.inode.clone(), Some(self.clone()));
+        cache(&mut children, name, Some(child.clone()));
+        Ok(child)
+    }
+
+    /// Removes the non-directory `name` from this directory on behalf of
+    /// `credentials`.
+    pub fn unlink(self: &Arc<Self>, name: &str, credentials: &Credentials) -> Result<()> {
+        self.check_dir(name)?;
+
+        let mut children = self.children.lock();
+        let child = self.lookup_locked(&mut children, name)?;
+        if child.inode.typ() == InodeType::Directory {
+            return Err(Error::new(Errno::EISDIR));
+        }
+        check_sticky(&self.inode, &child.inode, credentials)?;
+
+        self.inode.unlink(name)?;
+        cache(&mut children, name, None);
+        Ok(())
+    }
+
+    /// Removes the empty directory `name` from this directory on behalf of
+    /// `credentials`.
+    pub fn rmdir(self: &Arc<Self>, name: &str, credentials: &Credentials) -> Result<()> {
+        self.check_dir(name)?;
+
+        let mut children = self.children.lock();
+        let child = self.lookup_locked(&mut children, name)?;
+        if child.inode.typ() != InodeType::Directory {
+            return Err(Error::new(Errno::ENOTDIR));
+        }
+        check_sticky(&self.inode, &child.inode, credentials)?;
+
+        self.inode.rmdir(name)?;
+        cache(&mut children, name, None);
+        // Nothing can be looked up in the removed directory anymore.
+        child.evict_all();
+        Ok(())
+    }
+
+    /// Moves `old_name` in this directory to `new_name` in `new_dir` on
+    /// behalf of `credentials`, replacing the file there if any unless
+    /// `noreplace` is set, in which case it fails with `EEXIST`.
+    pub fn rename(
+        self: &Arc<Self>,
+        old_name: &str,
+        new_dir: &Arc<Dentry>,
+        new_name: &str,
+        noreplace: bool,
+        credentials: &Credentials,
+    ) -> Result<()> {
+        self.check_dir(old_name)?;
+        new_dir.check_dir(new_name)?;
+
+        let check = |old_child: &Arc<Dentry>, new_child: Option<&Arc<Dentry>>| {
+            check_sticky(&self.inode, &old_child.inode, credentials)?;
+            if let Some(new_child) = new_child {
+                if noreplace {
+                    return Err(Error::new(Errno::EEXIST));
+                }
+                check_sticky(&new_dir.inode, &new_child.inode, credentials)?;
+            }
+            Ok(())
+        };
+        if Arc::ptr_eq(self, new_dir) {
+            let mut children = self.children.lock();
+            return self.rename_locked(old_name, new_dir, new_name, check, &mut children, None);
+        }
+        // Lock in address order so that a rename the other way cannot deadlock.
+        let (mut old_children, mut new_children) = if Arc::as_ptr(self) < Arc::as_ptr(new_dir) {
+            let old_children = self.children.lock();
+            (old_children, new_dir.children.lock())
+        } else {
+            let new_children = new_dir.children.lock();
+            (self.children.lock(), new_children)
+        };
+        self.rename_locked(
+            old_name,
+            new_dir,
+            new_name,
+            check,
+            &mut old_children,
+            Some(&mut new_children),
+        )
+    }
+
+    /// Drops the cached children of this directory and all of its descendants.
+    ///
+    /// Children keep their parents alive, so the tree of a filesystem is only
+    /// freed once this breaks the cycles, e.g., when it is unmounted.
+    pub fn evict_all(&self) {
+        let children: Vec<Arc<Dentry>> = core::mem::take(&mut *self.children.lock())
+            .into_values()
+            .flatten()
+            .collect();
+        for child in children {
+            child.evict_all();
+        }
+    }
+
+    /// Does the rename with the children of both directories locked.
+    /// `new_children` is `None` if both are this directory. `check` is given
+    /// the file moved and the one replaced, if any, and may refuse the rename.
+    fn rename_locked<F>(
+        self: &Arc<Self>,
+        old_name: &str,
+        new_dir: &Arc<Dentry>,
+        new_name: &str,
+        check: F,
+        old_children: &mut Children,
+        mut new_children: Option<&mut Children>,
+    ) -> Result<()>
+    where
+        F: FnOnce(&Arc<Dentry>, Option<&Arc<Dentry>>) -> Result<()>,
+    {
+        let old_child = self.lookup_locked(old_children, old_name)?;
+        let new_child = {
+            let children = match new_children.as_deref_mut() {
+                Some(children) => children,
+                None => &mut *old_children,
+            };
+            match new_dir.lookup_locked(children, new_name) {
+                Ok(new_child) => Some(new_child),
+                Err(err) if err.code == Errno::ENOENT => None,
+                Err(err) => return Err(err),
+            }
+        };
+
+        check(&old_child, new_child.as_ref())?;
+
+        let is_dir = old_child.inode.typ() == InodeType::Directory;
+        if let Some(new_child) = &new_child {
+            // Renaming a file to another link of it does nothing.
+            if Arc::ptr_eq(&old_child.inode, &new_child.inode) {
+                return Ok(());
+            }
+            match (is_dir, new_child.inode.typ() == InodeType::Directory) {
+                (true, false) => return Err(Error::new(Errno::ENOTDIR)),
+                (false, true) => return Err(Error::new(Errno::EISDIR)),
+                _ => {}
+            }
+        }
+        // A directory cannot be moved into itself.
+        if is_dir && new_dir.is_descendant_of(&old_child) {
+            return Err(Error::new(Errno::EINVAL));
+        }
+
+        self.inode.rename(old_name, &new_dir.inode, new_name)?;
+
+        cache(old_children, old_name, None);
+        let children = match new_children {
+            Some(children) => children,
+            None => old_children,
+        };
+        cache(children, new_name, Some(old_child.clone()));
+        *old_child.location.write() = (new_name.to_string(), Some(new_dir.clone()));
+        if let Some(new_child) = new_child {
+            new_child.evict_all();
+        }
+        Ok(())
+    }
+
+    /// Whether this dentry is `ancestor` or below it.
+    fn is_descendant_of(self: &Arc<Self>, ancestor: &Arc<Dentry>) -> bool {
+        let mut current = Some(self.clone());
+        while let Some(dentry) = current {
+            if Arc::ptr_eq(&dentry, ancestor) {
+                return true;
+            }
+            current = dentry.parent();
+        }
+        false
+    }
+
+    fn check_dir(&self, name: &str) -> Result<()> {
+        if self.inode.typ() != InodeType::Directory {
+            return Err(Error::new(Errno::ENOTDIR));
+        }
+        if name.len() > NAME_MAX {
+            return Err(Error::new(Errno::ENAMETOOLONG));
+        }
+        Ok(())
+    }
+
+    fn lookup_locked(self: &Arc<Self>, children: &mut Children, name: &str) -> Result<Arc<Dentry>> {
+        if let Some(child) = children.get(name) {
+            return child.clone().ok_or(Error::new(Errno::ENOENT));
+        }
+
+        match self.inode.lookup(name) {
+            Ok(inode) if !self.inode.caches_lookups() => {
+                Ok(Self::new(name.to_string(), inode, Some(self.clone())))
+            }
+            Ok(inode) => {
+                let child = Self::new(name.to_string(), inode, Some(self.clone()));
+                cache(children, name, Some(child.clone()));
+                Ok(child)
+            }
+            Err(err) if !self.inode.caches_lookups() => Err(err),
+            Err(err) if err.code == Errno::ENOENT => {
+                cache(children, name, None);
+                Err(err)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    fn exists_locked(self: &Arc<Self>, children: &mut Children, name: &str) -> Result<bool> {
+        match children.get(name) {
+            Some(child) => Ok(child.is_some()),
+            None => match self.inode.lookup(name) {
+                Ok(_) => Ok(true),
+                Err(err) if err.code == Errno::ENOENT => Ok(false),
+                Err(err) => Err(err),
+            },
+        }
+    }
+}
+
+/// Caches `child` as `name` in `children`, a negative entry if `None`.
+fn cache(children: &mut Children, name: &str, child: Option<Arc<Dentry>>) {
+    if children.len() >= MAX_CACHED_CHILDREN && !children.contains_key(name) {
+        // Drop the negative entries and the dentries held by nothing but
+        // the cache, which have no cached children either.
+        children.retain(|_, child| {
+            child
+                .as_ref()
+                .is_some_and(|child| Arc::strong_count(child) > 1)
+        });
+    }
+    children.insert(name.to_string(), child);
+}
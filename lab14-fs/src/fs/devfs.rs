@@ -0,0 +1,200 @@
+//! The device filesystem mounted on `/dev`.
+//!
+//! It holds a device file for each device registered when it is mounted.
+//! Opening one goes to the driver, see [`crate::fs::device`].
+
+use core::time::Duration;
+
+use alloc::{
+    collections::btree_map::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+};
+use ostd::{
+    mm::{VmReader, VmWriter},
+    timer::Jiffies,
+};
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::device::{self, DeviceType};
+use crate::fs::{
+    DirEntry, DirVisitor, FileSystem, FsStats, Inode, InodeMeta, InodeType, dentry::NAME_MAX,
+    visit_dir_entries,
+};
+
+const DEVFS_MAGIC: u64 = 0x1373;
+
+pub struct DevFS {
+    root: Arc<DevInode>,
+}
+
+impl DevFS {
+    pub fn new() -> Self {
+        let now = Jiffies::elapsed().as_duration();
+        let mut entries = BTreeMap::new();
+        for (idx, device) in device::devices().into_iter().enumerate() {
+            let mode = match device.typ() {
+                DeviceType::Char => 0o666,
+                DeviceType::Block => 0o660,
+            };
+            let inner = Inner::Device(device.typ(), device.id());
+            // The root directory gets 1.
+            let inode = DevInode::new(idx as u64 + 2, inner, mode, now);
+            entries.insert(device.name(), inode);
+        }
+
+        Self {
+            root: DevInode::new(1, Inner::Directory(entries), 0o755, now),
+        }
+    }
+}
+
+impl FileSystem for DevFS {
+    fn name(&self) -> &str {
+        "devtmpfs"
+    }
+
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+
+    fn stats(&self) -> FsStats {
+        FsStats {
+            magic: DEVFS_MAGIC,
+            block_size: ostd::mm::PAGE_SIZE,
+            total_blocks: 0,
+            free_blocks: 0,
+            avail_blocks: 0,
+            total_inodes: 0,
+            free_inodes: 0,
+            name_max: NAME_MAX,
+        }
+    }
+}
+
+enum Inner {
+    Directory(BTreeMap<String, Arc<DevInode>>),
+    /// A device file, holding the type and the number of the device.
+    Device(DeviceType, u64),
+}
+
+struct DevInode {
+    ino: u64,
+    inner: Inner,
+    mode: u16,
+    /// The time the filesystem was created, which the nodes never change.
+    time: Duration,
+}
+
+impl DevInode {
+    fn new(ino: u64, inner: Inner, mode: u16, time: Duration) -> Arc<Self> {
+        Arc::new(Self {
+            ino,
+            inner,
+            mode,
+            time,
+        })
+    }
+}
+
+impl Inode for DevInode {
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
+        let Inner::Directory(entries) = &self.inner else {
+            return Err(Error::new(Errno::ENOTDIR));
+        };
+        let inode = entries.get(name).ok_or(Error::new(Errno::ENOENT))?;
+        Ok(inode.clone())
+    }
+
+    fn create(&self, _name: &str, _type_: InodeType) -> Result<Arc<dyn Inode>> {
+        match self.inner {
+            Inner::Directory(_) => Err(Error::new(Errno::EPERM)),
+            Inner::Device(..) => Err(Error::new(Errno::ENOTDIR)),
+        }
+    }
+
+    fn readdir(&self, offset: usize, visitor: &mut DirVisitor) -> Result<usize> {
+        let Inner::Directory(entries) = &self.inner else {
+            return Err(Error::new(Errno::ENOTDIR));
+        };
+
+        // The root directory is its own parent within the filesystem.
+        let dots = [".", ".."].map(|name| DirEntry {
+            ino: self.ino,
+            type_: InodeType::Directory,
+            name: name.to_string(),
+        });
+        let entries = entries.iter().map(|(name, inode)| DirEntry {
+            ino: inode.ino,
+            type_: inode.typ(),
+            name: name.clone(),
+        });
+        Ok(visit_dir_entries(
+            // The tree never changes, so the index of an entry is stable.
+            dots.into_iter().chain(entries).enumerate(),
+            offset,
+            visitor,
+        ))
+    }
+
+    fn read_link(&self) -> Result<String> {
+        Err(Error::new(Errno::EINVAL))
+    }
+
+    fn write_link(&self, _target: &str) -> Result<()> {
+        Err(Error::new(Errno::EINVAL))
+    }
+
+    /// Device files are read through the driver, never here.
+    fn read_at(&self, _offset: usize, _writer: VmWriter) -> Result<usize> {
+        match self.inner {
+            Inner::Directory(_) => Err(Error::new(Errno::EISDIR)),
+            Inner::Device(..) => Err(Error::new(Errno::EINVAL)),
+        }
+    }
+
+    fn write_at(&self, _offset: usize, _reader: VmReader) -> Result<usize> {
+        match self.inner {
+            Inner::Directory(_) => Err(Error::new(Errno::EISDIR)),
+            Inner::Device(..) => Err(Error::new(Errno::EINVAL)),
+        }
+    }
+
+    fn metadata(&self) -> InodeMeta {
+        let (nlink, rdev) = match &self.inner {
+            Inner::Directory(_) => (2, 0),
+            Inner::Device(_, id) => (1, *id),
+        };
+        InodeMeta {
+            nlink,
+            rdev,
+            atime: self.time,
+            mtime: self.time,
+            ctime: self.time,
+            ..InodeMeta::new(self.ino, self.typ(), self.mode)
+        }
+    }
+
+    fn size(&self) -> usize {
+        0
+    }
+
+    fn typ(&self) -> InodeType {
+        match &self.inner {
+            Inner::Directory(_) => InodeType::Directory,
+            Inner::Device(type_, _) => type_.inode_type(),
+        }
+    }
+
+    fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn mode(&self) -> u16 {
+        self.mode
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
@@ -0,0 +1,154 @@
+//! The block devices and their partitions, read and written in bytes.
+//!
+//! The I/O goes through the buffer cache, which only caches the metadata of
+//! the filesystems. It is not kept coherent with the file data, which the
+//! filesystems read and write on the device directly, nor between a disk and
+//! its partitions, which are cached apart. So a mounted filesystem may not see
+//! the writes to its device, and the other way around.
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+use ostd::{
+    mm::{VmReader, VmWriter},
+    sync::Mutex,
+};
+
+use crate::drivers::blk::{BlockDevice, SECTOR_SIZE};
+use crate::drivers::partition::PARTITIONS;
+use crate::error::{Errno, Error, Result};
+use crate::fs::device::{Device, DeviceType, register};
+use crate::fs::util::buffer_cache::BUFFER_CACHE;
+use crate::fs::{FileLike, InodeMeta, InodeType, SeekFrom, makedev};
+
+/// The major number Linux gives to the first dynamically registered block
+/// driver, which is usually virtio-blk.
+const VIRTIO_BLK_MAJOR: u32 = 254;
+/// The minor numbers of each disk, the first for the disk itself and the
+/// others for its partitions.
+const MINORS_PER_DISK: u32 = 16;
+
+struct BlockDeviceNode {
+    name: String,
+    minor: u32,
+    blk_device: Arc<dyn BlockDevice>,
+}
+
+/// Registers the disks and their partitions.
+pub(super) fn init() {
+    let disks: Vec<Arc<dyn BlockDevice>> =
+        crate::drivers::BLOCK_DEVICES.get().unwrap().lock().clone();
+    let partitions = PARTITIONS.get().unwrap().lock().clone();
+    for (idx, disk) in disks.into_iter().enumerate() {
+        let name = crate::drivers::block_device_name(idx);
+        let minor = idx as u32 * MINORS_PER_DISK;
+        for partition in partitions.iter() {
+            let Some(Ok(number)) = partition
+                .name()
+                .strip_prefix(name.as_str())
+                .map(str::parse::<u32>)
+            else {
+                continue;
+            };
+            if number < MINORS_PER_DISK {
+                let _ = register(Arc::new(BlockDeviceNode {
+                    name: String::from(partition.name()),
+                    minor: minor + number,
+                    blk_device: partition.clone(),
+                }));
+            }
+        }
+        let _ = register(Arc::new(BlockDeviceNode {
+            name,
+            minor,
+            blk_device: disk,
+        }));
+    }
+}
+
+impl Device for BlockDeviceNode {
+    fn typ(&self) -> DeviceType {
+        DeviceType::Block
+    }
+
+    fn id(&self) -> u64 {
+        makedev(VIRTIO_BLK_MAJOR, self.minor)
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn open(&self) -> Result<Arc<dyn FileLike>> {
+        Ok(Arc::new(BlockFile {
+            blk_device: self.blk_device.clone(),
+            id: self.id(),
+            offset: Mutex::new(0),
+        }))
+    }
+}
+
+struct BlockFile {
+    blk_device: Arc<dyn BlockDevice>,
+    id: u64,
+    offset: Mutex<usize>,
+}
+
+impl BlockFile {
+    fn capacity(&self) -> usize {
+        self.blk_device.num_sectors() * SECTOR_SIZE
+    }
+}
+
+impl FileLike for BlockFile {
+    fn read(&self, writer: VmWriter) -> Result<usize> {
+        let mut offset = self.offset.lock();
+        let read_len = self.read_at(*offset, writer)?;
+        *offset += read_len;
+        Ok(read_len)
+    }
+
+    fn write(&self, reader: VmReader) -> Result<usize> {
+        let mut offset = self.offset.lock();
+        let write_len = self.write_at(*offset, reader)?;
+        *offset += write_len;
+        Ok(write_len)
+    }
+
+    fn read_at(&self, offset: usize, mut writer: VmWriter) -> Result<usize> {
+        let capacity = self.capacity();
+        if offset >= capacity {
+            return Ok(0);
+        }
+        let len = writer.avail().min(capacity - offset);
+        Ok(BUFFER_CACHE.read_to_writer(&self.blk_device, offset, len, &mut writer))
+    }
+
+    fn write_at(&self, offset: usize, mut reader: VmReader) -> Result<usize> {
+        let capacity = self.capacity();
+        if offset >= capacity && reader.has_remain() {
+            return Err(Error::new(Errno::ENOSPC));
+        }
+        let len = reader.remain().min(capacity.saturating_sub(offset));
+        Ok(BUFFER_CACHE.write_from_reader(&self.blk_device, offset, len, &mut reader))
+    }
+
+    fn metadata(&self) -> InodeMeta {
+        InodeMeta {
+            rdev: self.id,
+            blksize: SECTOR_SIZE,
+            ..InodeMeta::new(0, InodeType::BlockDevice, 0o660)
+        }
+    }
+
+    fn seek(&self, pos: SeekFrom) -> Result<usize> {
+        let mut offset = self.offset.lock();
+        let new_offset = match pos {
+            SeekFrom::Start(start) => Some(start),
+            SeekFrom::Current(delta) => offset.checked_add_signed(delta),
+            SeekFrom::End(delta) => self.capacity().checked_add_signed(delta),
+        };
+        *offset = new_offset
+            .filter(|&new_offset| new_offset <= isize::MAX as usize)
+            .ok_or(Error::new(Errno::EINVAL))?;
+        Ok(*offset)
+    }
+}
@@ -0,0 +1,92 @@
+//! The memory devices: `/dev/null`, `/dev/zero` and `/dev/full`.
+
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+};
+use ostd::mm::{VmReader, VmWriter};
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::device::{Device, DeviceType};
+use crate::fs::{FileLike, InodeMeta, InodeType, SeekFrom, makedev};
+
+const MEM_MAJOR: u32 = 1;
+
+#[derive(Debug, Clone, Copy)]
+pub enum MemDevice {
+    /// Reads nothing and takes all writes.
+    Null,
+    /// Reads zeros and takes all writes.
+    Zero,
+    /// Reads zeros and fails writes with `ENOSPC`.
+    Full,
+}
+
+impl Device for MemDevice {
+    fn typ(&self) -> DeviceType {
+        DeviceType::Char
+    }
+
+    fn id(&self) -> u64 {
+        let minor = match self {
+            MemDevice::Null => 3,
+            MemDevice::Zero => 5,
+            MemDevice::Full => 7,
+        };
+        makedev(MEM_MAJOR, minor)
+    }
+
+    fn name(&self) -> String {
+        match self {
+            MemDevice::Null => "null",
+            MemDevice::Zero => "zero",
+            MemDevice::Full => "full",
+        }
+        .to_string()
+    }
+
+    fn open(&self) -> Result<Arc<dyn FileLike>> {
+        Ok(Arc::new(*self))
+    }
+}
+
+impl FileLike for MemDevice {
+    fn read(&self, mut writer: VmWriter) -> Result<usize> {
+        if let MemDevice::Null = self {
+            return Ok(0);
+        }
+        let len = writer.avail();
+        writer
+            .fill_zeros(len)
+            .map_err(|_| Error::new(Errno::EFAULT))
+    }
+
+    fn write(&self, mut reader: VmReader) -> Result<usize> {
+        if let MemDevice::Full = self {
+            return Err(Error::new(Errno::ENOSPC));
+        }
+        let len = reader.remain();
+        reader.skip(len);
+        Ok(len)
+    }
+
+    fn read_at(&self, _offset: usize, writer: VmWriter) -> Result<usize> {
+        self.read(writer)
+    }
+
+    fn write_at(&self, _offset: usize, reader: VmReader) -> Result<usize> {
+        self.write(reader)
+    }
+
+    fn metadata(&self) -> InodeMeta {
+        InodeMeta {
+            rdev: self.id(),
+            ..InodeMeta::new(0, InodeType::CharDevice, 0o666)
+        }
+    }
+
+    /// The offset stays at zero, as on Linux.
+    fn seek(&self, _pos: SeekFrom) -> Result<usize> {
+        Ok(0)
+    }
+}
@@ -0,0 +1,181 @@
+//! The character and block devices, registered by their device numbers.
+//!
+//! Opening a device file looks up the driver registered for its type and
+//! device number, and reads and writes go to the file the driver opens.
+
+mod block;
+mod mem;
+mod tty;
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use alloc::{collections::btree_map::BTreeMap, string::String, sync::Arc, vec::Vec};
+use ostd::{
+    mm::{VmReader, VmWriter},
+    sync::RwMutex,
+};
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::device::{mem::MemDevice, tty::Terminal};
+use crate::fs::{FileLike, Inode, InodeMeta, InodeType, OpenFlags, Path, SeekFrom};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DeviceType {
+    Char,
+    Block,
+}
+
+impl DeviceType {
+    /// The type of the device files of this type of device.
+    pub fn inode_type(&self) -> InodeType {
+        match self {
+            DeviceType::Char => InodeType::CharDevice,
+            DeviceType::Block => InodeType::BlockDevice,
+        }
+    }
+
+    pub fn from_inode_type(type_: InodeType) -> Option<Self> {
+        match type_ {
+            InodeType::CharDevice => Some(DeviceType::Char),
+            InodeType::BlockDevice => Some(DeviceType::Block),
+            _ => None,
+        }
+    }
+}
+
+pub trait Device: Send + Sync {
+    fn typ(&self) -> DeviceType;
+
+    /// The device number, as made by [`makedev`](crate::fs::makedev).
+    fn id(&self) -> u64;
+
+    /// The name of the device file in `/dev`.
+    fn name(&self) -> String;
+
+    /// Opens the device for I/O.
+    fn open(&self) -> Result<Arc<dyn FileLike>>;
+}
+
+static DEVICES: RwMutex<BTreeMap<(DeviceType, u64), Arc<dyn Device>>> =
+    RwMutex::new(BTreeMap::new());
+
+/// Registers the built-in devices and the block devices.
+pub fn init() {
+    let devices: [Arc<dyn Device>; 5] = [
+        Arc::new(MemDevice::Null),
+        Arc::new(MemDevice::Zero),
+        Arc::new(MemDevice::Full),
+        Arc::new(Terminal::Console),
+        Arc::new(Terminal::Tty),
+    ];
+    for device in devices {
+        register(device).unwrap();
+    }
+    block::init();
+}
+
+/// Makes `device` the one opened through its type and device number. Fails
+/// with `EBUSY` if another device has them.
+pub fn register(device: Arc<dyn Device>) -> Result<()> {
+    let mut devices = DEVICES.write();
+    let key = (device.typ(), device.id());
+    if devices.contains_key(&key) {
+        return Err(Error::new(Errno::EBUSY));
+    }
+    devices.insert(key, device);
+    Ok(())
+}
+
+pub fn unregister(type_: DeviceType, id: u64) -> Option<Arc<dyn Device>> {
+    DEVICES.write().remove(&(type_, id))
+}
+
+pub fn get(type_: DeviceType, id: u64) -> Option<Arc<dyn Device>> {
+    DEVICES.read().get(&(type_, id)).cloned()
+}
+
+/// All the registered devices, ordered by type and device number.
+pub fn devices() -> Vec<Arc<dyn Device>> {
+    DEVICES.read().values().cloned().collect()
+}
+
+/// A device opened through a device file.
+///
+/// The driver does the I/O, while the attributes and the location are those
+/// of the device file.
+pub struct DeviceFile {
+    path: Path,
+    file: Arc<dyn FileLike>,
+    flags: AtomicU32,
+}
+
+impl DeviceFile {
+    /// Opens the device that the device file at `path` stands for. Fails with
+    /// `ENXIO` if no driver has registered it.
+    pub fn open(path: Path, flags: OpenFlags) -> Result<Self> {
+        let meta = path.inode().metadata();
+        let device = DeviceType::from_inode_type(meta.type_)
+            .and_then(|type_| get(type_, meta.rdev))
+            .ok_or(Error::new(Errno::ENXIO))?;
+        Ok(Self {
+            file: device.open()?,
+            path,
+            flags: AtomicU32::new((flags - OpenFlags::CREATION_FLAGS).bits()),
+        })
+    }
+}
+
+impl FileLike for DeviceFile {
+    fn read(&self, writer: VmWriter) -> Result<usize> {
+        if !self.status_flags().is_readable() {
+            return Err(Error::new(Errno::EBADF));
+        }
+        self.file.read(writer)
+    }
+
+    fn write(&self, reader: VmReader) -> Result<usize> {
+        if !self.status_flags().is_writable() {
+            return Err(Error::new(Errno::EBADF));
+        }
+        self.file.write(reader)
+    }
+
+    fn read_at(&self, offset: usize, writer: VmWriter) -> Result<usize> {
+        if !self.status_flags().is_readable() {
+            return Err(Error::new(Errno::EBADF));
+        }
+        self.file.read_at(offset, writer)
+    }
+
+    fn write_at(&self, offset: usize, reader: VmReader) -> Result<usize> {
+        if !self.status_flags().is_writable() {
+            return Err(Error::new(Errno::EBADF));
+        }
+        self.file.write_at(offset, reader)
+    }
+
+    fn metadata(&self) -> InodeMeta {
+        self.path.inode().metadata()
+    }
+
+    fn seek(&self, pos: SeekFrom) -> Result<usize> {
+        self.file.seek(pos)
+    }
+
+    fn as_inode(&self) -> Option<Arc<dyn Inode>> {
+        Some(self.path.inode().clone())
+    }
+
+    fn as_path(&self) -> Option<Path> {
+        Some(self.path.clone())
+    }
+
+    fn status_flags(&self) -> OpenFlags {
+        OpenFlags::from_bits_truncate(self.flags.load(Ordering::Relaxed))
+    }
+
+    fn set_status_flags(&self, flags: OpenFlags) {
+        let flags = self.status_flags().with_status_flags(flags);
+        self.flags.store(flags.bits(), Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,67 @@
+//! The terminal devices: `/dev/console` and `/dev/tty`.
+//!
+//! There is a single terminal, the console, so `/dev/tty`, the controlling
+//! terminal of the process, is always the console as well.
+
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+};
+use ostd::mm::{VmReader, VmWriter};
+
+use crate::error::Result;
+use crate::fs::device::{Device, DeviceType};
+use crate::fs::file::{CONSOLE_MAJOR, CONSOLE_MINOR, read_console, write_console};
+use crate::fs::{FileLike, InodeMeta, InodeType, makedev};
+
+const TTY_MAJOR: u32 = 5;
+const TTY_MINOR: u32 = 0;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Terminal {
+    Console,
+    Tty,
+}
+
+impl Device for Terminal {
+    fn typ(&self) -> DeviceType {
+        DeviceType::Char
+    }
+
+    fn id(&self) -> u64 {
+        match self {
+            Terminal::Console => makedev(CONSOLE_MAJOR, CONSOLE_MINOR),
+            Terminal::Tty => makedev(TTY_MAJOR, TTY_MINOR),
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Terminal::Console => "console",
+            Terminal::Tty => "tty",
+        }
+        .to_string()
+    }
+
+    fn open(&self) -> Result<Arc<dyn FileLike>> {
+        Ok(Arc::new(*self))
+    }
+}
+
+impl FileLike for Terminal {
+    fn read(&self, writer: VmWriter) -> Result<usize> {
+        read_console(writer)
+    }
+
+    fn write(&self, reader: VmReader) -> Result<usize> {
+        write_console(reader)
+    }
+
+    fn metadata(&self) -> InodeMeta {
+        InodeMeta {
+            rdev: self.id(),
+            blksize: 1024,
+            ..InodeMeta::new(0, InodeType::CharDevice, 0o620)
+        }
+    }
+}
@@ -27,17 +27,25 @@ impl BlockGroup {
     pub fn inode_table_start_bid(&self) -> Ext2Bid {
         self.inode_table_start_bid.into()
     }
+
+    pub fn block_bitmap_bid(&self) -> Ext2Bid {
+        self.bitmap_start_bid.into()
+    }
+
+    pub fn inode_bitmap_bid(&self) -> Ext2Bid {
+        self.inode_start_bid.into()
+    }
 }
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Pod)]
 pub(super) struct RawGroupDescriptor {
-    block_bitmap: u32,
-    inode_bitmap: u32,
-    inode_table: u32,
-    free_blocks_count: u16,
-    free_inodes_count: u16,
-    dirs_count: u16,
+    pub block_bitmap: u32,
+    pub inode_bitmap: u32,
+    pub inode_table: u32,
+    pub free_blocks_count: u16,
+    pub free_inodes_count: u16,
+    pub dirs_count: u16,
     pad: u16,
     reserved: [u32; 3],
 }
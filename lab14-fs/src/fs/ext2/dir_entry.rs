@@ -1,5 +1,3 @@
-use core::ffi::CStr;
-
 use alloc::string::{String, ToString};
 use ostd::Pod;
 
@@ -15,7 +13,23 @@ pub struct Ext2DirEntry {
     name: [u8; MAX_NAME_LEN],
 }
 
+/// The size of an entry without its name.
+const HEADER_LEN: usize = 8;
+
 impl Ext2DirEntry {
+    /// An entry naming inode `ino`, taking no more space than it needs.
+    pub fn new(ino: u32, name: &str, type_: u8) -> Self {
+        let mut entry = Self {
+            ino,
+            name_len: name.len() as u8,
+            type_,
+            ..Default::default()
+        };
+        entry.name[..name.len()].copy_from_slice(name.as_bytes());
+        entry.record_len = entry.min_length();
+        entry
+    }
+
     pub fn inode(&self) -> u32 {
         self.ino
     }
@@ -24,15 +38,33 @@ impl Ext2DirEntry {
         self.record_len
     }
 
+    pub fn set_length(&mut self, record_len: u16) {
+        self.record_len = record_len;
+    }
+
+    /// The space the entry needs, which is 4-byte aligned.
+    pub fn min_length(&self) -> u16 {
+        (HEADER_LEN + self.name_len as usize).next_multiple_of(4) as u16
+    }
+
     pub fn name_length(&self) -> u8 {
         self.name_len
     }
 
+    /// The file type recorded in the entry, zero if the filesystem does not
+    /// record file types in directories.
+    pub fn file_type(&self) -> u8 {
+        self.type_
+    }
+
     pub fn name(&self) -> String {
-        CStr::from_bytes_until_nul(&self.name)
-            .unwrap()
-            .to_string_lossy()
-            .to_string()
+        String::from_utf8_lossy(&self.name[..self.name_len as usize]).to_string()
+    }
+
+    /// The bytes stored on disk, which stop at the end of the name so that
+    /// the next entry is not overwritten.
+    pub fn as_disk_bytes(&self) -> &[u8] {
+        &self.as_bytes()[..HEADER_LEN + self.name_len as usize]
     }
 }
 
@@ -1,18 +1,29 @@
-#![expect(unused_variables)]
+use core::time::Duration;
 
 use alloc::{
+    collections::btree_map::BTreeMap,
     sync::{Arc, Weak},
     vec::Vec,
 };
 use log::debug;
-use ostd::Pod;
+use ostd::{
+    Pod,
+    mm::{Frame, PAGE_SIZE, io_util::HasVmReaderWriter},
+    sync::{Mutex, RwMutex},
+};
 
 use crate::{
     drivers::blk::SECTOR_SIZE,
+    error::{Errno, Error, Result},
     fs::{
-        InodeType,
+        DirEntry, DirVisitor, FileSystem, InodeMeta, InodeType, dev_major_minor,
         ext2::{Ext2Bid, Ext2Fs, dir_entry::Ext2DirEntry},
-        util::sector_ptr::SectorPtr,
+        makedev,
+        util::{
+            buffer_cache::BUFFER_CACHE,
+            page_cache::{PageCache, PageCacheBackend},
+            sector_ptr::SectorPtr,
+        },
     },
 };
 
@@ -25,11 +36,22 @@ pub struct Inode {
     block_group_idx: usize,
     inner: Inner,
     fs: Weak<Ext2Fs>,
+    /// Serializes the changes to the size and the block pointers.
+    lock: Mutex<()>,
+    /// Held by write-back, and exclusively while blocks are freed, so that no
+    /// page is written to a block that may belong to another file already.
+    /// Write-back may run with `lock` held, when a page allocation shrinks
+    /// the page caches, so it cannot take `lock` itself.
+    truncate_lock: RwMutex<()>,
 }
 
 enum Inner {
-    File,
-    Directory(Vec<Ext2DirEntry>),
+    File(Arc<PageCache>),
+    /// The entries, by their byte offsets in the directory.
+    Directory(RwMutex<BTreeMap<usize, Ext2DirEntry>>),
+    SymbolLink,
+    /// Device files and named pipes, which have no content.
+    Special,
 }
 
 impl Inode {
@@ -38,70 +60,238 @@ impl Inode {
         inode_id: u32,
         block_group_idx: usize,
         fs: Weak<Ext2Fs>,
-    ) -> Arc<Self> {
+    ) -> Result<Arc<Self>> {
         let raw_inode: RawInode = sector_ptr.read();
 
         let type_ = match raw_inode.mode & 0xF000 {
             0x4000 => InodeType::Directory,
             0x8000 => InodeType::File,
             0xA000 => InodeType::SymbolLink,
-            _ => panic!("Unsupported inode type"),
+            0x2000 => InodeType::CharDevice,
+            0x6000 => InodeType::BlockDevice,
+            0x1000 => InodeType::NamedPipe,
+            // The inode is corrupted.
+            _ => return Err(Error::new(Errno::EIO)),
         };
 
         debug!("Inode {} type: {:?}", inode_id, type_);
         debug!("Raw inode data: {:#x?}", raw_inode);
 
-        let inner = match type_ {
-            InodeType::Directory => {
-                Inner::Directory(read_directory(type_, &raw_inode, fs.clone()).unwrap())
+        let entries = match type_ {
+            InodeType::Directory => read_directory(&raw_inode, &fs),
+            _ => BTreeMap::new(),
+        };
+
+        Ok(Arc::new_cyclic(|weak_self: &Weak<Inode>| {
+            let inner = match type_ {
+                InodeType::Directory => Inner::Directory(RwMutex::new(entries)),
+                InodeType::File => {
+                    let backend: Weak<dyn PageCacheBackend> = weak_self.clone();
+                    Inner::File(PageCache::with_backend(backend))
+                }
+                InodeType::SymbolLink => Inner::SymbolLink,
+                InodeType::CharDevice | InodeType::BlockDevice | InodeType::NamedPipe => {
+                    Inner::Special
+                }
+            };
+
+            Inode {
+                inode_id,
+                type_,
+                block_group_idx,
+                inner,
+                fs,
+                sector_ptr,
+                lock: Mutex::new(()),
+                truncate_lock: RwMutex::new(()),
             }
-            InodeType::File | InodeType::SymbolLink => Inner::File,
+        }))
+    }
+
+    /// Returns the number of bytes from `offset` up to `offset + len` that are
+    /// backed by allocated blocks.
+    fn allocated_len(&self, raw_inode: &RawInode, offset: usize, len: usize) -> usize {
+        let fs = self.fs.upgrade().expect("Filesystem has been dropped");
+        let block_size = fs.block_size;
+
+        let mut pos = offset;
+        while pos < offset + len && raw_inode.block_ptrs.get(&fs, pos / block_size).is_some() {
+            pos = (pos / block_size + 1) * block_size;
+        }
+        pos.min(offset + len) - offset
+    }
+
+    /// Allocates the missing blocks backing `offset..offset + len`. On failure,
+    /// the blocks allocated so far are kept.
+    fn allocate_blocks(&self, raw_inode: &mut RawInode, offset: usize, len: usize) -> Result<()> {
+        let fs = self.fs.upgrade().expect("Filesystem has been dropped");
+        let sectors_per_block = (fs.block_size / SECTOR_SIZE) as u32;
+
+        let mut result = Ok(());
+        for block_index in offset / fs.block_size..(offset + len).div_ceil(fs.block_size) {
+            if raw_inode.block_ptrs.get(&fs, block_index).is_some() {
+                continue;
+            }
+            let allocated = fs.alloc_data_block().and_then(|bid| {
+                raw_inode
+                    .block_ptrs
+                    .set(&fs, block_index, bid)
+                    .inspect_err(|_| fs.free_block(bid))
+            });
+            match allocated {
+                Ok(indirect) => raw_inode.blocks_count += (indirect as u32 + 1) * sectors_per_block,
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            }
+        }
+        self.sector_ptr.write(raw_inode);
+        result
+    }
+
+    /// Adds `entry` to this directory, in the free space of an entry if one
+    /// has enough, or else in a new block.
+    fn add_entry(&self, mut entry: Ext2DirEntry) -> Result<()> {
+        let Inner::Directory(ref entries) = self.inner else {
+            return Err(Error::new(Errno::ENOTDIR));
         };
+        let fs = self.fs.upgrade().expect("Filesystem has been dropped");
+        let block_size = fs.block_size;
+        let needed = entry.min_length();
+
+        let _guard = self.lock.lock();
+        let mut raw_inode: RawInode = self.sector_ptr.read();
+        for block_index in 0..raw_inode.size() / block_size {
+            let Some(bid) = raw_inode.block_ptrs.get(&fs, block_index) else {
+                continue;
+            };
+            let block_offset = fs.bid_to_offset(bid);
+
+            let mut offset = 0;
+            while offset < block_size {
+                let mut old: Ext2DirEntry =
+                    BUFFER_CACHE.read_val(&fs.blk_device, block_offset + offset);
+                if old.length() == 0 {
+                    break;
+                }
 
-        let inode = Arc::new(Inode {
-            inode_id,
-            type_,
-            block_group_idx,
-            inner,
-            fs,
-            sector_ptr,
-        });
-        inode
+                // An unused entry is free as a whole.
+                let used = if old.inode() == 0 {
+                    0
+                } else {
+                    old.min_length()
+                };
+                if old.length() >= used + needed {
+                    entry.set_length(old.length() - used);
+                    if used != 0 {
+                        old.set_length(used);
+                        BUFFER_CACHE.write_bytes(
+                            &fs.blk_device,
+                            block_offset + offset,
+                            old.as_disk_bytes(),
+                        );
+                    }
+                    BUFFER_CACHE.write_bytes(
+                        &fs.blk_device,
+                        block_offset + offset + used as usize,
+                        entry.as_disk_bytes(),
+                    );
+                    let pos = block_index * block_size + offset + used as usize;
+                    entries.write().insert(pos, entry);
+                    return Ok(());
+                }
+                offset += old.length() as usize;
+            }
+        }
+
+        // The entry takes the whole of the new block.
+        let size = raw_inode.size();
+        self.allocate_blocks(&mut raw_inode, size, block_size)?;
+        let bid = raw_inode.block_ptrs.get(&fs, size / block_size).unwrap();
+        entry.set_length(block_size as u16);
+        BUFFER_CACHE.write_bytes(&fs.blk_device, fs.bid_to_offset(bid), entry.as_disk_bytes());
+        raw_inode.set_size(size + block_size);
+        self.sector_ptr.write(&raw_inode);
+        entries.write().insert(size, entry);
+        Ok(())
     }
 }
 
-fn read_directory(
-    type_: InodeType,
-    raw_inode: &RawInode,
-    fs: Weak<Ext2Fs>,
-) -> Option<Vec<Ext2DirEntry>> {
-    if type_ != InodeType::Directory {
-        return None;
+impl PageCacheBackend for Inode {
+    fn read_page(&self, idx: usize, frame: &Frame<()>) -> Result<()> {
+        let raw_inode: RawInode = self.sector_ptr.read();
+        let fs = self.fs.upgrade().expect("Filesystem has been dropped");
+        let block_size = fs.block_size;
+        let blocks_per_page = PAGE_SIZE / block_size;
+
+        for i in 0..blocks_per_page {
+            // Unallocated blocks stay zeroed.
+            let Some(bid) = raw_inode.block_ptrs.get(&fs, idx * blocks_per_page + i) else {
+                continue;
+            };
+            let mut writer = frame.writer().to_fallible();
+            writer.skip(i * block_size).limit(block_size);
+            fs.blk_device.read_to_vm_writer(
+                fs.bid_to_sector(bid),
+                block_size / SECTOR_SIZE,
+                &mut writer,
+            );
+        }
+        Ok(())
     }
 
-    // Read directory entries
-    let mut dir_entries = Vec::new();
-    for &block_ptr in &raw_inode.block_ptrs.direct_pointers {
-        if block_ptr.0 == 0 {
-            continue;
+    fn write_page(&self, idx: usize, frame: &Frame<()>) -> Result<()> {
+        let _guard = self.truncate_lock.read();
+        let raw_inode: RawInode = self.sector_ptr.read();
+        let fs = self.fs.upgrade().expect("Filesystem has been dropped");
+        fs.mark_dirty();
+        let block_size = fs.block_size;
+        let blocks_per_page = PAGE_SIZE / block_size;
+
+        for i in 0..blocks_per_page {
+            let Some(bid) = raw_inode.block_ptrs.get(&fs, idx * blocks_per_page + i) else {
+                continue;
+            };
+            let mut reader = frame.reader().to_fallible();
+            reader.skip(i * block_size).limit(block_size);
+            fs.blk_device.write_from_vm_reader(
+                fs.bid_to_sector(bid),
+                block_size / SECTOR_SIZE,
+                &mut reader,
+            );
         }
+        Ok(())
+    }
+}
+
+fn read_directory(raw_inode: &RawInode, fs: &Weak<Ext2Fs>) -> BTreeMap<usize, Ext2DirEntry> {
+    let fs = fs.upgrade().expect("Filesystem has been dropped");
+    let block_size = fs.block_size;
 
-        let fs = fs.upgrade().expect("Filesystem has been dropped");
-        let block_size = fs.block_size as usize;
-        let sector = fs.bid_to_sector(block_ptr);
+    // Read directory entries
+    let mut dir_entries = BTreeMap::new();
+    for block_index in 0..raw_inode.size().div_ceil(block_size) {
+        let Some(block_ptr) = raw_inode.block_ptrs.get(&fs, block_index) else {
+            continue;
+        };
+        let block_offset = fs.bid_to_offset(block_ptr);
 
         let mut offset = 0;
         while offset < block_size {
-            let dir_entry: Ext2DirEntry = fs
-                .blk_device
-                .read_val_offset(sector + offset / SECTOR_SIZE, offset % SECTOR_SIZE);
-
-            if dir_entry.inode() == 0 {
+            let dir_entry: Ext2DirEntry =
+                BUFFER_CACHE.read_val(&fs.blk_device, block_offset + offset);
+            if dir_entry.length() == 0 {
                 break;
             }
-
+            let pos = block_index * block_size + offset;
             offset += dir_entry.length() as usize;
-            dir_entries.push(dir_entry);
+
+            // Unused entry
+            if dir_entry.inode() == 0 {
+                continue;
+            }
+            dir_entries.insert(pos, dir_entry);
 
             debug!(
                 "Dir Entry: inode={}, rec_len={}, name_len={}, name={}",
@@ -113,9 +303,17 @@ fn read_directory(
         }
     }
 
-    Some(dir_entries)
+    dir_entries
 }
 
+/// The file types recorded in directory entries.
+const EXT2_FT_REG_FILE: u8 = 1;
+const EXT2_FT_DIR: u8 = 2;
+const EXT2_FT_CHRDEV: u8 = 3;
+const EXT2_FT_BLKDEV: u8 = 4;
+const EXT2_FT_FIFO: u8 = 5;
+const EXT2_FT_SYMLINK: u8 = 7;
+
 impl super::super::Inode for Inode {
     fn lookup(&self, name: &str) -> crate::error::Result<alloc::sync::Arc<dyn crate::fs::Inode>> {
         if self.type_ != InodeType::Directory {
@@ -123,7 +321,7 @@ impl super::super::Inode for Inode {
         }
 
         if let Inner::Directory(ref entries) = self.inner {
-            for entry in entries {
+            for entry in entries.read().values() {
                 if entry.name() == name {
                     let fs = self.fs.upgrade().expect("Filesystem has been dropped");
                     let inode = fs.lookup_inode(entry.inode())?;
@@ -134,20 +332,118 @@ impl super::super::Inode for Inode {
         Err(crate::error::Error::new(crate::error::Errno::ENOENT))
     }
 
-    fn create(
-        &self,
-        name: &str,
-        type_: InodeType,
-    ) -> crate::error::Result<alloc::sync::Arc<dyn crate::fs::Inode>> {
-        todo!()
+    /// Only regular files can be created for now, directories and symbolic
+    /// links need blocks allocated for their content.
+    fn create(&self, name: &str, type_: InodeType) -> Result<Arc<dyn crate::fs::Inode>> {
+        match type_ {
+            InodeType::File => self.mknod(name, type_, 0),
+            _ => Err(Error::new(Errno::EPERM)),
+        }
+    }
+
+    fn mknod(&self, name: &str, type_: InodeType, rdev: u64) -> Result<Arc<dyn crate::fs::Inode>> {
+        if self.type_ != InodeType::Directory {
+            return Err(Error::new(Errno::ENOTDIR));
+        }
+        let (mode, file_type) = match type_ {
+            InodeType::File => (0x8000, EXT2_FT_REG_FILE),
+            InodeType::CharDevice => (0x2000, EXT2_FT_CHRDEV),
+            InodeType::BlockDevice => (0x6000, EXT2_FT_BLKDEV),
+            _ => return Err(Error::new(Errno::EINVAL)),
+        };
+
+        let fs = self.fs.upgrade().expect("Filesystem has been dropped");
+        // There is no wall clock, so the times stay at the epoch.
+        let mut raw_inode = RawInode {
+            mode: mode | 0o644,
+            hard_links: 1,
+            ..Default::default()
+        };
+        if type_ != InodeType::File {
+            raw_inode.block_ptrs.set_device(rdev);
+        }
+        let inode = fs.alloc_inode(&raw_inode)?;
+
+        let file_type = if fs.has_file_types() { file_type } else { 0 };
+        let entry = Ext2DirEntry::new(inode.inode_id, name, file_type);
+        if let Err(err) = self.add_entry(entry) {
+            fs.free_inode(inode.inode_id);
+            return Err(err);
+        }
+        Ok(inode)
+    }
+
+    fn readdir(&self, offset: usize, visitor: &mut DirVisitor) -> Result<usize> {
+        let Inner::Directory(ref entries) = self.inner else {
+            return Err(Error::new(Errno::ENOTDIR));
+        };
+
+        let fs = self.fs.upgrade().expect("Filesystem has been dropped");
+        // An entry is at its byte offset in the directory, which it keeps
+        // until it is removed.
+        let mut next = offset;
+        for (&pos, entry) in entries.read().range(offset..) {
+            let type_ = match entry.file_type() {
+                EXT2_FT_REG_FILE => InodeType::File,
+                EXT2_FT_DIR => InodeType::Directory,
+                EXT2_FT_CHRDEV => InodeType::CharDevice,
+                EXT2_FT_BLKDEV => InodeType::BlockDevice,
+                EXT2_FT_FIFO => InodeType::NamedPipe,
+                EXT2_FT_SYMLINK => InodeType::SymbolLink,
+                // The type is not recorded in the entry.
+                _ => fs.lookup_inode(entry.inode())?.type_,
+            };
+            let entry = DirEntry {
+                ino: entry.inode() as u64,
+                type_,
+                name: entry.name(),
+            };
+            if !visitor(entry, pos + 1) {
+                break;
+            }
+            next = pos + 1;
+        }
+        Ok(next)
     }
 
     fn read_link(&self) -> crate::error::Result<alloc::string::String> {
-        todo!()
+        if self.type_ != InodeType::SymbolLink {
+            return Err(Error::new(Errno::EINVAL));
+        }
+
+        let raw_inode: RawInode = self.sector_ptr.read();
+        let fs = self.fs.upgrade().expect("Filesystem has been dropped");
+        let size = raw_inode.size();
+        let mut target = alloc::vec![0u8; size];
+
+        // Short targets are stored in place of the block pointers ("fast"
+        // symlinks), which own no data blocks except maybe an ACL block.
+        let acl_sectors = if raw_inode.file_acl != 0 {
+            fs.block_size / SECTOR_SIZE
+        } else {
+            0
+        };
+        if raw_inode.blocks_count as usize == acl_sectors {
+            let inline = raw_inode.block_ptrs.as_bytes();
+            if size > inline.len() {
+                return Err(Error::new(Errno::EIO));
+            }
+            target.copy_from_slice(&inline[..size]);
+        } else {
+            for (block_index, chunk) in target.chunks_mut(fs.block_size).enumerate() {
+                let bid = raw_inode
+                    .block_ptrs
+                    .get(&fs, block_index)
+                    .ok_or(Error::new(Errno::EIO))?;
+                BUFFER_CACHE.read_bytes(&fs.blk_device, fs.bid_to_offset(bid), chunk);
+            }
+        }
+
+        alloc::string::String::from_utf8(target).map_err(|_| Error::new(Errno::EINVAL))
     }
 
-    fn write_link(&self, target: &str) -> crate::error::Result<()> {
-        todo!()
+    fn write_link(&self, _target: &str) -> Result<()> {
+        Err(Error::new(Errno::EPERM))
     }
 
     fn read_at(
@@ -155,70 +451,176 @@ impl super::super::Inode for Inode {
         offset: usize,
         mut writer: ostd::mm::VmWriter,
     ) -> crate::error::Result<usize> {
-        if self.type_ != InodeType::File {
-            return Err(crate::error::Error::new(crate::error::Errno::EISDIR));
+        let Inner::File(page_cache) = &self.inner else {
+            return Err(Error::new(Errno::EISDIR));
+        };
+
+        let size = self.sector_ptr.read().size();
+        if offset >= size {
+            return Ok(0);
         }
+        let read_len = (size - offset).min(writer.avail());
+        page_cache.read(offset, read_len, &mut writer)
+    }
 
-        let sector_ptr = &self.sector_ptr;
-        let raw_inode: RawInode = sector_ptr.read();
+    fn write_at(
+        &self,
+        offset: usize,
+        mut reader: ostd::mm::VmReader,
+    ) -> crate::error::Result<usize> {
+        let Inner::File(page_cache) = &self.inner else {
+            return Err(Error::new(Errno::EISDIR));
+        };
         let fs = self.fs.upgrade().expect("Filesystem has been dropped");
-        let block_size = fs.block_size as usize;
-
-        let mut bytes_read = 0;
-        let mut current_offset = offset;
+        fs.mark_dirty();
+
+        let _guard = self.lock.lock();
+        let mut raw_inode: RawInode = self.sector_ptr.read();
+        let len = reader.remain();
+        // Write as much as fits if the disk runs full.
+        let allocated = self.allocate_blocks(&mut raw_inode, offset, len);
+        let write_len = self.allocated_len(&raw_inode, offset, len);
+        if write_len == 0 && len > 0 {
+            return Err(allocated.unwrap_err());
+        }
 
-        // Find start block and offset within block
-        let mut block_index = current_offset / block_size;
-        let mut offset_in_block = current_offset % block_size;
+        let written = page_cache.write(offset, write_len, &mut reader)?;
+        if offset + written > raw_inode.size() {
+            raw_inode.set_size(offset + written);
+            self.sector_ptr.write(&raw_inode);
+        }
+        Ok(written)
+    }
 
-        // Read data block by block
-        while writer.avail() > 0 {
-            let block_ptr = if block_index < 12 {
-                raw_inode.block_ptrs.direct_pointers[block_index as usize]
-            } else {
-                // For simplicity, we only handle direct pointers here
-                break;
-            };
-            if block_ptr.0 == 0 {
-                break;
+    fn resize(&self, new_size: usize) -> Result<()> {
+        let Inner::File(page_cache) = &self.inner else {
+            return Err(Error::new(Errno::EISDIR));
+        };
+        let fs = self.fs.upgrade().expect("Filesystem has been dropped");
+        fs.mark_dirty();
+
+        let _guard = self.lock.lock();
+        let mut raw_inode: RawInode = self.sector_ptr.read();
+        if new_size < raw_inode.size() {
+            // Load the last page kept, so that its tail is zeroed on disk too.
+            if new_size % PAGE_SIZE != 0 {
+                page_cache.get_page(new_size / PAGE_SIZE)?;
             }
-            let sector = fs.bid_to_sector(block_ptr);
-            let to_read = core::cmp::min(block_size - offset_in_block, writer.avail() - bytes_read);
-
-            debug!(
-                "Reading block_index: {}, block_ptr: {:?}, sector: {}, offset_in_block: {}, to_read: {}",
-                block_index, block_ptr, sector, offset_in_block, to_read
-            );
-            fs.blk_device.read_to_vm_writer(
-                sector + offset_in_block / SECTOR_SIZE,
-                (to_read + SECTOR_SIZE - 1) / SECTOR_SIZE,
-                &mut writer,
-            );
-
-            bytes_read += to_read;
-            current_offset += to_read;
-            offset_in_block = 0; // After first block, offset is 0
-            block_index += 1;
+            // Held until the inode without the freed blocks is written.
+            let _truncate_guard = self.truncate_lock.write();
+            page_cache.resize(new_size);
+            let freed = raw_inode
+                .block_ptrs
+                .truncate(&fs, new_size.div_ceil(fs.block_size));
+            raw_inode.blocks_count -= (freed * (fs.block_size / SECTOR_SIZE)) as u32;
+            raw_inode.set_size(new_size);
+            self.sector_ptr.write(&raw_inode);
+            return Ok(());
         }
-
-        Ok(bytes_read)
+        // Growing leaves a hole, which reads as zeros.
+        raw_inode.set_size(new_size);
+        self.sector_ptr.write(&raw_inode);
+        Ok(())
     }
 
-    fn write_at(&self, offset: usize, reader: ostd::mm::VmReader) -> crate::error::Result<usize> {
-        todo!()
+    fn allocate(&self, offset: usize, len: usize) -> Result<()> {
+        if !matches!(self.inner, Inner::File(_)) {
+            return Err(Error::new(Errno::EISDIR));
+        }
+        let fs = self.fs.upgrade().expect("Filesystem has been dropped");
+        fs.mark_dirty();
+
+        let _guard = self.lock.lock();
+        let mut raw_inode: RawInode = self.sector_ptr.read();
+        self.allocate_blocks(&mut raw_inode, offset, len)
     }
 
-    fn metadata(&self) -> &crate::fs::InodeMeta {
-        todo!()
+    fn metadata(&self) -> InodeMeta {
+        let raw_inode = self.sector_ptr.read();
+        let fs = self.fs.upgrade().expect("Filesystem has been dropped");
+        InodeMeta {
+            nlink: raw_inode.hard_links as u32,
+            uid: ((raw_inode.os_dependent_2.uid_high as u32) << 16) | raw_inode.uid as u32,
+            gid: ((raw_inode.os_dependent_2.gid_high as u32) << 16) | raw_inode.gid as u32,
+            size: raw_inode.size(),
+            blocks: raw_inode.blocks_count as usize,
+            blksize: fs.block_size,
+            rdev: match self.inner {
+                Inner::Special => raw_inode.block_ptrs.device(),
+                _ => 0,
+            },
+            atime: Duration::from_secs(raw_inode.atime as u64),
+            mtime: Duration::from_secs(raw_inode.mtime as u64),
+            ctime: Duration::from_secs(raw_inode.ctime as u64),
+            ..InodeMeta::new(self.ino(), self.type_, raw_inode.mode & 0o7777)
+        }
     }
 
     fn size(&self) -> usize {
-        todo!()
+        self.sector_ptr.read().size()
     }
 
     fn typ(&self) -> InodeType {
         self.type_
     }
+
+    fn ino(&self) -> u64 {
+        self.inode_id as u64
+    }
+
+    fn mode(&self) -> u16 {
+        self.sector_ptr.read().mode & 0o7777
+    }
+
+    fn set_mode(&self, mode: u16) -> Result<()> {
+        let fs = self.fs.upgrade().expect("Filesystem has been dropped");
+        fs.mark_dirty();
+        let _guard = self.lock.lock();
+        let mut raw_inode: RawInode = self.sector_ptr.read();
+        raw_inode.mode = (raw_inode.mode & 0xF000) | (mode & 0o7777);
+        self.sector_ptr.write(&raw_inode);
+        Ok(())
+    }
+
+    fn set_owner(&self, uid: u32, gid: u32) -> Result<()> {
+        let fs = self.fs.upgrade().expect("Filesystem has been dropped");
+        fs.mark_dirty();
+        let _guard = self.lock.lock();
+        let mut raw_inode: RawInode = self.sector_ptr.read();
+        raw_inode.uid = uid as u16;
+        raw_inode.os_dependent_2.uid_high = (uid >> 16) as u16;
+        raw_inode.gid = gid as u16;
+        raw_inode.os_dependent_2.gid_high = (gid >> 16) as u16;
+        self.sector_ptr.write(&raw_inode);
+        Ok(())
+    }
+
+    fn page_cache(&self) -> Option<Arc<PageCache>> {
+        match &self.inner {
+            Inner::File(page_cache) => Some(page_cache.clone()),
+            _ => None,
+        }
+    }
+
+    fn fs(&self) -> Option<Arc<dyn FileSystem>> {
+        self.fs.upgrade().map(|fs| fs as Arc<dyn FileSystem>)
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    fn sync_data(&self) -> Result<()> {
+        if let Inner::File(page_cache) = &self.inner {
+            page_cache.flush()?;
+        }
+
+        // The inode itself and the indirect blocks live in the buffer cache.
+        let fs = self.fs.upgrade().expect("Filesystem has been dropped");
+        BUFFER_CACHE.sync(&fs.blk_device);
+        fs.blk_device.flush();
+        Ok(())
+    }
 }
 
 #[repr(C)]
@@ -263,15 +665,221 @@ pub(super) struct RawInode {
     pub os_dependent_2: OsDependent2,
 }
 
+impl RawInode {
+    pub fn size(&self) -> usize {
+        if self.mode & 0xF000 == 0x8000 {
+            ((self.size_high as usize) << 32) | self.size_low as usize
+        } else {
+            self.size_low as usize
+        }
+    }
+
+    pub fn set_size(&mut self, size: usize) {
+        self.size_low = size as u32;
+        if self.mode & 0xF000 == 0x8000 {
+            self.size_high = (size >> 32) as u32;
+        }
+    }
+}
+
+const DIRECT_POINTERS: usize = 12;
+
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Pod, Default)]
 pub struct BlockPointers {
-    direct_pointers: [Ext2Bid; 12],
+    direct_pointers: [Ext2Bid; DIRECT_POINTERS],
     single_indirect_pointer: Ext2Bid,
     double_indirect_pointer: Ext2Bid,
     triple_indirect_pointer: Ext2Bid,
 }
 
+impl BlockPointers {
+    /// Maps the `block_index`-th block of the file to its block on the device.
+    ///
+    /// Returns `None` if the block is not allocated.
+    pub fn get(&self, fs: &Ext2Fs, block_index: usize) -> Option<Ext2Bid> {
+        if block_index < DIRECT_POINTERS {
+            let bid = self.direct_pointers[block_index];
+            return (bid.0 != 0).then_some(bid);
+        }
+
+        let (level, mut index, mut span) = locate_indirect(fs, block_index)?;
+        let per_block = fs.block_size / size_of::<Ext2Bid>();
+        let mut bid = self.indirect_pointers()[level];
+        while span > 1 {
+            if bid.0 == 0 {
+                return None;
+            }
+            span /= per_block;
+            bid = fs.read_indirect(bid, index / span);
+            index %= span;
+        }
+        (bid.0 != 0).then_some(bid)
+    }
+
+    /// Maps the `block_index`-th block of the file to `bid`, allocating the
+    /// missing indirect blocks on the way.
+    ///
+    /// Returns the number of indirect blocks allocated.
+    pub fn set(&mut self, fs: &Ext2Fs, block_index: usize, bid: Ext2Bid) -> Result<usize> {
+        if block_index < DIRECT_POINTERS {
+            self.direct_pointers[block_index] = bid;
+            return Ok(0);
+        }
+
+        let (level, mut index, mut span) =
+            locate_indirect(fs, block_index).ok_or(Error::new(Errno::EFBIG))?;
+        let per_block = fs.block_size / size_of::<Ext2Bid>();
+        let mut allocated = 0;
+        let mut pointers = self.indirect_pointers_mut();
+        let root = &mut *pointers[level];
+        if root.0 == 0 {
+            *root = fs.alloc_zeroed_block()?;
+            allocated += 1;
+        }
+
+        let mut table = *root;
+        loop {
+            span /= per_block;
+            let slot = index / span;
+            index %= span;
+            if span == 1 {
+                fs.write_indirect(table, slot, bid);
+                return Ok(allocated);
+            }
+
+            let mut child = fs.read_indirect(table, slot);
+            if child.0 == 0 {
+                child = fs.alloc_zeroed_block()?;
+                fs.write_indirect(table, slot, child);
+                allocated += 1;
+            }
+            table = child;
+        }
+    }
+
+    /// Frees the blocks from the `num_blocks`-th block of the file on, and the
+    /// indirect blocks that no longer map anything.
+    ///
+    /// Returns the number of blocks freed.
+    pub fn truncate(&mut self, fs: &Ext2Fs, num_blocks: usize) -> usize {
+        let mut freed = 0;
+        for bid in self.direct_pointers.iter_mut().skip(num_blocks) {
+            if bid.0 != 0 {
+                fs.free_block(*bid);
+                *bid = Ext2Bid(0);
+                freed += 1;
+            }
+        }
+
+        let per_block = fs.block_size / size_of::<Ext2Bid>();
+        let mut start = DIRECT_POINTERS;
+        let mut span = per_block;
+        for root in self.indirect_pointers_mut() {
+            let keep = num_blocks.saturating_sub(start);
+            if root.0 != 0 && keep < span {
+                freed += truncate_indirect(fs, *root, span, keep);
+                if keep == 0 {
+                    *root = Ext2Bid(0);
+                }
+            }
+            start += span;
+            span *= per_block;
+        }
+        freed
+    }
+
+    /// The device number of a device file, which Linux keeps in the first
+    /// direct pointer in the old encoding, or else in the second one in the
+    /// new encoding.
+    pub fn device(&self) -> u64 {
+        let [old, new] = [0, 1].map(|idx| self.direct_pointers[idx].0);
+        if old != 0 {
+            makedev((old >> 8) & 0xff, old & 0xff)
+        } else {
+            makedev((new & 0xfff00) >> 8, (new & 0xff) | ((new >> 12) & 0xfff00))
+        }
+    }
+
+    pub fn set_device(&mut self, rdev: u64) {
+        let (major, minor) = dev_major_minor(rdev);
+        if major < 256 && minor < 256 {
+            self.direct_pointers[0] = Ext2Bid((major << 8) | minor);
+            self.direct_pointers[1] = Ext2Bid(0);
+        } else {
+            self.direct_pointers[0] = Ext2Bid(0);
+            self.direct_pointers[1] =
+                Ext2Bid((minor & 0xff) | (major << 8) | ((minor & !0xff) << 12));
+        }
+    }
+
+    fn indirect_pointers(&self) -> [Ext2Bid; 3] {
+        [
+            self.single_indirect_pointer,
+            self.double_indirect_pointer,
+            self.triple_indirect_pointer,
+        ]
+    }
+
+    fn indirect_pointers_mut(&mut self) -> [&mut Ext2Bid; 3] {
+        [
+            &mut self.single_indirect_pointer,
+            &mut self.double_indirect_pointer,
+            &mut self.triple_indirect_pointer,
+        ]
+    }
+}
+
+/// Finds the indirect tree containing the `block_index`-th block of a file.
+///
+/// Returns the level of the tree (0 for the single indirect one), the index of
+/// the block inside the tree and the number of blocks the tree maps.
+fn locate_indirect(fs: &Ext2Fs, block_index: usize) -> Option<(usize, usize, usize)> {
+    let per_block = fs.block_size / size_of::<Ext2Bid>();
+    let mut index = block_index - DIRECT_POINTERS;
+    let mut span = per_block;
+    for level in 0..3 {
+        if index < span {
+            return Some((level, index, span));
+        }
+        index -= span;
+        span *= per_block;
+    }
+    None
+}
+
+/// Frees the blocks past the first `keep` ones mapped by the indirect block
+/// `table`, which maps `span` blocks, and `table` itself if `keep` is zero.
+///
+/// Returns the number of blocks freed.
+fn truncate_indirect(fs: &Ext2Fs, table: Ext2Bid, span: usize, keep: usize) -> usize {
+    let per_block = fs.block_size / size_of::<Ext2Bid>();
+    let child_span = span / per_block;
+    let mut freed = 0;
+    for slot in keep / child_span..per_block {
+        let child = fs.read_indirect(table, slot);
+        if child.0 == 0 {
+            continue;
+        }
+        let child_keep = keep.saturating_sub(slot * child_span);
+        if child_span > 1 {
+            freed += truncate_indirect(fs, child, child_span, child_keep);
+        } else {
+            fs.free_block(child);
+            freed += 1;
+        }
+        if child_keep == 0 && keep != 0 {
+            fs.write_indirect(table, slot, Ext2Bid(0));
+        }
+    }
+
+    if keep == 0 {
+        fs.free_block(table);
+        freed += 1;
+    }
+    freed
+}
+
 /// OS dependent 2.
 ///
 /// Here we use the Linux definition.
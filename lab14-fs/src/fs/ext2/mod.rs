@@ -4,21 +4,25 @@
 
 use core::fmt::Debug;
 use core::ops::Add;
+use core::sync::atomic::{AtomicBool, Ordering};
 
 use alloc::sync::Weak;
-use alloc::{collections::btree_map::BTreeMap, sync::Arc, vec::Vec};
+use alloc::{collections::btree_map::BTreeMap, sync::Arc, vec, vec::Vec};
 use log::{debug, info};
 use ostd::Pod;
+use ostd::mm::VmReader;
 use ostd::{early_println, sync::Mutex};
 
 use crate::fs::ext2::inode::RawInode;
 use crate::fs::ext2::super_block::EXT2_FIRST_SUPERBLOCK_OFFSET;
+use crate::fs::util::buffer_cache::BUFFER_CACHE;
 use crate::fs::util::sector_ptr::SectorPtr;
 use crate::{
     drivers::blk::{BlockDevice, SECTOR_SIZE},
-    error::{Error, Result},
+    error::{Errno, Error, Result},
     fs::{
-        FileSystem,
+        FileSystem, FsStats, InodeType,
+        dentry::NAME_MAX,
         ext2::{
             block_group::BlockGroup,
             inode::Inode,
@@ -33,8 +37,15 @@ mod inode;
 mod super_block;
 
 const EXT2_MAGIC: u16 = 0xEF53;
+/// The `state` bit of the superblock telling that the filesystem was cleanly unmounted.
+const EXT2_VALID_FS: u16 = 0x1;
 /// The root inode number.
 const ROOT_INO: u32 = 2;
+/// The first inode number not reserved, which revision 0 does not record.
+const EXT2_GOOD_OLD_FIRST_INO: u32 = 11;
+/// The `feature_incompat` bit of the superblock telling that directory
+/// entries record the file types.
+const EXT2_FEATURE_INCOMPAT_FILETYPE: u32 = 0x2;
 
 pub struct Ext2Fs {
     blk_device: Arc<dyn BlockDevice>,
@@ -46,6 +57,11 @@ pub struct Ext2Fs {
     blocks_per_group: u32,
     inode_size: usize,
     block_size: usize,
+    /// Serializes the updates to the bitmaps and the free counts.
+    alloc_lock: Mutex<()>,
+    /// Whether the superblock was marked as not clean, which is done on the
+    /// first write so that a filesystem only read stays untouched.
+    marked_dirty: AtomicBool,
 
     self_ref: Weak<Ext2Fs>,
 }
@@ -53,7 +69,7 @@ pub struct Ext2Fs {
 impl Ext2Fs {
     pub fn new(blk_device: Arc<dyn BlockDevice>) -> Result<Arc<Self>> {
         let raw_super_block: RawSuperBlock =
-            blk_device.read_val(EXT2_FIRST_SUPERBLOCK_OFFSET / SECTOR_SIZE);
+            BUFFER_CACHE.read_val(&blk_device, EXT2_FIRST_SUPERBLOCK_OFFSET);
 
         if raw_super_block.magic != EXT2_MAGIC {
             return Err(Error::new(crate::error::Errno::EACCES));
@@ -63,16 +79,20 @@ impl Ext2Fs {
 
         let super_block = SuperBlock::from(raw_super_block);
 
-        // We currently only support exactly one block group.
-        assert!(super_block.inodes_per_group == super_block.inodes_count);
-        assert!(super_block.blocks_per_group == super_block.blocks_count);
-        // We currently only support 4KB block size.
-        assert!(super_block.block_size == 4096);
+        // We currently only support exactly one block group and 4KB blocks.
+        if super_block.inodes_per_group != super_block.inodes_count
+            || super_block.blocks_per_group != super_block.blocks_count
+            || super_block.block_size != 4096
+        {
+            return Err(Error::new(crate::error::Errno::EINVAL));
+        }
 
         let first_group_bid = super_block.group_descriptor_table_bid();
 
-        let raw_descriptor: block_group::RawGroupDescriptor = blk_device
-            .read_val(first_group_bid.0 as usize * super_block.block_size as usize / SECTOR_SIZE);
+        let raw_descriptor: block_group::RawGroupDescriptor = BUFFER_CACHE.read_val(
+            &blk_device,
+            first_group_bid.0 as usize * super_block.block_size as usize,
+        );
 
         let mut blk_groups = Vec::new();
         blk_groups.push(BlockGroup::new(raw_descriptor));
@@ -86,15 +106,25 @@ impl Ext2Fs {
             super_block: SuperBlock::from(super_block),
             inode_cache: Mutex::new(BTreeMap::new()),
             block_groups: blk_groups,
+            alloc_lock: Mutex::new(()),
+            marked_dirty: AtomicBool::new(false),
             self_ref: fs.clone(),
         });
+        // Fail the mount rather than `root_inode` if the root is corrupted.
+        let root = fs.lookup_inode(ROOT_INO)?;
+        if crate::fs::Inode::typ(root.as_ref()) != InodeType::Directory {
+            return Err(Error::new(Errno::EIO));
+        }
 
         Ok(fs)
     }
 
     fn lookup_inode(&self, inode_number: u32) -> Result<Arc<Inode>> {
         let idx = inode_number - 1;
-        if let Some(inode) = self.inode_cache.lock().get(&inode_number) {
+        // Hold the lock so that no one else creates another inode meanwhile,
+        // which would have a page cache of its own.
+        let mut inode_cache = self.inode_cache.lock();
+        if let Some(inode) = inode_cache.get(&inode_number) {
             return Ok(inode.clone());
         }
 
@@ -102,6 +132,21 @@ impl Ext2Fs {
             return Err(Error::new(crate::error::Errno::ENOENT));
         }
 
+        let sector_ptr = self.inode_sector_ptr(inode_number);
+        let inode = Inode::new(
+            sector_ptr,
+            inode_number,
+            (idx / self.inodes_per_group) as usize,
+            self.self_ref.clone(),
+        )?;
+        inode_cache.insert(inode_number, inode.clone());
+
+        Ok(inode)
+    }
+
+    /// Locates the on-disk inode numbered `inode_number`.
+    fn inode_sector_ptr(&self, inode_number: u32) -> SectorPtr<RawInode> {
+        let idx = inode_number - 1;
         let inode_table_block =
             self.block_groups[(idx / self.inodes_per_group) as usize].inode_table_start_bid();
         let inodes_per_block = (self.block_size / self.inode_size) as u32;
@@ -119,21 +164,191 @@ impl Ext2Fs {
             self.bid_to_sector(bid_num) + offset_in_block as usize * self.inode_size / SECTOR_SIZE;
         let sector_offset = (offset_in_block as usize * self.inode_size) % SECTOR_SIZE;
 
-        let sector_ptr: SectorPtr<RawInode> =
-            SectorPtr::new(sector, sector_offset, &self.blk_device);
+        SectorPtr::new(sector, sector_offset, &self.blk_device)
+    }
 
-        let inode = Inode::new(
-            sector_ptr,
-            inode_number,
-            (idx / self.inodes_per_group) as usize,
-            self.self_ref.clone(),
+    pub fn bid_to_sector(&self, bid: Ext2Bid) -> usize {
+        bid.0 as usize * self.block_size / SECTOR_SIZE
+    }
+
+    /// Returns the byte offset of block `bid` on the device.
+    pub fn bid_to_offset(&self, bid: Ext2Bid) -> usize {
+        bid.0 as usize * self.block_size
+    }
+
+    /// Reads the `idx`-th block pointer stored in the indirect block `bid`.
+    fn read_indirect(&self, bid: Ext2Bid, idx: usize) -> Ext2Bid {
+        BUFFER_CACHE.read_val(
+            &self.blk_device,
+            self.bid_to_offset(bid) + idx * size_of::<Ext2Bid>(),
+        )
+    }
+
+    /// Stores `value` as the `idx`-th block pointer of the indirect block `bid`.
+    fn write_indirect(&self, bid: Ext2Bid, idx: usize, value: Ext2Bid) {
+        BUFFER_CACHE.write_val(
+            &self.blk_device,
+            self.bid_to_offset(bid) + idx * size_of::<Ext2Bid>(),
+            &value,
         );
+    }
 
-        Ok(inode)
+    /// Marks the filesystem as not clean until it is unmounted again. Called
+    /// before anything is written to the device.
+    fn mark_dirty(&self) {
+        if self.marked_dirty.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let mut raw_super_block: RawSuperBlock =
+            BUFFER_CACHE.read_val(&self.blk_device, EXT2_FIRST_SUPERBLOCK_OFFSET);
+        raw_super_block.state &= !EXT2_VALID_FS;
+        raw_super_block.mnt_count = raw_super_block.mnt_count.wrapping_add(1);
+        BUFFER_CACHE.write_val(
+            &self.blk_device,
+            EXT2_FIRST_SUPERBLOCK_OFFSET,
+            &raw_super_block,
+        );
     }
 
-    pub fn bid_to_sector(&self, bid: Ext2Bid) -> usize {
-        bid.0 as usize * self.block_size / SECTOR_SIZE
+    /// Allocates a free block. Its content is undefined.
+    fn alloc_block(&self) -> Result<Ext2Bid> {
+        let _guard = self.alloc_lock.lock();
+        let bitmap = self.bid_to_offset(self.block_groups[0].block_bitmap_bid());
+        let first_bid = self.super_block.first_data_block;
+
+        let mut bits = vec![0u8; self.block_size];
+        BUFFER_CACHE.read_bytes(&self.blk_device, bitmap, &mut bits);
+        let bit = (0..(self.super_block.blocks_count - first_bid) as usize)
+            .find(|&bit| bits[bit / 8] & (1 << (bit % 8)) == 0)
+            .ok_or(Error::new(Errno::ENOSPC))?;
+
+        let byte = bits[bit / 8] | (1 << (bit % 8));
+        BUFFER_CACHE.write_val(&self.blk_device, bitmap + bit / 8, &byte);
+        self.add_free_counts(-1, 0);
+        Ok(Ext2Bid(first_bid + bit as u32))
+    }
+
+    /// Allocates a block and fills it with zeros, e.g., for an indirect block.
+    fn alloc_zeroed_block(&self) -> Result<Ext2Bid> {
+        let bid = self.alloc_block()?;
+        BUFFER_CACHE.write_bytes(
+            &self.blk_device,
+            self.bid_to_offset(bid),
+            &vec![0u8; self.block_size],
+        );
+        Ok(bid)
+    }
+
+    /// Allocates a block for file data and zeroes it. File data bypasses the
+    /// buffer cache, so the zeros are written to the device directly.
+    fn alloc_data_block(&self) -> Result<Ext2Bid> {
+        let bid = self.alloc_block()?;
+        // The device pads short data with zeros.
+        let mut zeros = VmReader::from(&[][..]).to_fallible();
+        self.blk_device.write_from_vm_reader(
+            self.bid_to_sector(bid),
+            self.block_size / SECTOR_SIZE,
+            &mut zeros,
+        );
+        Ok(bid)
+    }
+
+    /// Returns block `bid` to the free blocks.
+    fn free_block(&self, bid: Ext2Bid) {
+        let _guard = self.alloc_lock.lock();
+        let bitmap = self.bid_to_offset(self.block_groups[0].block_bitmap_bid());
+        let bit = (bid.0 - self.super_block.first_data_block) as usize;
+
+        let byte: u8 = BUFFER_CACHE.read_val(&self.blk_device, bitmap + bit / 8);
+        BUFFER_CACHE.write_val(
+            &self.blk_device,
+            bitmap + bit / 8,
+            &(byte & !(1 << (bit % 8))),
+        );
+        self.add_free_counts(1, 0);
+        // A cached copy of the block, e.g., of an indirect block, must not be
+        // written back over the data of its next owner.
+        BUFFER_CACHE.discard(&self.blk_device, bid.0 as usize);
+    }
+
+    /// Allocates an inode and stores `raw_inode` in it.
+    fn alloc_inode(&self, raw_inode: &RawInode) -> Result<Arc<Inode>> {
+        let inode_number = {
+            let _guard = self.alloc_lock.lock();
+            let bitmap = self.bid_to_offset(self.block_groups[0].inode_bitmap_bid());
+            let first_ino = match self.super_block.first_ino {
+                0 => EXT2_GOOD_OLD_FIRST_INO,
+                first_ino => first_ino,
+            };
+
+            let mut bits = vec![0u8; self.super_block.inodes_count.div_ceil(8) as usize];
+            BUFFER_CACHE.read_bytes(&self.blk_device, bitmap, &mut bits);
+            // Bit `n` stands for inode `n + 1`.
+            let bit = (first_ino as usize - 1..self.super_block.inodes_count as usize)
+                .find(|&bit| bits[bit / 8] & (1 << (bit % 8)) == 0)
+                .ok_or(Error::new(Errno::ENOSPC))?;
+
+            let byte = bits[bit / 8] | (1 << (bit % 8));
+            BUFFER_CACHE.write_val(&self.blk_device, bitmap + bit / 8, &byte);
+            self.add_free_counts(0, -1);
+            bit as u32 + 1
+        };
+
+        self.inode_sector_ptr(inode_number).write(raw_inode);
+        self.lookup_inode(inode_number)
+    }
+
+    /// Returns the inode numbered `inode_number` to the free inodes.
+    fn free_inode(&self, inode_number: u32) {
+        let _guard = self.alloc_lock.lock();
+        self.inode_cache.lock().remove(&inode_number);
+        self.inode_sector_ptr(inode_number)
+            .write(&RawInode::default());
+
+        let bitmap = self.bid_to_offset(self.block_groups[0].inode_bitmap_bid());
+        let bit = (inode_number - 1) as usize;
+        let byte: u8 = BUFFER_CACHE.read_val(&self.blk_device, bitmap + bit / 8);
+        BUFFER_CACHE.write_val(
+            &self.blk_device,
+            bitmap + bit / 8,
+            &(byte & !(1 << (bit % 8))),
+        );
+        self.add_free_counts(0, 1);
+    }
+
+    /// Whether directory entries record the file types.
+    fn has_file_types(&self) -> bool {
+        self.super_block.feature_incompat & EXT2_FEATURE_INCOMPAT_FILETYPE != 0
+    }
+
+    /// Adjusts the free block and inode counts of the group descriptor and
+    /// the superblock.
+    fn add_free_counts(&self, blocks: i32, inodes: i32) {
+        self.mark_dirty();
+        let descriptor_offset = self.bid_to_offset(self.super_block.group_descriptor_table_bid());
+        let mut descriptor: block_group::RawGroupDescriptor =
+            BUFFER_CACHE.read_val(&self.blk_device, descriptor_offset);
+        descriptor.free_blocks_count = descriptor
+            .free_blocks_count
+            .wrapping_add_signed(blocks as i16);
+        descriptor.free_inodes_count = descriptor
+            .free_inodes_count
+            .wrapping_add_signed(inodes as i16);
+        BUFFER_CACHE.write_val(&self.blk_device, descriptor_offset, &descriptor);
+
+        let mut raw_super_block: RawSuperBlock =
+            BUFFER_CACHE.read_val(&self.blk_device, EXT2_FIRST_SUPERBLOCK_OFFSET);
+        raw_super_block.free_blocks_count = raw_super_block
+            .free_blocks_count
+            .wrapping_add_signed(blocks);
+        raw_super_block.free_inodes_count = raw_super_block
+            .free_inodes_count
+            .wrapping_add_signed(inodes);
+        BUFFER_CACHE.write_val(
+            &self.blk_device,
+            EXT2_FIRST_SUPERBLOCK_OFFSET,
+            &raw_super_block,
+        );
     }
 }
 
@@ -157,6 +372,74 @@ impl FileSystem for Ext2Fs {
     fn root_inode(&self) -> Arc<dyn crate::fs::Inode> {
         self.lookup_inode(ROOT_INO).unwrap()
     }
+
+    fn stats(&self) -> FsStats {
+        // The in-memory copies go stale as blocks are allocated, so read the
+        // counts from the device.
+        let raw_super_block: RawSuperBlock =
+            BUFFER_CACHE.read_val(&self.blk_device, EXT2_FIRST_SUPERBLOCK_OFFSET);
+        let table_offset = self.bid_to_offset(self.super_block.group_descriptor_table_bid());
+        let (free_blocks, free_inodes) = (0..self.block_groups.len())
+            .map(|idx| {
+                let descriptor: block_group::RawGroupDescriptor = BUFFER_CACHE.read_val(
+                    &self.blk_device,
+                    table_offset + idx * size_of::<block_group::RawGroupDescriptor>(),
+                );
+                (
+                    descriptor.free_blocks_count as u64,
+                    descriptor.free_inodes_count as u64,
+                )
+            })
+            .fold((0, 0), |(blocks, inodes), (free_blocks, free_inodes)| {
+                (blocks + free_blocks, inodes + free_inodes)
+            });
+
+        FsStats {
+            magic: EXT2_MAGIC as u64,
+            block_size: self.block_size,
+            total_blocks: raw_super_block.blocks_count as u64,
+            free_blocks,
+            // The reserved blocks are left for the superuser.
+            avail_blocks: free_blocks.saturating_sub(raw_super_block.reserved_blocks_count as u64),
+            total_inodes: raw_super_block.inodes_count as u64,
+            free_inodes,
+            name_max: NAME_MAX,
+        }
+    }
+
+    fn sync(&self) -> Result<()> {
+        let inodes: Vec<Arc<Inode>> = self.inode_cache.lock().values().cloned().collect();
+        for inode in inodes {
+            if let Some(page_cache) = crate::fs::Inode::page_cache(inode.as_ref()) {
+                page_cache.flush()?;
+            }
+        }
+
+        BUFFER_CACHE.sync(&self.blk_device);
+        self.blk_device.flush();
+        Ok(())
+    }
+
+    fn unmount(&self) -> Result<()> {
+        self.sync()?;
+        // A filesystem never written is left as it was found.
+        if !self.marked_dirty.load(Ordering::Acquire) {
+            return Ok(());
+        }
+
+        let mut raw_super_block: RawSuperBlock =
+            BUFFER_CACHE.read_val(&self.blk_device, EXT2_FIRST_SUPERBLOCK_OFFSET);
+        raw_super_block.state |= EXT2_VALID_FS;
+        BUFFER_CACHE.write_val(
+            &self.blk_device,
+            EXT2_FIRST_SUPERBLOCK_OFFSET,
+            &raw_super_block,
+        );
+        BUFFER_CACHE.sync(&self.blk_device);
+        self.blk_device.flush();
+        info!("Ext2: unmounted cleanly");
+        Ok(())
+    }
 }
 
 #[repr(C)]
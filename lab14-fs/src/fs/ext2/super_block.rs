@@ -29,6 +29,7 @@ pub struct SuperBlock {
     pub max_mnt_count: u16,
     pub first_ino: u32,
     pub inode_size: u16,
+    pub feature_incompat: u32,
 }
 
 impl SuperBlock {
@@ -93,6 +94,7 @@ impl From<RawSuperBlock> for SuperBlock {
             max_mnt_count: value.max_mnt_count,
             first_ino: value.first_ino,
             inode_size: value.inode_size,
+            feature_incompat: value.feature_incompat,
             idx: value.block_group_idx as u32,
         }
     }
@@ -0,0 +1,119 @@
+use crate::drivers::blk::SECTOR_SIZE;
+
+/// The offset of the signature of the boot sector and the FSInfo sector.
+const SIGNATURE_OFFSET: usize = 510;
+const SIGNATURE: u16 = 0xAA55;
+
+const FS_INFO_LEAD_SIG: u32 = 0x41615252;
+const FS_INFO_STRUC_SIG: u32 = 0x61417272;
+/// The offset of the free cluster count in the FSInfo sector.
+pub const FS_INFO_FREE_COUNT_OFFSET: usize = 488;
+/// The offset of the next free cluster hint in the FSInfo sector.
+pub const FS_INFO_NEXT_FREE_OFFSET: usize = 492;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatType {
+    Fat12,
+    Fat16,
+    Fat32,
+}
+
+/// The fields of the BIOS parameter block that we use.
+///
+/// The on-disk structure is not naturally aligned, so it is parsed field by field.
+#[derive(Debug)]
+pub struct BootSector {
+    pub bytes_per_sector: u16,
+    pub sectors_per_cluster: u8,
+    pub reserved_sectors: u16,
+    pub num_fats: u8,
+    /// The number of entries in the fixed root directory (FAT12/16 only).
+    pub root_entry_count: u16,
+    pub total_sectors: u32,
+    /// The number of sectors occupied by one FAT.
+    pub fat_size: u32,
+    /// The first cluster of the root directory (FAT32 only).
+    pub root_cluster: u32,
+    /// The sector number of the FSInfo structure (FAT32 only).
+    pub fs_info_sector: u16,
+}
+
+impl BootSector {
+    /// Parses the boot sector. Returns `None` if it does not look like FAT.
+    pub fn parse(raw: &[u8; SECTOR_SIZE]) -> Option<Self> {
+        if read_u16(raw, SIGNATURE_OFFSET) != SIGNATURE {
+            return None;
+        }
+
+        let bytes_per_sector = read_u16(raw, 11);
+        let sectors_per_cluster = raw[13];
+        let fat_size_16 = read_u16(raw, 22) as u32;
+        let total_sectors_16 = read_u16(raw, 19) as u32;
+        let boot_sector = Self {
+            bytes_per_sector,
+            sectors_per_cluster,
+            reserved_sectors: read_u16(raw, 14),
+            num_fats: raw[16],
+            root_entry_count: read_u16(raw, 17),
+            total_sectors: if total_sectors_16 != 0 {
+                total_sectors_16
+            } else {
+                read_u32(raw, 32)
+            },
+            fat_size: if fat_size_16 != 0 {
+                fat_size_16
+            } else {
+                read_u32(raw, 36)
+            },
+            root_cluster: read_u32(raw, 44),
+            fs_info_sector: read_u16(raw, 48),
+        };
+
+        let valid = bytes_per_sector as usize == SECTOR_SIZE
+            && sectors_per_cluster.is_power_of_two()
+            && boot_sector.reserved_sectors != 0
+            && boot_sector.num_fats != 0
+            && boot_sector.fat_size != 0
+            && boot_sector.total_sectors > boot_sector.first_data_sector();
+        valid.then_some(boot_sector)
+    }
+
+    /// The number of sectors occupied by the fixed root directory.
+    pub fn root_dir_sectors(&self) -> u32 {
+        (self.root_entry_count as u32 * 32).div_ceil(self.bytes_per_sector as u32)
+    }
+
+    pub fn first_data_sector(&self) -> u32 {
+        self.reserved_sectors as u32
+            + self.num_fats as u32 * self.fat_size
+            + self.root_dir_sectors()
+    }
+
+    pub fn cluster_count(&self) -> u32 {
+        (self.total_sectors - self.first_data_sector()) / self.sectors_per_cluster as u32
+    }
+
+    /// The FAT type is determined by the number of clusters only.
+    pub fn fat_type(&self) -> FatType {
+        match self.cluster_count() {
+            0..4085 => FatType::Fat12,
+            4085..65525 => FatType::Fat16,
+            _ => FatType::Fat32,
+        }
+    }
+}
+
+/// Checks the signatures of an FSInfo sector.
+pub fn is_valid_fs_info(raw: &[u8; SECTOR_SIZE]) -> bool {
+    read_u32(raw, 0) == FS_INFO_LEAD_SIG
+        && read_u32(raw, 484) == FS_INFO_STRUC_SIG
+        && read_u16(raw, SIGNATURE_OFFSET) == SIGNATURE
+}
+
+fn read_u16(raw: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([raw[offset], raw[offset + 1]])
+}
+
+fn read_u32(raw: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(raw[offset..offset + 4].try_into().unwrap())
+}
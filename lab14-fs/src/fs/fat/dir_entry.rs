@@ -0,0 +1,357 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::time::Duration;
+use ostd::Pod;
+
+pub const DIR_ENTRY_SIZE: usize = 32;
+
+pub const ATTR_READ_ONLY: u8 = 0x01;
+pub const ATTR_HIDDEN: u8 = 0x02;
+pub const ATTR_SYSTEM: u8 = 0x04;
+pub const ATTR_VOLUME_ID: u8 = 0x08;
+pub const ATTR_DIRECTORY: u8 = 0x10;
+pub const ATTR_ARCHIVE: u8 = 0x20;
+pub const ATTR_LONG_NAME: u8 = ATTR_READ_ONLY | ATTR_HIDDEN | ATTR_SYSTEM | ATTR_VOLUME_ID;
+
+/// The first name byte of the entry marking the end of the directory.
+pub const END_OF_DIR: u8 = 0x00;
+/// The first name byte of a deleted entry.
+pub const DELETED: u8 = 0xE5;
+
+/// `nt_res` flags telling that the base name or the extension is in lower case.
+const NT_LOWER_BASE: u8 = 0x08;
+const NT_LOWER_EXT: u8 = 0x10;
+
+/// The flag of the sequence number of the last long name entry.
+const LAST_LONG_ENTRY: u8 = 0x40;
+/// The number of UTF-16 characters stored in one long name entry.
+const CHARS_PER_LONG_ENTRY: usize = 13;
+pub const MAX_NAME_LEN: usize = 255;
+
+/// A short (8.3) directory entry.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, Pod)]
+pub struct RawDirEntry {
+    pub name: [u8; 11],
+    pub attr: u8,
+    pub nt_res: u8,
+    pub create_time_tenth: u8,
+    pub create_time: u16,
+    pub create_date: u16,
+    pub access_date: u16,
+    pub first_cluster_high: u16,
+    pub write_time: u16,
+    pub write_date: u16,
+    pub first_cluster_low: u16,
+    pub file_size: u32,
+}
+
+impl RawDirEntry {
+    pub fn new(name: [u8; 11], attr: u8, first_cluster: u32) -> Self {
+        let mut entry = Self {
+            name,
+            attr,
+            ..Default::default()
+        };
+        entry.set_first_cluster(first_cluster);
+        entry
+    }
+
+    pub fn first_cluster(&self) -> u32 {
+        ((self.first_cluster_high as u32) << 16) | self.first_cluster_low as u32
+    }
+
+    pub fn set_first_cluster(&mut self, cluster: u32) {
+        self.first_cluster_high = (cluster >> 16) as u16;
+        self.first_cluster_low = cluster as u16;
+    }
+
+    /// The last modification time, since the Unix epoch.
+    pub fn modified_time(&self) -> Duration {
+        fat_timestamp(self.write_date, self.write_time)
+    }
+
+    /// The last access time, since the Unix epoch. Only the date is recorded.
+    pub fn accessed_time(&self) -> Duration {
+        fat_timestamp(self.access_date, 0)
+    }
+
+    pub fn is_long_name(&self) -> bool {
+        self.attr & ATTR_LONG_NAME == ATTR_LONG_NAME
+    }
+
+    pub fn is_directory(&self) -> bool {
+        self.attr & ATTR_DIRECTORY != 0
+    }
+
+    pub fn is_volume_id(&self) -> bool {
+        self.attr & ATTR_VOLUME_ID != 0
+    }
+
+    /// The name stored in the 8.3 fields, e.g., `README.TXT`.
+    pub fn short_name(&self) -> String {
+        let mut base = trim_padding(&self.name[..8]);
+        let mut ext = trim_padding(&self.name[8..]);
+        // 0x05 stands for a leading 0xE5 in the name.
+        if base.first() == Some(&0x05) {
+            base[0] = DELETED;
+        }
+        if self.nt_res & NT_LOWER_BASE != 0 {
+            base.make_ascii_lowercase();
+        }
+        if self.nt_res & NT_LOWER_EXT != 0 {
+            ext.make_ascii_lowercase();
+        }
+
+        let mut name = String::from_utf8_lossy(&base).to_string();
+        if !ext.is_empty() {
+            name.push('.');
+            name.push_str(&String::from_utf8_lossy(&ext));
+        }
+        name
+    }
+}
+
+/// A long file name entry, holding 13 UTF-16 characters of the name.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, Pod)]
+pub struct RawLongDirEntry {
+    pub order: u8,
+    pub name1: [u8; 10],
+    pub attr: u8,
+    pub type_: u8,
+    pub checksum: u8,
+    pub name2: [u8; 12],
+    pub first_cluster_low: [u8; 2],
+    pub name3: [u8; 4],
+}
+
+impl RawLongDirEntry {
+    /// The position of this entry in the name, starting from 1.
+    pub fn sequence(&self) -> usize {
+        (self.order & !LAST_LONG_ENTRY) as usize
+    }
+
+    pub fn is_last(&self) -> bool {
+        self.order & LAST_LONG_ENTRY != 0
+    }
+
+    fn chars(&self) -> impl Iterator<Item = u16> + '_ {
+        self.name1
+            .chunks(2)
+            .chain(self.name2.chunks(2))
+            .chain(self.name3.chunks(2))
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+    }
+
+    fn set_chars(&mut self, chars: &[u16; CHARS_PER_LONG_ENTRY]) {
+        let mut bytes = chars.iter().flat_map(|c| c.to_le_bytes());
+        for byte in self
+            .name1
+            .iter_mut()
+            .chain(self.name2.iter_mut())
+            .chain(self.name3.iter_mut())
+        {
+            *byte = bytes.next().unwrap();
+        }
+    }
+}
+
+/// Collects the long name entries preceding a short entry.
+#[derive(Default)]
+pub struct LongNameBuilder {
+    /// The name pieces indexed by sequence number - 1.
+    parts: Vec<Option<[u16; CHARS_PER_LONG_ENTRY]>>,
+    checksum: u8,
+    /// The slot index of the first long name entry.
+    pub first_slot: usize,
+}
+
+impl LongNameBuilder {
+    pub fn push(&mut self, slot: usize, entry: &RawLongDirEntry) {
+        if entry.is_last() {
+            self.parts = alloc::vec![None; entry.sequence()];
+            self.checksum = entry.checksum;
+            self.first_slot = slot;
+        }
+        let idx = entry.sequence();
+        if idx == 0 || idx > self.parts.len() || entry.checksum != self.checksum {
+            self.clear();
+            return;
+        }
+
+        let mut chars = [0u16; CHARS_PER_LONG_ENTRY];
+        for (dst, src) in chars.iter_mut().zip(entry.chars()) {
+            *dst = src;
+        }
+        self.parts[idx - 1] = Some(chars);
+    }
+
+    pub fn clear(&mut self) {
+        self.parts.clear();
+    }
+
+    /// Returns the long name if it is complete and belongs to `short_entry`.
+    pub fn take(&mut self, short_entry: &RawDirEntry) -> Option<String> {
+        let parts = core::mem::take(&mut self.parts);
+        if parts.is_empty()
+            || self.checksum != checksum(&short_entry.name)
+            || parts.iter().any(|part| part.is_none())
+        {
+            return None;
+        }
+
+        let chars: Vec<u16> = parts
+            .into_iter()
+            .flatten()
+            .flatten()
+            .take_while(|&c| c != 0)
+            .collect();
+        Some(String::from_utf16_lossy(&chars))
+    }
+}
+
+/// The checksum of a short name, stored in its long name entries.
+pub fn checksum(short_name: &[u8; 11]) -> u8 {
+    short_name
+        .iter()
+        .fold(0u8, |sum, &c| sum.rotate_right(1).wrapping_add(c))
+}
+
+/// Builds the long name entries for `name`, in on-disk order.
+pub fn long_name_entries(name: &str, short_name: &[u8; 11]) -> Vec<RawLongDirEntry> {
+    let mut chars: Vec<u16> = name.encode_utf16().collect();
+    let count = chars.len().div_ceil(CHARS_PER_LONG_ENTRY);
+    // The name is terminated by a NUL and padded with 0xFFFF.
+    if chars.len() % CHARS_PER_LONG_ENTRY != 0 {
+        chars.push(0);
+    }
+    chars.resize(count * CHARS_PER_LONG_ENTRY, 0xFFFF);
+
+    let checksum = checksum(short_name);
+    (1..=count)
+        .rev()
+        .map(|seq| {
+            let mut entry = RawLongDirEntry {
+                order: seq as u8,
+                attr: ATTR_LONG_NAME,
+                checksum,
+                ..Default::default()
+            };
+            if seq == count {
+                entry.order |= LAST_LONG_ENTRY;
+            }
+            let start = (seq - 1) * CHARS_PER_LONG_ENTRY;
+            entry.set_chars(
+                chars[start..start + CHARS_PER_LONG_ENTRY]
+                    .try_into()
+                    .unwrap(),
+            );
+            entry
+        })
+        .collect()
+}
+
+/// Returns the 8.3 form of `name` if the name can be stored without a long
+/// name, i.e., it is a valid upper-case 8.3 name.
+pub fn exact_short_name(name: &str) -> Option<[u8; 11]> {
+    let (base, ext) = match name.rsplit_once('.') {
+        Some((base, ext)) => (base, ext),
+        None => (name, ""),
+    };
+    let valid = |part: &str, max: usize| {
+        part.len() <= max
+            && part
+                .bytes()
+                .all(|c| is_short_name_char(c) && !c.is_ascii_lowercase())
+    };
+    if base.is_empty() || !valid(base, 8) || !valid(ext, 3) {
+        return None;
+    }
+
+    let mut short_name = [b' '; 11];
+    short_name[..base.len()].copy_from_slice(base.as_bytes());
+    short_name[8..8 + ext.len()].copy_from_slice(ext.as_bytes());
+    Some(short_name)
+}
+
+/// Generates a `BASIS~N.EXT` short name for `name` that is not in `existing`.
+pub fn generate_short_name(name: &str, existing: &[[u8; 11]]) -> Option<[u8; 11]> {
+    let to_short = |part: &str| -> Vec<u8> {
+        part.bytes()
+            .filter(|&c| c != b' ' && c != b'.')
+            .map(|c| {
+                let c = c.to_ascii_uppercase();
+                if is_short_name_char(c) { c } else { b'_' }
+            })
+            .collect()
+    };
+    let trimmed = name.trim_start_matches('.');
+    let (base, ext) = match trimmed.rsplit_once('.') {
+        Some((base, ext)) => (to_short(base), to_short(ext)),
+        None => (to_short(trimmed), Vec::new()),
+    };
+
+    let mut short_name = [b' '; 11];
+    let ext_len = ext.len().min(3);
+    short_name[8..8 + ext_len].copy_from_slice(&ext[..ext_len]);
+    for n in 1..1000000usize {
+        let tail = alloc::format!("~{}", n);
+        let base_len = base.len().min(8 - tail.len());
+        short_name[..8].fill(b' ');
+        short_name[..base_len].copy_from_slice(&base[..base_len]);
+        short_name[base_len..base_len + tail.len()].copy_from_slice(tail.as_bytes());
+        if !existing.contains(&short_name) {
+            return Some(short_name);
+        }
+    }
+    None
+}
+
+/// Checks whether `name` can be stored in a directory.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name
+            .chars()
+            .any(|c| (c as u32) < 0x20 || "\"*/:<>?\\|".contains(c))
+}
+
+fn is_short_name_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || b"!#$%&'()-@^_`{}~".contains(&c)
+}
+
+fn trim_padding(bytes: &[u8]) -> Vec<u8> {
+    let len = bytes.iter().rposition(|&c| c != b' ').map_or(0, |i| i + 1);
+    bytes[..len].to_vec()
+}
+
+/// Converts a FAT date and time, in local time which is taken as UTC, to the
+/// time since the Unix epoch.
+fn fat_timestamp(date: u16, time: u16) -> Duration {
+    // A zero date means the time is not recorded.
+    if date == 0 {
+        return Duration::ZERO;
+    }
+    let year = 1980 + (date >> 9) as i64;
+    let month = ((date >> 5) & 0xF).clamp(1, 12) as i64;
+    let day = (date & 0x1F).max(1) as i64;
+
+    // Count the days from 1970-01-01 with years starting in March, so that
+    // the leap day is the last day of a year.
+    let (year, month) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let days =
+        year * 365 + year / 4 - year / 100 + year / 400 + (153 * month + 2) / 5 + day - 1 - 719_468;
+
+    let hours = (time >> 11) as i64;
+    let minutes = ((time >> 5) & 0x3F) as i64;
+    let seconds = (time & 0x1F) as i64 * 2;
+    Duration::from_secs((days * 86400 + hours * 3600 + minutes * 60 + seconds) as u64)
+}
@@ -0,0 +1,758 @@
+use core::{
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use alloc::{
+    string::String,
+    sync::{Arc, Weak},
+    vec,
+    vec::Vec,
+};
+use log::debug;
+use ostd::{
+    mm::{Frame, PAGE_SIZE, VmReader, VmWriter, io_util::HasVmReaderWriter},
+    sync::Mutex,
+};
+
+use crate::{
+    drivers::blk::SECTOR_SIZE,
+    error::{Errno, Error, Result},
+    fs::{
+        DirVisitor, FileSystem, InodeMeta, InodeType,
+        fat::{
+            FatFs,
+            dir_entry::{
+                ATTR_ARCHIVE, ATTR_DIRECTORY, DELETED, DIR_ENTRY_SIZE, END_OF_DIR, LongNameBuilder,
+                MAX_NAME_LEN, RawDirEntry, RawLongDirEntry, exact_short_name, generate_short_name,
+                is_valid_name, long_name_entries,
+            },
+        },
+        util::{
+            buffer_cache::BUFFER_CACHE,
+            page_cache::{PageCache, PageCacheBackend},
+        },
+        visit_dir_entries,
+    },
+};
+
+/// FAT has no inode numbers. Directories are identified by their first
+/// cluster and files by the location of their directory entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InodeKey {
+    /// The first cluster of the directory, zero for the root.
+    Directory(u32),
+    /// The byte offset of the short directory entry on the device.
+    File(usize),
+}
+
+impl InodeKey {
+    fn ino(&self) -> u64 {
+        // FAT has no inode numbers. Directories are numbered by their first
+        // cluster and files by the slot of their entry, kept apart by parity.
+        match *self {
+            InodeKey::Directory(cluster) => cluster as u64 * 2 + 2,
+            InodeKey::File(entry_offset) => (entry_offset / DIR_ENTRY_SIZE) as u64 * 2 + 1,
+        }
+    }
+}
+
+pub struct FatInode {
+    key: InodeKey,
+    type_: InodeType,
+    /// The cluster chain holding the content. Empty for the FAT12/16 root
+    /// directory, which lives in a fixed region, and for empty files.
+    clusters: Mutex<Vec<u32>>,
+    size: AtomicUsize,
+    /// Serializes the operations that allocate clusters or change directory entries.
+    write_lock: Mutex<()>,
+    /// Whether the entry has been removed from its directory. The clusters
+    /// are freed once the inode is dropped, as it may still be open.
+    removed: AtomicBool,
+    page_cache: Option<Arc<PageCache>>,
+    fs: Weak<FatFs>,
+    self_ref: Weak<FatInode>,
+}
+
+/// The number of the "." and ".." entries, which come first in a listing.
+const DOTS: usize = 2;
+
+/// A parsed directory entry.
+struct DirEntry {
+    name: String,
+    raw: RawDirEntry,
+    /// The byte offset of the short entry on the device.
+    offset: usize,
+}
+
+impl FatInode {
+    pub(super) fn new_root(fs: Weak<FatFs>) -> Arc<Self> {
+        let fat_fs = fs.upgrade().expect("Filesystem has been dropped");
+        let clusters = if fat_fs.root_entry_count == 0 {
+            fat_fs.cluster_chain(fat_fs.root_cluster)
+        } else {
+            Vec::new()
+        };
+        Self::new(
+            InodeKey::Directory(0),
+            InodeType::Directory,
+            clusters,
+            0,
+            fs,
+        )
+    }
+
+    pub(super) fn new_dir(first_cluster: u32, fs: Weak<FatFs>) -> Arc<Self> {
+        let fat_fs = fs.upgrade().expect("Filesystem has been dropped");
+        let clusters = fat_fs.cluster_chain(first_cluster);
+        Self::new(
+            InodeKey::Directory(first_cluster),
+            InodeType::Directory,
+            clusters,
+            0,
+            fs,
+        )
+    }
+
+    pub(super) fn new_file(entry_offset: usize, fs: Weak<FatFs>) -> Arc<Self> {
+        let fat_fs = fs.upgrade().expect("Filesystem has been dropped");
+        let raw: RawDirEntry = BUFFER_CACHE.read_val(&fat_fs.blk_device, entry_offset);
+        let clusters = fat_fs.cluster_chain(raw.first_cluster());
+        Self::new(
+            InodeKey::File(entry_offset),
+            InodeType::File,
+            clusters,
+            raw.file_size as usize,
+            fs,
+        )
+    }
+
+    fn new(
+        key: InodeKey,
+        type_: InodeType,
+        clusters: Vec<u32>,
+        size: usize,
+        fs: Weak<FatFs>,
+    ) -> Arc<Self> {
+        Arc::new_cyclic(|weak_self: &Weak<FatInode>| {
+            let page_cache = (type_ == InodeType::File).then(|| {
+                let backend: Weak<dyn PageCacheBackend> = weak_self.clone();
+                PageCache::with_backend(backend)
+            });
+            FatInode {
+                key,
+                type_,
+                clusters: Mutex::new(clusters),
+                size: AtomicUsize::new(size),
+                write_lock: Mutex::new(()),
+                removed: AtomicBool::new(false),
+                page_cache,
+                fs,
+                self_ref: weak_self.clone(),
+            }
+        })
+    }
+
+    fn fat_fs(&self) -> Arc<FatFs> {
+        self.fs.upgrade().expect("Filesystem has been dropped")
+    }
+
+    /// Returns the device byte offsets of all entry slots of the directory.
+    fn dir_slots(&self, fs: &FatFs) -> Vec<usize> {
+        let clusters = self.clusters.lock();
+        if self.key == InodeKey::Directory(0) && fs.root_entry_count != 0 {
+            return (0..fs.root_entry_count)
+                .map(|i| fs.root_dir_offset + i * DIR_ENTRY_SIZE)
+                .collect();
+        }
+
+        clusters
+            .iter()
+            .flat_map(|&cluster| {
+                let start = fs.cluster_offset(cluster);
+                (0..fs.cluster_size / DIR_ENTRY_SIZE).map(move |i| start + i * DIR_ENTRY_SIZE)
+            })
+            .collect()
+    }
+
+    /// Reads the entries of the directory, resolving long names. Each comes
+    /// with the byte offset of its short entry in the directory.
+    fn read_entries(&self, fs: &FatFs) -> Vec<(usize, DirEntry)> {
+        let mut entries = Vec::new();
+        let mut long_name = LongNameBuilder::default();
+        for (slot, offset) in self.dir_slots(fs).into_iter().enumerate() {
+            let raw: RawDirEntry = BUFFER_CACHE.read_val(&fs.blk_device, offset);
+            match raw.name[0] {
+                END_OF_DIR => break,
+                DELETED => {
+                    long_name.clear();
+                    continue;
+                }
+                _ => {}
+            }
+
+            if raw.is_long_name() {
+                let raw_long: RawLongDirEntry = BUFFER_CACHE.read_val(&fs.blk_device, offset);
+                long_name.push(slot, &raw_long);
+                continue;
+            }
+            if raw.is_volume_id() {
+                long_name.clear();
+                continue;
+            }
+
+            let name = long_name.take(&raw).unwrap_or_else(|| raw.short_name());
+            debug!("FAT dir entry: name={}, raw={:x?}", name, raw);
+            entries.push((slot * DIR_ENTRY_SIZE, DirEntry { name, raw, offset }));
+        }
+        entries
+    }
+
+    fn find_entry(&self, fs: &FatFs, name: &str) -> Option<DirEntry> {
+        // FAT names are case-insensitive.
+        self.read_entries(fs).into_iter().find_map(|(_, entry)| {
+            (entry.name.eq_ignore_ascii_case(name)
+                || entry.raw.short_name().eq_ignore_ascii_case(name))
+            .then_some(entry)
+        })
+    }
+
+    /// The inode number of the entry, as given by `ino` of its inode.
+    fn ino_of(entry: &DirEntry) -> u64 {
+        if entry.raw.is_directory() {
+            InodeKey::Directory(entry.raw.first_cluster()).ino()
+        } else {
+            InodeKey::File(entry.offset).ino()
+        }
+    }
+
+    fn inode_of(&self, fs: &FatFs, entry: &DirEntry) -> Arc<FatInode> {
+        if entry.raw.is_directory() {
+            fs.dir_inode(entry.raw.first_cluster())
+        } else {
+            fs.file_inode(entry.offset)
+        }
+    }
+
+    /// Finds `count` consecutive free slots, growing the directory if needed.
+    fn alloc_slots(&self, fs: &FatFs, count: usize) -> Result<Vec<usize>> {
+        loop {
+            let slots = self.dir_slots(fs);
+            let mut run_start = 0;
+            let mut end_of_dir = false;
+            for (i, &offset) in slots.iter().enumerate() {
+                let first_byte: u8 = BUFFER_CACHE.read_val(&fs.blk_device, offset);
+                // Every slot after the end marker is free as well.
+                end_of_dir |= first_byte == END_OF_DIR;
+                if !end_of_dir && first_byte != DELETED {
+                    run_start = i + 1;
+                    continue;
+                }
+                if i + 1 - run_start == count {
+                    return Ok(slots[run_start..=i].to_vec());
+                }
+            }
+
+            if self.key == InodeKey::Directory(0) && fs.root_entry_count != 0 {
+                return Err(Error::new(Errno::ENOSPC));
+            }
+            let mut clusters = self.clusters.lock();
+            let cluster = fs.alloc_cluster(clusters.last().copied())?;
+            fs.zero_cluster(cluster);
+            clusters.push(cluster);
+        }
+    }
+
+    /// Makes sure that the file has clusters for its first `len` bytes.
+    fn reserve_clusters(&self, fs: &FatFs, len: usize) -> Result<()> {
+        let InodeKey::File(entry_offset) = self.key else {
+            return Ok(());
+        };
+
+        let mut clusters = self.clusters.lock();
+        let was_empty = clusters.is_empty();
+        while clusters.len() * fs.cluster_size < len {
+            let cluster = fs.alloc_cluster(clusters.last().copied())?;
+            clusters.push(cluster);
+        }
+
+        // The entry of a removed file may have been reused.
+        if was_empty && !clusters.is_empty() && !self.removed.load(Ordering::Acquire) {
+            let mut raw: RawDirEntry = BUFFER_CACHE.read_val(&fs.blk_device, entry_offset);
+            raw.set_first_cluster(clusters[0]);
+            BUFFER_CACHE.write_val(&fs.blk_device, entry_offset, &raw);
+        }
+        Ok(())
+    }
+
+    /// Frees the clusters past the first `len` bytes of the file.
+    fn release_clusters(&self, fs: &FatFs, len: usize) {
+        let InodeKey::File(entry_offset) = self.key else {
+            return;
+        };
+
+        let mut clusters = self.clusters.lock();
+        let keep = len.div_ceil(fs.cluster_size);
+        if keep >= clusters.len() {
+            return;
+        }
+        let freed = clusters.split_off(keep);
+        match clusters.last() {
+            Some(&last) => fs.write_fat(last, fs.end_of_chain() | 0x7),
+            None if !self.removed.load(Ordering::Acquire) => {
+                let mut raw: RawDirEntry = BUFFER_CACHE.read_val(&fs.blk_device, entry_offset);
+                raw.set_first_cluster(0);
+                BUFFER_CACHE.write_val(&fs.blk_device, entry_offset, &raw);
+            }
+            None => {}
+        }
+        fs.free_clusters(&freed);
+    }
+
+    fn set_size(&self, fs: &FatFs, size: usize) {
+        let InodeKey::File(entry_offset) = self.key else {
+            return;
+        };
+        self.size.store(size, Ordering::Release);
+        if self.removed.load(Ordering::Acquire) {
+            return;
+        }
+        fs.mark_dirty();
+        let mut raw: RawDirEntry = BUFFER_CACHE.read_val(&fs.blk_device, entry_offset);
+        raw.file_size = size as u32;
+        BUFFER_CACHE.write_val(&fs.blk_device, entry_offset, &raw);
+    }
+
+    /// Marks the short entry at `entry_offset` and the long name entries
+    /// before it as deleted.
+    fn delete_entry(&self, fs: &FatFs, entry_offset: usize) {
+        let slots = self.dir_slots(fs);
+        let Some(idx) = slots.iter().position(|&offset| offset == entry_offset) else {
+            return;
+        };
+        BUFFER_CACHE.write_val(&fs.blk_device, entry_offset, &DELETED);
+        for &offset in slots[..idx].iter().rev() {
+            let raw: RawDirEntry = BUFFER_CACHE.read_val(&fs.blk_device, offset);
+            if raw.name[0] == DELETED || !raw.is_long_name() {
+                break;
+            }
+            BUFFER_CACHE.write_val(&fs.blk_device, offset, &DELETED);
+        }
+    }
+
+    /// Removes the entry of `inode` from this directory and from the inode
+    /// cache, so that its clusters are freed once it is no longer used.
+    fn remove_entry(&self, fs: &FatFs, entry: &DirEntry, inode: &FatInode) {
+        fs.mark_dirty();
+        self.delete_entry(fs, entry.offset);
+        inode.removed.store(true, Ordering::Release);
+        fs.inode_cache.lock().remove(&inode.key);
+    }
+
+    /// Calls `f` with the device sector and the length of each piece of the
+    /// file in `[start, end)`. Both ends must be sector aligned.
+    fn for_each_extent(
+        &self,
+        fs: &FatFs,
+        start: usize,
+        end: usize,
+        mut f: impl FnMut(usize, usize, usize),
+    ) {
+        let clusters = self.clusters.lock();
+        let mut pos = start;
+        while pos < end {
+            let Some(&cluster) = clusters.get(pos / fs.cluster_size) else {
+                break;
+            };
+            let offset_in_cluster = pos % fs.cluster_size;
+            let len = (fs.cluster_size - offset_in_cluster).min(end - pos);
+            f(
+                pos - start,
+                (fs.cluster_offset(cluster) + offset_in_cluster) / SECTOR_SIZE,
+                len,
+            );
+            pos += len;
+        }
+    }
+}
+
+impl PageCacheBackend for FatInode {
+    fn read_page(&self, idx: usize, frame: &Frame<()>) -> Result<()> {
+        let fs = self.fat_fs();
+        let start = idx * PAGE_SIZE;
+        // Bytes beyond the end of the file must read as zeros, which new frames are.
+        let end = self
+            .size
+            .load(Ordering::Acquire)
+            .clamp(start, start + PAGE_SIZE)
+            .next_multiple_of(SECTOR_SIZE);
+
+        self.for_each_extent(&fs, start, end, |offset, sector, len| {
+            let mut writer = frame.writer().to_fallible();
+            writer.skip(offset).limit(len);
+            fs.blk_device
+                .read_to_vm_writer(sector, len / SECTOR_SIZE, &mut writer);
+        });
+
+        let size = self.size.load(Ordering::Acquire);
+        if size > start && size < end {
+            let mut writer = frame.writer().to_fallible();
+            writer.skip(size - start);
+            let _ = writer.fill_zeros(end - size);
+        }
+        Ok(())
+    }
+
+    fn write_page(&self, idx: usize, frame: &Frame<()>) -> Result<()> {
+        let fs = self.fat_fs();
+        fs.mark_dirty();
+        let start = idx * PAGE_SIZE;
+        let end = self
+            .size
+            .load(Ordering::Acquire)
+            .clamp(start, start + PAGE_SIZE)
+            .next_multiple_of(SECTOR_SIZE);
+
+        self.for_each_extent(&fs, start, end, |offset, sector, len| {
+            let mut reader = frame.reader().to_fallible();
+            reader.skip(offset).limit(len);
+            fs.blk_device
+                .write_from_vm_reader(sector, len / SECTOR_SIZE, &mut reader);
+        });
+        Ok(())
+    }
+}
+
+impl super::super::Inode for FatInode {
+    fn lookup(&self, name: &str) -> Result<Arc<dyn crate::fs::Inode>> {
+        if self.type_ != InodeType::Directory {
+            return Err(Error::new(Errno::ENOTDIR));
+        }
+
+        let fs = self.fat_fs();
+        // The root directory has no "." and ".." entries.
+        if self.key == InodeKey::Directory(0) && (name == "." || name == "..") {
+            return Ok(self.self_ref.upgrade().unwrap());
+        }
+
+        let entry = self
+            .find_entry(&fs, name)
+            .ok_or(Error::new(Errno::ENOENT))?;
+        Ok(self.inode_of(&fs, &entry))
+    }
+
+    fn readdir(&self, offset: usize, visitor: &mut DirVisitor) -> Result<usize> {
+        if self.type_ != InodeType::Directory {
+            return Err(Error::new(Errno::ENOTDIR));
+        }
+
+        let fs = self.fat_fs();
+        // The root directory has no "." and ".." entries.
+        let dots = if self.key == InodeKey::Directory(0) {
+            [".", ".."]
+                .map(|name| crate::fs::DirEntry {
+                    ino: self.key.ino(),
+                    type_: InodeType::Directory,
+                    name: String::from(name),
+                })
+                .to_vec()
+        } else {
+            Vec::new()
+        };
+        // An entry is at the byte offset of its short entry in the directory,
+        // which it keeps until it is removed. The offsets come after the dots
+        // made up for the root directory.
+        let entries = self.read_entries(&fs).into_iter().map(|(pos, entry)| {
+            let entry = crate::fs::DirEntry {
+                ino: Self::ino_of(&entry),
+                type_: if entry.raw.is_directory() {
+                    InodeType::Directory
+                } else {
+                    InodeType::File
+                },
+                name: entry.name,
+            };
+            (DOTS + pos, entry)
+        });
+        Ok(visit_dir_entries(
+            dots.into_iter().enumerate().chain(entries),
+            offset,
+            visitor,
+        ))
+    }
+
+    fn create(&self, name: &str, type_: InodeType) -> Result<Arc<dyn crate::fs::Inode>> {
+        if self.type_ != InodeType::Directory {
+            return Err(Error::new(Errno::ENOTDIR));
+        }
+        if name.len() > MAX_NAME_LEN {
+            return Err(Error::new(Errno::ENAMETOOLONG));
+        }
+        if !is_valid_name(name) {
+            return Err(Error::new(Errno::EINVAL));
+        }
+        // FAT can only store regular files and directories.
+        if type_ != InodeType::File && type_ != InodeType::Directory {
+            return Err(Error::new(Errno::EPERM));
+        }
+
+        let fs = self.fat_fs();
+        let _guard = self.write_lock.lock();
+        let entries = self.read_entries(&fs);
+        if entries
+            .iter()
+            .any(|(_, entry)| entry.name.eq_ignore_ascii_case(name))
+        {
+            return Err(Error::new(Errno::EEXIST));
+        }
+
+        let (short_name, long_entries) = match exact_short_name(name) {
+            Some(short_name) => (short_name, Vec::new()),
+            None => {
+                let existing: Vec<[u8; 11]> =
+                    entries.iter().map(|(_, entry)| entry.raw.name).collect();
+                let short_name =
+                    generate_short_name(name, &existing).ok_or(Error::new(Errno::EEXIST))?;
+                (short_name, long_name_entries(name, &short_name))
+            }
+        };
+
+        fs.mark_dirty();
+        let slots = self.alloc_slots(&fs, long_entries.len() + 1)?;
+
+        let entry = if type_ == InodeType::Directory {
+            let cluster = fs.alloc_cluster(None)?;
+            fs.zero_cluster(cluster);
+
+            // ".." points to cluster 0 if the parent is the root directory.
+            let parent_cluster = match self.key {
+                InodeKey::Directory(cluster) => cluster,
+                InodeKey::File(_) => unreachable!(),
+            };
+            let mut dot_name = [b' '; 11];
+            dot_name[0] = b'.';
+            let dot = RawDirEntry::new(dot_name, ATTR_DIRECTORY, cluster);
+            dot_name[1] = b'.';
+            let dot_dot = RawDirEntry::new(dot_name, ATTR_DIRECTORY, parent_cluster);
+            let cluster_offset = fs.cluster_offset(cluster);
+            BUFFER_CACHE.write_val(&fs.blk_device, cluster_offset, &dot);
+            BUFFER_CACHE.write_val(&fs.blk_device, cluster_offset + DIR_ENTRY_SIZE, &dot_dot);
+
+            RawDirEntry::new(short_name, ATTR_DIRECTORY, cluster)
+        } else {
+            RawDirEntry::new(short_name, ATTR_ARCHIVE, 0)
+        };
+
+        for (long_entry, &offset) in long_entries.iter().zip(slots.iter()) {
+            BUFFER_CACHE.write_val(&fs.blk_device, offset, long_entry);
+        }
+        let entry_offset = *slots.last().unwrap();
+        BUFFER_CACHE.write_val(&fs.blk_device, entry_offset, &entry);
+
+        let inode = self.inode_of(
+            &fs,
+            &DirEntry {
+                name: String::from(name),
+                raw: entry,
+                offset: entry_offset,
+            },
+        );
+        Ok(inode)
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        if self.type_ != InodeType::Directory {
+            return Err(Error::new(Errno::ENOTDIR));
+        }
+
+        let fs = self.fat_fs();
+        let _guard = self.write_lock.lock();
+        let entry = self
+            .find_entry(&fs, name)
+            .ok_or(Error::new(Errno::ENOENT))?;
+        if entry.raw.is_directory() {
+            return Err(Error::new(Errno::EISDIR));
+        }
+        let inode = self.inode_of(&fs, &entry);
+        self.remove_entry(&fs, &entry, &inode);
+        Ok(())
+    }
+
+    fn rmdir(&self, name: &str) -> Result<()> {
+        if self.type_ != InodeType::Directory {
+            return Err(Error::new(Errno::ENOTDIR));
+        }
+
+        let fs = self.fat_fs();
+        let _guard = self.write_lock.lock();
+        let entry = self
+            .find_entry(&fs, name)
+            .ok_or(Error::new(Errno::ENOENT))?;
+        if !entry.raw.is_directory() {
+            return Err(Error::new(Errno::ENOTDIR));
+        }
+        let inode = self.inode_of(&fs, &entry);
+        let _child_guard = inode.write_lock.lock();
+        if inode
+            .read_entries(&fs)
+            .iter()
+            .any(|(_, entry)| entry.name != "." && entry.name != "..")
+        {
+            return Err(Error::new(Errno::ENOTEMPTY));
+        }
+        self.remove_entry(&fs, &entry, &inode);
+        Ok(())
+    }
+
+    fn read_link(&self) -> Result<String> {
+        Err(Error::new(Errno::EINVAL))
+    }
+
+    fn write_link(&self, _target: &str) -> Result<()> {
+        Err(Error::new(Errno::EPERM))
+    }
+
+    fn read_at(&self, offset: usize, mut writer: VmWriter) -> Result<usize> {
+        let Some(page_cache) = &self.page_cache else {
+            return Err(Error::new(Errno::EISDIR));
+        };
+
+        let size = self.size.load(Ordering::Acquire);
+        if offset >= size {
+            return Ok(0);
+        }
+        let read_len = (size - offset).min(writer.avail());
+        page_cache.read(offset, read_len, &mut writer)
+    }
+
+    fn write_at(&self, offset: usize, mut reader: VmReader) -> Result<usize> {
+        let Some(page_cache) = &self.page_cache else {
+            return Err(Error::new(Errno::EISDIR));
+        };
+
+        let fs = self.fat_fs();
+        let _guard = self.write_lock.lock();
+        let end = offset + reader.remain();
+        // FAT stores the file size in 32 bits.
+        if end > u32::MAX as usize {
+            return Err(Error::new(Errno::EFBIG));
+        }
+        self.reserve_clusters(&fs, end)?;
+
+        let written = page_cache.write(offset, reader.remain(), &mut reader)?;
+        if offset + written > self.size.load(Ordering::Acquire) {
+            self.set_size(&fs, offset + written);
+        }
+        Ok(written)
+    }
+
+    fn resize(&self, new_size: usize) -> Result<()> {
+        let Some(page_cache) = &self.page_cache else {
+            return Err(Error::new(Errno::EISDIR));
+        };
+        // FAT stores the file size in 32 bits.
+        if new_size > u32::MAX as usize {
+            return Err(Error::new(Errno::EFBIG));
+        }
+
+        let fs = self.fat_fs();
+        let _guard = self.write_lock.lock();
+        let size = self.size.load(Ordering::Acquire);
+        if new_size > size {
+            // FAT has no holes, so the clusters are allocated and zeroed.
+            self.reserve_clusters(&fs, new_size)?;
+            let zeros = vec![0u8; PAGE_SIZE];
+            let mut pos = size;
+            while pos < new_size {
+                let len = (PAGE_SIZE - pos % PAGE_SIZE).min(new_size - pos);
+                let mut reader = VmReader::from(&zeros[..len]).to_fallible();
+                page_cache.write(pos, len, &mut reader)?;
+                pos += len;
+            }
+        } else {
+            // Load the last page kept, so that its tail is zeroed on disk too.
+            if new_size % PAGE_SIZE != 0 {
+                page_cache.get_page(new_size / PAGE_SIZE)?;
+            }
+            page_cache.resize(new_size);
+            self.release_clusters(&fs, new_size);
+        }
+        self.set_size(&fs, new_size);
+        Ok(())
+    }
+
+    fn metadata(&self) -> InodeMeta {
+        let fs = self.fat_fs();
+        // Directories keep their times in their "." entry, which the root
+        // directory does not have.
+        let raw = match self.key {
+            InodeKey::File(entry_offset) => Some(entry_offset),
+            InodeKey::Directory(0) => None,
+            InodeKey::Directory(cluster) => Some(fs.cluster_offset(cluster)),
+        }
+        .map(|offset| BUFFER_CACHE.read_val::<RawDirEntry>(&fs.blk_device, offset));
+        let size = self.size();
+        InodeMeta {
+            size,
+            blocks: size.div_ceil(fs.cluster_size) * (fs.cluster_size / SECTOR_SIZE),
+            blksize: fs.cluster_size,
+            atime: raw.map_or(Duration::ZERO, |raw| raw.accessed_time()),
+            mtime: raw.map_or(Duration::ZERO, |raw| raw.modified_time()),
+            ctime: raw.map_or(Duration::ZERO, |raw| raw.modified_time()),
+            ..InodeMeta::new(self.ino(), self.type_, self.mode())
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self.type_ {
+            InodeType::Directory => {
+                let fs = self.fat_fs();
+                self.dir_slots(&fs).len() * DIR_ENTRY_SIZE
+            }
+            _ => self.size.load(Ordering::Acquire),
+        }
+    }
+
+    fn typ(&self) -> InodeType {
+        self.type_
+    }
+
+    fn ino(&self) -> u64 {
+        self.key.ino()
+    }
+
+    fn page_cache(&self) -> Option<Arc<PageCache>> {
+        self.page_cache.clone()
+    }
+
+    fn fs(&self) -> Option<Arc<dyn FileSystem>> {
+        self.fs.upgrade().map(|fs| fs as Arc<dyn FileSystem>)
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    fn sync_data(&self) -> Result<()> {
+        if let Some(page_cache) = &self.page_cache {
+            page_cache.flush()?;
+        }
+
+        // The directory entry and the FAT live in the buffer cache.
+        let fs = self.fat_fs();
+        BUFFER_CACHE.sync(&fs.blk_device);
+        fs.blk_device.flush();
+        Ok(())
+    }
+}
+
+impl Drop for FatInode {
+    fn drop(&mut self) {
+        if !*self.removed.get_mut() {
+            return;
+        }
+        if let Some(fs) = self.fs.upgrade() {
+            fs.free_clusters(self.clusters.get_mut());
+        }
+    }
+}
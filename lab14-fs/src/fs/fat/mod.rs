@@ -0,0 +1,426 @@
+//! FAT12/16/32 file system implementation
+//!
+//! References: Microsoft FAT Specification (fatgen103) and https://wiki.osdev.org/FAT
+
+use core::fmt::Debug;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use alloc::sync::Weak;
+use alloc::{collections::btree_map::BTreeMap, sync::Arc, vec, vec::Vec};
+use log::{debug, info};
+use ostd::sync::Mutex;
+
+use crate::fs::util::buffer_cache::BUFFER_CACHE;
+use crate::{
+    drivers::blk::{BlockDevice, SECTOR_SIZE},
+    error::{Errno, Error, Result},
+    fs::{
+        FileSystem, FsStats,
+        fat::{
+            boot_sector::{BootSector, FatType},
+            inode::{FatInode, InodeKey},
+        },
+    },
+};
+
+mod boot_sector;
+mod dir_entry;
+mod inode;
+
+const MSDOS_SUPER_MAGIC: u64 = 0x4d44;
+/// Cluster numbers 0 and 1 are reserved, the data area starts at cluster 2.
+const FIRST_DATA_CLUSTER: u32 = 2;
+
+pub struct FatFs {
+    blk_device: Arc<dyn BlockDevice>,
+    fat_type: FatType,
+    num_fats: usize,
+    /// The byte offset of the first FAT.
+    fat_offset: usize,
+    /// The size of one FAT in bytes.
+    fat_size: usize,
+    /// The byte offset and the number of entries of the fixed root directory (FAT12/16).
+    root_dir_offset: usize,
+    root_entry_count: usize,
+    /// The first cluster of the root directory (FAT32).
+    root_cluster: u32,
+    /// The byte offset of cluster 2.
+    data_offset: usize,
+    cluster_size: usize,
+    cluster_count: u32,
+    /// The byte offset of the FSInfo sector (FAT32).
+    fs_info_offset: Option<usize>,
+
+    alloc_state: Mutex<AllocState>,
+    inode_cache: Mutex<BTreeMap<InodeKey, Arc<FatInode>>>,
+    /// Whether the clean bit was cleared, which is done on the first write
+    /// so that a volume only read stays untouched.
+    marked_dirty: AtomicBool,
+
+    self_ref: Weak<FatFs>,
+}
+
+struct AllocState {
+    free_clusters: u32,
+    /// Where to start looking for a free cluster.
+    next_free: u32,
+}
+
+impl FatFs {
+    pub fn new(blk_device: Arc<dyn BlockDevice>) -> Result<Arc<Self>> {
+        let mut raw_boot_sector = [0u8; SECTOR_SIZE];
+        BUFFER_CACHE.read_bytes(&blk_device, 0, &mut raw_boot_sector);
+        let boot_sector = BootSector::parse(&raw_boot_sector).ok_or(Error::new(Errno::EINVAL))?;
+
+        debug!("FAT boot sector: {:#x?}", boot_sector);
+
+        let fat_type = boot_sector.fat_type();
+        let sector_size = boot_sector.bytes_per_sector as usize;
+        let fat_offset = boot_sector.reserved_sectors as usize * sector_size;
+        let fat_size = boot_sector.fat_size as usize * sector_size;
+        let root_dir_offset = fat_offset + boot_sector.num_fats as usize * fat_size;
+        let fs_info_offset = (fat_type == FatType::Fat32 && boot_sector.fs_info_sector != 0)
+            .then(|| boot_sector.fs_info_sector as usize * sector_size)
+            .filter(|&offset| {
+                let mut raw_fs_info = [0u8; SECTOR_SIZE];
+                BUFFER_CACHE.read_bytes(&blk_device, offset, &mut raw_fs_info);
+                boot_sector::is_valid_fs_info(&raw_fs_info)
+            });
+
+        let fs = Arc::new_cyclic(|fs| FatFs {
+            blk_device,
+            fat_type,
+            num_fats: boot_sector.num_fats as usize,
+            fat_offset,
+            fat_size,
+            root_dir_offset,
+            root_entry_count: boot_sector.root_entry_count as usize,
+            root_cluster: boot_sector.root_cluster,
+            data_offset: boot_sector.first_data_sector() as usize * sector_size,
+            cluster_size: boot_sector.sectors_per_cluster as usize * sector_size,
+            cluster_count: boot_sector.cluster_count(),
+            fs_info_offset,
+            alloc_state: Mutex::new(AllocState {
+                free_clusters: 0,
+                next_free: FIRST_DATA_CLUSTER,
+            }),
+            inode_cache: Mutex::new(BTreeMap::new()),
+            marked_dirty: AtomicBool::new(false),
+            self_ref: fs.clone(),
+        });
+
+        *fs.alloc_state.lock() = fs.load_alloc_state();
+
+        info!("FAT: mounted {:?} volume, {:#?}", fs.fat_type, fs);
+        Ok(fs)
+    }
+
+    /// Gets the inode of the directory starting at `cluster`. Zero means the root.
+    fn dir_inode(&self, cluster: u32) -> Arc<FatInode> {
+        let is_root =
+            cluster == 0 || (self.fat_type == FatType::Fat32 && cluster == self.root_cluster);
+        let cluster = if is_root { 0 } else { cluster };
+        let key = InodeKey::Directory(cluster);
+
+        let mut inode_cache = self.inode_cache.lock();
+        if let Some(inode) = inode_cache.get(&key) {
+            return inode.clone();
+        }
+
+        let inode = if cluster == 0 {
+            FatInode::new_root(self.self_ref.clone())
+        } else {
+            FatInode::new_dir(cluster, self.self_ref.clone())
+        };
+        inode_cache.insert(key, inode.clone());
+        inode
+    }
+
+    /// Gets the inode of the file whose short directory entry is at `entry_offset`.
+    fn file_inode(&self, entry_offset: usize) -> Arc<FatInode> {
+        let key = InodeKey::File(entry_offset);
+
+        let mut inode_cache = self.inode_cache.lock();
+        if let Some(inode) = inode_cache.get(&key) {
+            return inode.clone();
+        }
+
+        let inode = FatInode::new_file(entry_offset, self.self_ref.clone());
+        inode_cache.insert(key, inode.clone());
+        inode
+    }
+
+    /// Returns the byte offset of `cluster` on the device.
+    fn cluster_offset(&self, cluster: u32) -> usize {
+        self.data_offset + (cluster - FIRST_DATA_CLUSTER) as usize * self.cluster_size
+    }
+
+    fn is_data_cluster(&self, cluster: u32) -> bool {
+        (FIRST_DATA_CLUSTER..self.cluster_count + FIRST_DATA_CLUSTER).contains(&cluster)
+    }
+
+    /// The smallest FAT entry value marking the end of a cluster chain.
+    fn end_of_chain(&self) -> u32 {
+        match self.fat_type {
+            FatType::Fat12 => 0xFF8,
+            FatType::Fat16 => 0xFFF8,
+            FatType::Fat32 => 0x0FFF_FFF8,
+        }
+    }
+
+    /// Reads the FAT entry of `cluster` from the first FAT.
+    fn read_fat(&self, cluster: u32) -> u32 {
+        let n = cluster as usize;
+        match self.fat_type {
+            FatType::Fat12 => {
+                let val: u16 = self.read_fat_val(n + n / 2);
+                if n % 2 == 0 {
+                    (val & 0xFFF) as u32
+                } else {
+                    (val >> 4) as u32
+                }
+            }
+            FatType::Fat16 => self.read_fat_val::<u16>(n * 2) as u32,
+            FatType::Fat32 => self.read_fat_val::<u32>(n * 4) & 0x0FFF_FFFF,
+        }
+    }
+
+    /// Writes the FAT entry of `cluster` to every copy of the FAT.
+    fn write_fat(&self, cluster: u32, value: u32) {
+        self.mark_dirty();
+        let n = cluster as usize;
+        match self.fat_type {
+            FatType::Fat12 => {
+                let old: u16 = self.read_fat_val(n + n / 2);
+                let new = if n % 2 == 0 {
+                    (old & 0xF000) | (value as u16 & 0xFFF)
+                } else {
+                    (old & 0x000F) | ((value as u16) << 4)
+                };
+                self.write_fat_val(n + n / 2, &new);
+            }
+            FatType::Fat16 => self.write_fat_val(n * 2, &(value as u16)),
+            FatType::Fat32 => {
+                // The high 4 bits are reserved and must be preserved.
+                let old: u32 = self.read_fat_val(n * 4);
+                self.write_fat_val(n * 4, &((old & 0xF000_0000) | (value & 0x0FFF_FFFF)));
+            }
+        }
+    }
+
+    fn read_fat_val<T: ostd::Pod>(&self, offset: usize) -> T {
+        BUFFER_CACHE.read_val(&self.blk_device, self.fat_offset + offset)
+    }
+
+    fn write_fat_val<T: ostd::Pod>(&self, offset: usize, val: &T) {
+        for i in 0..self.num_fats {
+            BUFFER_CACHE.write_val(
+                &self.blk_device,
+                self.fat_offset + i * self.fat_size + offset,
+                val,
+            );
+        }
+    }
+
+    /// Returns the cluster following `cluster` in its chain.
+    fn next_cluster(&self, cluster: u32) -> Option<u32> {
+        let next = self.read_fat(cluster);
+        (next < self.end_of_chain() && self.is_data_cluster(next)).then_some(next)
+    }
+
+    /// Collects the clusters of the chain starting at `first`.
+    fn cluster_chain(&self, first: u32) -> Vec<u32> {
+        let mut chain = Vec::new();
+        let mut cluster = self.is_data_cluster(first).then_some(first);
+        while let Some(current) = cluster {
+            chain.push(current);
+            // A corrupted FAT may contain loops.
+            if chain.len() > self.cluster_count as usize {
+                break;
+            }
+            cluster = self.next_cluster(current);
+        }
+        chain
+    }
+
+    /// Allocates a free cluster and appends it to the chain ending at `prev`.
+    fn alloc_cluster(&self, prev: Option<u32>) -> Result<u32> {
+        let mut alloc_state = self.alloc_state.lock();
+        let start = alloc_state.next_free.max(FIRST_DATA_CLUSTER);
+        let end = self.cluster_count + FIRST_DATA_CLUSTER;
+        let cluster = (start..end)
+            .chain(FIRST_DATA_CLUSTER..start)
+            .find(|&cluster| self.read_fat(cluster) == 0)
+            .ok_or(Error::new(Errno::ENOSPC))?;
+
+        self.write_fat(cluster, self.end_of_chain() | 0x7);
+        if let Some(prev) = prev {
+            self.write_fat(prev, cluster);
+        }
+        alloc_state.free_clusters = alloc_state.free_clusters.saturating_sub(1);
+        alloc_state.next_free = cluster + 1;
+        Ok(cluster)
+    }
+
+    /// Returns the clusters of `chain` to the free clusters.
+    fn free_clusters(&self, chain: &[u32]) {
+        let mut alloc_state = self.alloc_state.lock();
+        for &cluster in chain {
+            self.write_fat(cluster, 0);
+        }
+        alloc_state.free_clusters =
+            (alloc_state.free_clusters + chain.len() as u32).min(self.cluster_count);
+        if let Some(&first) = chain.iter().min() {
+            alloc_state.next_free = alloc_state.next_free.min(first);
+        }
+    }
+
+    /// Fills `cluster` with zeros through the buffer cache.
+    fn zero_cluster(&self, cluster: u32) {
+        let zeros = vec![0u8; self.cluster_size];
+        BUFFER_CACHE.write_bytes(&self.blk_device, self.cluster_offset(cluster), &zeros);
+    }
+
+    /// Reads the free cluster count from FSInfo, or counts the free clusters.
+    fn load_alloc_state(&self) -> AllocState {
+        if let Some(offset) = self.fs_info_offset {
+            let free_clusters: u32 = BUFFER_CACHE.read_val(
+                &self.blk_device,
+                offset + boot_sector::FS_INFO_FREE_COUNT_OFFSET,
+            );
+            let next_free: u32 = BUFFER_CACHE.read_val(
+                &self.blk_device,
+                offset + boot_sector::FS_INFO_NEXT_FREE_OFFSET,
+            );
+            if free_clusters <= self.cluster_count {
+                return AllocState {
+                    free_clusters,
+                    next_free: if self.is_data_cluster(next_free) {
+                        next_free
+                    } else {
+                        FIRST_DATA_CLUSTER
+                    },
+                };
+            }
+        }
+
+        let free_clusters = (FIRST_DATA_CLUSTER..self.cluster_count + FIRST_DATA_CLUSTER)
+            .filter(|&cluster| self.read_fat(cluster) == 0)
+            .count() as u32;
+        AllocState {
+            free_clusters,
+            next_free: FIRST_DATA_CLUSTER,
+        }
+    }
+
+    /// Records the allocation hints in FSInfo.
+    fn store_alloc_state(&self) {
+        let Some(offset) = self.fs_info_offset else {
+            return;
+        };
+        let alloc_state = self.alloc_state.lock();
+        BUFFER_CACHE.write_val(
+            &self.blk_device,
+            offset + boot_sector::FS_INFO_FREE_COUNT_OFFSET,
+            &alloc_state.free_clusters,
+        );
+        BUFFER_CACHE.write_val(
+            &self.blk_device,
+            offset + boot_sector::FS_INFO_NEXT_FREE_OFFSET,
+            &alloc_state.next_free,
+        );
+    }
+
+    /// Marks the volume as dirty until it is unmounted again. Called before
+    /// anything is written to the device.
+    fn mark_dirty(&self) {
+        if !self.marked_dirty.swap(true, Ordering::AcqRel) {
+            self.set_clean(false);
+        }
+    }
+
+    /// Sets or clears the "volume cleanly unmounted" bit kept in FAT entry 1.
+    fn set_clean(&self, clean: bool) {
+        let clean_bit = match self.fat_type {
+            FatType::Fat12 => return,
+            FatType::Fat16 => 0x8000,
+            FatType::Fat32 => 0x0800_0000,
+        };
+        let entry = self.read_fat(1);
+        let entry = if clean {
+            entry | clean_bit
+        } else {
+            entry & !clean_bit
+        };
+        self.write_fat(1, entry);
+    }
+}
+
+impl Debug for FatFs {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("FatFs")
+            .field("fat_type", &self.fat_type)
+            .field("num_fats", &self.num_fats)
+            .field("fat_offset", &self.fat_offset)
+            .field("fat_size", &self.fat_size)
+            .field("data_offset", &self.data_offset)
+            .field("cluster_size", &self.cluster_size)
+            .field("cluster_count", &self.cluster_count)
+            .finish()
+    }
+}
+
+impl FileSystem for FatFs {
+    fn name(&self) -> &str {
+        "vfat"
+    }
+
+    fn root_inode(&self) -> Arc<dyn crate::fs::Inode> {
+        self.dir_inode(0)
+    }
+
+    fn stats(&self) -> FsStats {
+        let free_clusters = self.alloc_state.lock().free_clusters as u64;
+        // FAT has no inodes, so it reports none.
+        FsStats {
+            magic: MSDOS_SUPER_MAGIC,
+            block_size: self.cluster_size,
+            total_blocks: self.cluster_count as u64,
+            free_blocks: free_clusters,
+            avail_blocks: free_clusters,
+            total_inodes: 0,
+            free_inodes: 0,
+            name_max: dir_entry::MAX_NAME_LEN,
+        }
+    }
+
+    fn sync(&self) -> Result<()> {
+        let inodes: Vec<Arc<FatInode>> = self.inode_cache.lock().values().cloned().collect();
+        for inode in inodes {
+            if let Some(page_cache) = crate::fs::Inode::page_cache(inode.as_ref()) {
+                page_cache.flush()?;
+            }
+        }
+
+        if self.marked_dirty.load(Ordering::Acquire) {
+            self.store_alloc_state();
+        }
+        BUFFER_CACHE.sync(&self.blk_device);
+        self.blk_device.flush();
+        Ok(())
+    }
+
+    fn unmount(&self) -> Result<()> {
+        self.sync()?;
+        // A volume never written is left as it was found.
+        if !self.marked_dirty.load(Ordering::Acquire) {
+            return Ok(());
+        }
+
+        self.set_clean(true);
+        BUFFER_CACHE.sync(&self.blk_device);
+        self.blk_device.flush();
+        info!("FAT: unmounted cleanly");
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use alloc::{sync::Arc, vec};
+use alloc::{string::String, sync::Arc, vec};
 use ostd::{
     early_print,
     mm::{Fallible, FallibleVmRead, VmReader, VmWriter},
@@ -7,54 +7,176 @@ use ostd::{
 use crate::{
     console::receive_str,
     error::{Errno, Error, Result},
-    fs::Inode,
+    fs::{DirVisitor, Inode, InodeMeta, InodeType, Path, makedev},
 };
-use core::str;
+
+bitflags::bitflags! {
+    pub struct OpenFlags: u32 {
+        const O_WRONLY = 1 << 0;
+        const O_RDWR = 1 << 1;
+        const O_CREAT = 1 << 6;
+        const O_EXCL = 1 << 7;
+        const O_NOCTTY = 1 << 8;
+        const O_TRUNC = 1 << 9;
+        const O_APPEND = 1 << 10;
+        const O_NONBLOCK = 1 << 11;
+        const O_DIRECTORY = 1 << 16;
+        const O_NOFOLLOW = 1 << 17;
+        const O_CLOEXEC = 1 << 19;
+    }
+}
+
+impl OpenFlags {
+    /// The flags that only matter when opening a file.
+    pub const CREATION_FLAGS: Self = Self::O_CREAT
+        .union(Self::O_EXCL)
+        .union(Self::O_NOCTTY)
+        .union(Self::O_TRUNC)
+        .union(Self::O_DIRECTORY)
+        .union(Self::O_NOFOLLOW)
+        .union(Self::O_CLOEXEC);
+    /// The flags that `fcntl(F_SETFL)` may change.
+    pub const SETTABLE_FLAGS: Self = Self::O_APPEND.union(Self::O_NONBLOCK);
+
+    pub fn is_readable(&self) -> bool {
+        !self.contains(Self::O_WRONLY)
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.intersects(Self::O_WRONLY | Self::O_RDWR)
+    }
+
+    /// These flags with the settable ones taken from `flags`.
+    pub fn with_status_flags(self, flags: Self) -> Self {
+        (self - Self::SETTABLE_FLAGS) | (flags & Self::SETTABLE_FLAGS)
+    }
+}
+
+/// The position a file offset is set relative to.
+#[derive(Debug, Clone, Copy)]
+pub enum SeekFrom {
+    Start(usize),
+    Current(isize),
+    End(isize),
+}
 
 pub trait FileLike: Sync + Send {
     fn read(&self, writer: VmWriter) -> Result<usize>;
     fn write(&self, reader: VmReader) -> Result<usize>;
 
+    /// Reads at `offset` without using or moving the file offset.
+    fn read_at(&self, _offset: usize, _writer: VmWriter) -> Result<usize> {
+        Err(Error::new(Errno::ESPIPE))
+    }
+
+    /// Writes at `offset` without using or moving the file offset.
+    fn write_at(&self, _offset: usize, _reader: VmReader) -> Result<usize> {
+        Err(Error::new(Errno::ESPIPE))
+    }
+
+    fn metadata(&self) -> InodeMeta;
+
+    /// Lists the directory entries from the file offset on, and moves the
+    /// offset past those taken by `visitor`. See [`Inode::readdir`].
+    fn readdir(&self, _visitor: &mut DirVisitor) -> Result<()> {
+        Err(Error::new(Errno::ENOTDIR))
+    }
+
+    /// Moves the file offset. Returns the new offset.
+    fn seek(&self, _pos: SeekFrom) -> Result<usize> {
+        Err(Error::new(Errno::ESPIPE))
+    }
+
     fn as_inode(&self) -> Option<Arc<dyn Inode>> {
         None
     }
+
+    /// The location of the file in the directory tree, if it has one.
+    fn as_path(&self) -> Option<Path> {
+        None
+    }
+
+    /// The access mode and the status flags (e.g., `O_APPEND`) of the open file.
+    fn status_flags(&self) -> OpenFlags {
+        OpenFlags::O_RDWR
+    }
+
+    /// Sets the status flags. Only `O_APPEND` and `O_NONBLOCK` can be changed,
+    /// and files that do not support them ignore them.
+    fn set_status_flags(&self, _flags: OpenFlags) {}
 }
 
-pub struct Stdin;
+pub(super) const CONSOLE_MAJOR: u32 = 5;
+pub(super) const CONSOLE_MINOR: u32 = 1;
 
-impl FileLike for Stdin {
-    fn read(&self, mut buf: VmWriter) -> Result<usize> {
-        let mut read_len = 0;
-        let mut need_return = false;
-
-        while !need_return {
-            let mut callback = |mut reader: VmReader<Fallible>| {
-                while reader.has_remain() {
-                    if let Some(ascii_char) =
-                        core::ascii::Char::from_u8(reader.read_val::<u8>().unwrap())
-                    {
-                        read_len += 1;
-                        // Return.
-                        if ascii_char.to_u8() == 13 {
-                            need_return = true;
-                            // We convert "Return" to "New Line" (Ascii 10)
-                            buf.write_val::<u8>(&10).unwrap();
-                        }
-                        // Output the character, although we cannot use backspace and other special char :)
-                        early_print!("{}", ascii_char);
-                        buf.write_val(&ascii_char.to_u8()).unwrap();
+/// The attributes of the console, i.e., of `/dev/console`.
+fn console_metadata() -> InodeMeta {
+    InodeMeta {
+        rdev: makedev(CONSOLE_MAJOR, CONSOLE_MINOR),
+        blksize: 1024,
+        ..InodeMeta::new(0, InodeType::CharDevice, 0o620)
+    }
+}
+
+/// Reads a line from the console, echoing it.
+pub(super) fn read_console(mut buf: VmWriter) -> Result<usize> {
+    let mut read_len = 0;
+    let mut need_return = false;
+
+    while !need_return {
+        let mut callback = |mut reader: VmReader<Fallible>| {
+            while reader.has_remain() {
+                if let Some(ascii_char) =
+                    core::ascii::Char::from_u8(reader.read_val::<u8>().unwrap())
+                {
+                    read_len += 1;
+                    // Return.
+                    if ascii_char.to_u8() == 13 {
+                        need_return = true;
+                        // We convert "Return" to "New Line" (Ascii 10)
+                        buf.write_val::<u8>(&10).unwrap();
                     }
+                    // Output the character, although we cannot use backspace and other special char :)
+                    early_print!("{}", ascii_char);
+                    buf.write_val(&ascii_char.to_u8()).unwrap();
                 }
-            };
+            }
+        };
 
-            receive_str(&mut callback);
-        }
-        Ok(read_len)
+        receive_str(&mut callback);
+    }
+    Ok(read_len)
+}
+
+pub(super) fn write_console(mut buf: VmReader) -> Result<usize> {
+    let mut buffer = vec![0u8; buf.remain()];
+    buf.read_fallible(&mut VmWriter::from(&mut buffer as &mut [u8]))
+        .map_err(|_| Error::new(Errno::EFAULT))?;
+
+    // Binary data may be written, which is printed as replacement characters.
+    early_print!("{}", String::from_utf8_lossy(&buffer));
+
+    Ok(buffer.len())
+}
+
+pub struct Stdin;
+
+impl FileLike for Stdin {
+    fn read(&self, buf: VmWriter) -> Result<usize> {
+        read_console(buf)
     }
 
     fn write(&self, _buf: VmReader) -> Result<usize> {
         Err(Error::new(Errno::ENOSYS))
     }
+
+    fn status_flags(&self) -> OpenFlags {
+        OpenFlags::empty()
+    }
+
+    fn metadata(&self) -> InodeMeta {
+        console_metadata()
+    }
 }
 
 pub struct Stdout;
@@ -64,14 +186,16 @@ impl FileLike for Stdout {
         Err(Error::new(Errno::ENOSYS))
     }
 
-    fn write(&self, mut buf: VmReader) -> Result<usize> {
-        let mut buffer = vec![0u8; buf.remain()];
-        buf.read_fallible(&mut VmWriter::from(&mut buffer as &mut [u8]))
-            .unwrap();
+    fn write(&self, buf: VmReader) -> Result<usize> {
+        write_console(buf)
+    }
 
-        early_print!("{}", str::from_utf8(&buffer).unwrap());
+    fn status_flags(&self) -> OpenFlags {
+        OpenFlags::O_WRONLY
+    }
 
-        Ok(buffer.len())
+    fn metadata(&self) -> InodeMeta {
+        console_metadata()
     }
 }
 
@@ -82,13 +206,15 @@ impl FileLike for Stderr {
         Err(Error::new(Errno::ENOSYS))
     }
 
-    fn write(&self, mut buf: VmReader) -> Result<usize> {
-        let mut buffer = vec![0u8; buf.remain()];
-        buf.read_fallible(&mut VmWriter::from(&mut buffer as &mut [u8]))
-            .unwrap();
+    fn write(&self, buf: VmReader) -> Result<usize> {
+        write_console(buf)
+    }
 
-        early_print!("{}", str::from_utf8(&buffer).unwrap());
+    fn status_flags(&self) -> OpenFlags {
+        OpenFlags::O_WRONLY
+    }
 
-        Ok(buffer.len())
+    fn metadata(&self) -> InodeMeta {
+        console_metadata()
     }
 }
@@ -1,22 +1,43 @@
 use alloc::{sync::Arc, vec::Vec};
 
+use crate::error::{Errno, Error, Result};
 use crate::fs::{FileLike, Stderr, Stdin, Stdout};
 
 pub type FileDescriptor = i32;
 
+/// The max number of file descriptors of a process.
+pub const MAX_FDS: usize = 1024;
+
+bitflags::bitflags! {
+    /// The flags of a file descriptor, as opposed to those of the open file.
+    pub struct FdFlags: u32 {
+        /// Close the file descriptor on `execve`.
+        const FD_CLOEXEC = 1;
+    }
+}
+
 /// Represents an open file entry
 pub struct FileEntry {
     file: Arc<dyn FileLike>,
+    flags: FdFlags,
 }
 
 impl FileEntry {
-    pub fn new(file: Arc<dyn FileLike>) -> Self {
-        FileEntry { file }
+    pub fn new(file: Arc<dyn FileLike>, flags: FdFlags) -> Self {
+        FileEntry { file, flags }
     }
 
     pub fn file(&self) -> &Arc<dyn FileLike> {
         &self.file
     }
+
+    pub fn flags(&self) -> FdFlags {
+        self.flags
+    }
+
+    pub fn set_flags(&mut self, flags: FdFlags) {
+        self.flags = flags;
+    }
 }
 
 /// File table structure
@@ -44,6 +65,7 @@ impl FileTable {
             if let Some(e) = entry {
                 new_table.push(Some(FileEntry {
                     file: e.file.clone(),
+                    flags: e.flags,
                 }));
             } else {
                 new_table.push(None);
@@ -59,12 +81,15 @@ impl FileTable {
         let mut table = Vec::new();
         table.push(Some(FileEntry {
             file: Arc::new(Stdin),
+            flags: FdFlags::empty(),
         }));
         table.push(Some(FileEntry {
             file: Arc::new(Stdout),
+            flags: FdFlags::empty(),
         }));
         table.push(Some(FileEntry {
             file: Arc::new(Stderr),
+            flags: FdFlags::empty(),
         }));
         FileTable {
             table,
@@ -72,27 +97,73 @@ impl FileTable {
         }
     }
 
-    pub fn insert(&mut self, entry: FileEntry) -> FileDescriptor {
-        let fd = if self.fds_in_use == self.table.len() {
-            self.table.push(Some(entry));
-            self.fds_in_use as FileDescriptor
-        } else {
-            let index = self.table.iter().position(|e| e.is_none()).unwrap();
-            self.table[index] = Some(entry);
-            index as FileDescriptor
-        };
-        self.fds_in_use += 1;
-        fd
+    /// Inserts `entry` at the lowest free file descriptor.
+    pub fn insert(&mut self, entry: FileEntry) -> Result<FileDescriptor> {
+        self.insert_from(0, entry)
+    }
+
+    /// Inserts `entry` at the lowest free file descriptor not less than `min_fd`.
+    pub fn insert_from(
+        &mut self,
+        min_fd: FileDescriptor,
+        entry: FileEntry,
+    ) -> Result<FileDescriptor> {
+        if min_fd < 0 || min_fd as usize >= MAX_FDS {
+            return Err(Error::new(Errno::EINVAL));
+        }
+        let fd = (min_fd as usize..MAX_FDS)
+            .find(|&fd| self.table.get(fd).is_none_or(|e| e.is_none()))
+            .ok_or(Error::new(Errno::EMFILE))?;
+        self.put(fd as FileDescriptor, entry);
+        Ok(fd as FileDescriptor)
+    }
+
+    /// Inserts `entry` at `fd`, returning the entry it replaces.
+    pub fn put(&mut self, fd: FileDescriptor, entry: FileEntry) -> Option<FileEntry> {
+        let fd = fd as usize;
+        if fd >= self.table.len() {
+            self.table.resize_with(fd + 1, || None);
+        }
+        let old_entry = self.table[fd].replace(entry);
+        if old_entry.is_none() {
+            self.fds_in_use += 1;
+        }
+        old_entry
     }
 
     pub fn get(&self, fd: FileDescriptor) -> Option<&FileEntry> {
         self.table.get(fd as usize)?.as_ref()
     }
 
+    /// The open file descriptors and their entries, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = (FileDescriptor, &FileEntry)> {
+        self.table
+            .iter()
+            .enumerate()
+            .filter_map(|(fd, entry)| Some((fd as FileDescriptor, entry.as_ref()?)))
+    }
+
+    pub fn get_mut(&mut self, fd: FileDescriptor) -> Option<&mut FileEntry> {
+        self.table.get_mut(fd as usize)?.as_mut()
+    }
+
     /// Closes a file descriptor
     pub fn close(&mut self, fd: FileDescriptor) -> Option<FileEntry> {
         let entry = self.table.get_mut(fd as usize)?.take()?;
         self.fds_in_use -= 1;
         Some(entry)
     }
+
+    /// Closes the file descriptors marked with `FD_CLOEXEC`.
+    pub fn close_on_exec(&mut self) {
+        for slot in self.table.iter_mut() {
+            if slot
+                .as_ref()
+                .is_some_and(|e| e.flags.contains(FdFlags::FD_CLOEXEC))
+            {
+                *slot = None;
+                self.fds_in_use -= 1;
+            }
+        }
+    }
 }
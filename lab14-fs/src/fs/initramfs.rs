@@ -0,0 +1,119 @@
+//! Unpacks a newc cpio archive into a directory tree.
+//!
+//! References: https://www.kernel.org/doc/html/latest/driver-api/early-userspace/buffer-format.html
+
+use alloc::sync::Arc;
+use log::{debug, warn};
+use ostd::mm::VmReader;
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::{Inode, InodeType};
+
+const NEWC_MAGIC: &[u8] = b"070701";
+/// The magic of the newc format with checksums, which we do not verify.
+const NEWC_CRC_MAGIC: &[u8] = b"070702";
+/// The length of the magic and the 13 8-digit hex fields.
+const HEADER_LEN: usize = 110;
+const TRAILER_NAME: &str = "TRAILER!!!";
+
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+const S_IFLNK: u32 = 0o120000;
+
+/// Extracts every entry of `archive` under the directory `root`.
+pub fn unpack(archive: &[u8], root: &Arc<dyn Inode>) -> Result<()> {
+    let mut offset = 0;
+    loop {
+        let header = archive
+            .get(offset..offset + HEADER_LEN)
+            .ok_or(Error::new(Errno::EINVAL))?;
+        let magic = &header[..6];
+        if magic != NEWC_MAGIC && magic != NEWC_CRC_MAGIC {
+            return Err(Error::new(Errno::EINVAL));
+        }
+
+        // Fields: ino, mode, uid, gid, nlink, mtime, filesize, devmajor,
+        // devminor, rdevmajor, rdevminor, namesize, check.
+        let field = |idx: usize| parse_hex(&header[6 + idx * 8..6 + (idx + 1) * 8]);
+        let mode = field(1)?;
+        let owner = (field(2)?, field(3)?);
+        let file_size = field(6)? as usize;
+        let name_size = field(11)? as usize;
+
+        let name_start = offset + HEADER_LEN;
+        let name = archive
+            .get(name_start..name_start + name_size)
+            .ok_or(Error::new(Errno::EINVAL))?;
+        // The name is NUL terminated.
+        let name = core::str::from_utf8(&name[..name_size.saturating_sub(1)])
+            .map_err(|_| Error::new(Errno::EINVAL))?;
+        if name == TRAILER_NAME {
+            return Ok(());
+        }
+
+        let data_start = (name_start + name_size).next_multiple_of(4);
+        let data = archive
+            .get(data_start..data_start + file_size)
+            .ok_or(Error::new(Errno::EINVAL))?;
+        offset = (data_start + file_size).next_multiple_of(4);
+
+        let path = name.trim_start_matches("./").trim_start_matches('/');
+        if path.is_empty() || path == "." {
+            continue;
+        }
+        debug!("initramfs: {} mode={:o} size={}", path, mode, file_size);
+        if let Err(err) = unpack_entry(root, path, mode, owner, data) {
+            warn!("initramfs: failed to unpack {}: {:?}", path, err);
+        }
+    }
+}
+
+fn unpack_entry(
+    root: &Arc<dyn Inode>,
+    path: &str,
+    mode: u32,
+    (uid, gid): (u32, u32),
+    data: &[u8],
+) -> Result<()> {
+    let (parent_path, name) = path.rsplit_once('/').unwrap_or(("", path));
+    let parent = make_dirs(root, parent_path)?;
+
+    let inode = match mode & S_IFMT {
+        S_IFDIR => match parent.lookup(name) {
+            Ok(inode) if inode.typ() == InodeType::Directory => inode,
+            _ => parent.create(name, InodeType::Directory)?,
+        },
+        S_IFREG => {
+            let inode = parent.create(name, InodeType::File)?;
+            inode.write_at(0, VmReader::from(data).to_fallible())?;
+            inode
+        }
+        S_IFLNK => {
+            let target = core::str::from_utf8(data).map_err(|_| Error::new(Errno::EINVAL))?;
+            parent.symlink(name, target)?
+        }
+        // Device nodes, FIFOs and sockets are not supported.
+        _ => return Err(Error::new(Errno::EINVAL)),
+    };
+    inode.set_owner(uid, gid)?;
+    inode.set_mode((mode & 0o7777) as u16)
+}
+
+/// Walks `path` from `root`, creating the missing directories.
+fn make_dirs(root: &Arc<dyn Inode>, path: &str) -> Result<Arc<dyn Inode>> {
+    let mut current = root.clone();
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        current = match current.lookup(name) {
+            Ok(inode) => inode,
+            Err(err) if err.code == Errno::ENOENT => current.create(name, InodeType::Directory)?,
+            Err(err) => return Err(err),
+        };
+    }
+    Ok(current)
+}
+
+fn parse_hex(digits: &[u8]) -> Result<u32> {
+    let digits = core::str::from_utf8(digits).map_err(|_| Error::new(Errno::EINVAL))?;
+    u32::from_str_radix(digits, 16).map_err(|_| Error::new(Errno::EINVAL))
+}
@@ -1,53 +1,207 @@
 #![expect(unused)]
 
+pub mod dentry;
+pub mod devfs;
+pub mod device;
 pub mod ext2;
+pub mod fat;
 mod file;
 pub mod file_table;
+mod initramfs;
+pub mod mount;
+pub mod overlayfs;
+pub mod path;
+pub mod permission;
 pub mod pipe;
+pub mod procfs;
 pub mod ramfs;
+pub mod sysfs;
 pub mod util;
 
-use crate::error::Result;
-use core::{ffi::CStr, time::Duration};
+use crate::error::{Errno, Error, Result};
+use core::{any::Any, ffi::CStr, time::Duration};
 
-use alloc::{boxed::Box, string::String, sync::Arc};
-pub use file::{FileLike, Stderr, Stdin, Stdout};
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+};
+pub use file::{FileLike, OpenFlags, SeekFrom, Stderr, Stdin, Stdout};
+use log::{error, info, warn};
 use ostd::{
     early_println,
-    mm::{VmReader, VmWriter},
+    mm::{PAGE_SIZE, VmReader, VmWriter},
 };
+pub use path::{Path, PathResolver};
 use spin::Once;
+use util::page_cache::PageCache;
 
-pub static ROOT: Once<Box<dyn FileSystem>> = Once::new();
+pub fn init() {
+    if !mount_root_device() {
+        mount_initramfs();
+    }
+    device::init();
+    mount_pseudo("dev", Arc::new(devfs::DevFS::new()), "devtmpfs");
+    mount_pseudo("proc", Arc::new(procfs::ProcFS::new()), "proc");
+    mount_pseudo("sys", Arc::new(sysfs::SysFS::new()), "sysfs");
+    ext2_test();
+}
 
-pub static EXT2_FS: Once<Arc<dyn FileSystem>> = Once::new();
+/// Mounts the volume named by the `root=` parameter as the root directory,
+/// trying the type given by `rootfstype=` or else every supported one.
+fn mount_root_device() -> bool {
+    let Some(root) = crate::cmdline::get("root") else {
+        return false;
+    };
+    let Some(source) = crate::drivers::canonical_block_device_name(root) else {
+        warn!("Root device {} not found, falling back to initramfs", root);
+        return false;
+    };
 
-pub fn init() {
-    ROOT.call_once(|| {
-        let ramfs = ramfs::RamFS::new();
-        Box::new(ramfs) as Box<dyn FileSystem>
-    });
+    let fstypes = match crate::cmdline::get("rootfstype") {
+        Some(fstype) => vec![fstype],
+        None => vec!["ext2", "vfat"],
+    };
+    for fstype in fstypes {
+        if let Ok(fs) = new_filesystem(fstype, &source, "") {
+            info!("Mount {} ({}) as root", source, fstype);
+            mount::init_root(fs, &source);
+            return true;
+        }
+    }
+    warn!(
+        "No filesystem found on {}, falling back to initramfs",
+        source
+    );
+    false
+}
 
-    for blk_device in crate::drivers::BLOCK_DEVICES.get().unwrap().lock().iter() {
-        if let Ok(fs) = ext2::Ext2Fs::new(blk_device.clone()) {
-            EXT2_FS.call_once(|| fs as Arc<dyn FileSystem>);
+/// Makes a ramfs holding the initramfs the root directory, then mounts the
+/// block devices below it.
+fn mount_initramfs() {
+    let root_fs: Arc<dyn FileSystem> = Arc::new(ramfs::RamFS::new());
+    mount::init_root(root_fs.clone(), "ramfs");
+
+    // Prefer the initramfs passed by the bootloader over the built-in one.
+    let archive = ostd::boot::boot_info()
+        .initramfs
+        .unwrap_or(crate::progs::INITRAMFS);
+    if !archive.is_empty() {
+        if let Err(err) = initramfs::unpack(archive, &root_fs.root_inode()) {
+            error!("Failed to unpack the initramfs: {:?}", err);
+        }
+    }
+
+    // Mount the first ext2 filesystem on /mnt and the first FAT one on /media.
+    let num_devices = crate::drivers::BLOCK_DEVICES.get().unwrap().lock().len();
+    for (fstype, dir) in [("ext2", "mnt"), ("vfat", "media")] {
+        for idx in 0..num_devices {
+            let source = crate::drivers::block_device_name(idx);
+            if mount::is_source_mounted(&source) {
+                continue;
+            }
+            let Ok(fs) = new_filesystem(fstype, &source, "") else {
+                continue;
+            };
+            let root = mount::root();
+            let result = root
+                .lookup(dir)
+                .or_else(|_| root.create(dir, InodeType::Directory))
+                .and_then(|target| mount::mount_device(fs, &source, &target));
+            if let Err(err) = result {
+                error!("Failed to mount {} on /{}: {:?}", source, dir, err);
+            }
             break;
         }
     }
+}
 
-    if let Some(fs) = EXT2_FS.get() {
-        fs.root_inode(); // Warm up inode cache
-        ext2_test();
+/// Mounts a filesystem not backed by a device (e.g., devfs) on `/<dir>`.
+fn mount_pseudo(dir: &str, fs: Arc<dyn FileSystem>, source: &str) {
+    let root = mount::root();
+    let target = match root.lookup(dir) {
+        Ok(target) => target,
+        // Only the in-memory filesystems can create the directory for now.
+        Err(_) if root.mount().fs().name() == "ramfs" => {
+            match root.create(dir, InodeType::Directory) {
+                Ok(target) => target,
+                Err(err) => {
+                    error!("Failed to create /{}: {:?}", dir, err);
+                    return;
+                }
+            }
+        }
+        Err(_) => {
+            warn!(
+                "No /{} on the root filesystem, {} is not mounted",
+                dir,
+                fs.name()
+            );
+            return;
+        }
+    };
+    if let Err(err) = mount::mount(fs, source, &target) {
+        error!("Failed to mount {} on /{}: {:?}", source, dir, err);
     }
 }
 
-fn ext2_test() {
-    if let Some(fs) = EXT2_FS.get() {
-        let root_inode = fs.root_inode();
-        let result = root_inode.lookup("hello_ext2.txt").unwrap();
+/// Creates a filesystem of type `fstype` on the block device named `source`,
+/// with the filesystem specific mount `options`. Virtual filesystems ignore
+/// `source`.
+pub fn new_filesystem(fstype: &str, source: &str, options: &str) -> Result<Arc<dyn FileSystem>> {
+    match fstype {
+        "ramfs" => return Ok(Arc::new(ramfs::RamFS::new())),
+        "tmpfs" => return Ok(Arc::new(ramfs::RamFS::new_tmpfs(options)?)),
+        "devtmpfs" => return Ok(Arc::new(devfs::DevFS::new())),
+        "proc" => return Ok(Arc::new(procfs::ProcFS::new())),
+        "sysfs" => return Ok(Arc::new(sysfs::SysFS::new())),
+        "overlay" => return Ok(overlayfs::OverlayFS::new(options)?),
+        _ => {}
+    }
+
+    let blk_device =
+        crate::drivers::lookup_block_device(source).ok_or(Error::new(Errno::ENOENT))?;
+    let fs: Arc<dyn FileSystem> = match fstype {
+        "ext2" => ext2::Ext2Fs::new(blk_device)?,
+        "vfat" => fat::FatFs::new(blk_device)?,
+        _ => return Err(Error::new(Errno::ENODEV)),
+    };
+    Ok(fs)
+}
+
+/// Writes back the cached data of all filesystems and flushes the block devices.
+pub fn sync() -> Result<()> {
+    for fs in mount::filesystems() {
+        fs.sync()?;
+    }
 
+    util::page_cache::flush_all()?;
+    util::buffer_cache::BUFFER_CACHE.sync_all();
+    for blk_device in crate::drivers::BLOCK_DEVICES.get().unwrap().lock().iter() {
+        blk_device.flush();
+    }
+    Ok(())
+}
+
+/// Prepares the filesystems for power off: writes back every cached page and
+/// buffer, unmounts the filesystems and flushes the block devices.
+pub fn shutdown() {
+    if let Err(err) = sync() {
+        error!("Failed to sync the filesystems: {:?}", err);
+    }
+    for fs in mount::filesystems().iter().rev() {
+        if let Err(err) = fs.unmount() {
+            error!("Failed to unmount {}: {:?}", fs.name(), err);
+        }
+    }
+}
+
+fn ext2_test() {
+    if let Ok(result) = PathResolver::from_root().lookup("/mnt/hello_ext2.txt", true) {
         let mut buf: [u8; 128] = [0; 128];
         result
+            .inode()
             .read_at(0, VmWriter::from(buf.as_mut()).to_fallible())
             .unwrap();
 
@@ -63,25 +217,195 @@ fn ext2_test() {
     }
 }
 
+/// The statistics of a filesystem, with the space counted in `block_size` units.
+#[derive(Debug, Clone, Copy)]
+pub struct FsStats {
+    /// The magic number telling the type of the filesystem.
+    pub magic: u64,
+    pub block_size: usize,
+    pub total_blocks: u64,
+    pub free_blocks: u64,
+    /// The free blocks that unprivileged users may use.
+    pub avail_blocks: u64,
+    pub total_inodes: u64,
+    pub free_inodes: u64,
+    /// The max length of a file name.
+    pub name_max: usize,
+}
+
 pub trait FileSystem: Send + Sync {
     fn name(&self) -> &str;
 
     fn root_inode(&self) -> Arc<dyn Inode>;
+
+    fn stats(&self) -> FsStats;
+
+    /// Writes back the dirty file data and metadata to the device.
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Writes back all cached state and marks the filesystem as cleanly unmounted.
+    fn unmount(&self) -> Result<()> {
+        Ok(())
+    }
 }
 
 pub trait Inode: Send + Sync {
     fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>>;
     fn create(&self, name: &str, type_: InodeType) -> Result<Arc<dyn Inode>>;
 
+    /// Whether the dentry cache may remember the names looked up in this
+    /// directory, which it may not if they come and go on their own (e.g.,
+    /// the processes in procfs).
+    fn caches_lookups(&self) -> bool {
+        true
+    }
+
+    /// Creates the device file `name` in this directory for the device of
+    /// `type_` numbered `rdev`.
+    fn mknod(&self, _name: &str, _type_: InodeType, _rdev: u64) -> Result<Arc<dyn Inode>> {
+        Err(Error::new(Errno::EPERM))
+    }
+
+    /// Creates the symbolic link `name` to `target` in this directory, which
+    /// is seen with its target set.
+    fn symlink(&self, _name: &str, _target: &str) -> Result<Arc<dyn Inode>> {
+        Err(Error::new(Errno::EPERM))
+    }
+
+    /// Adds `name` to this directory as a hard link to `old`, which is on the
+    /// same filesystem and is not a directory.
+    fn link(&self, _old: &Arc<dyn Inode>, _name: &str) -> Result<()> {
+        Err(Error::new(Errno::EPERM))
+    }
+
+    /// Removes the non-directory `name` from this directory.
+    fn unlink(&self, _name: &str) -> Result<()> {
+        Err(Error::new(Errno::EPERM))
+    }
+
+    /// Removes the directory `name` from this directory if it is empty.
+    fn rmdir(&self, _name: &str) -> Result<()> {
+        Err(Error::new(Errno::EPERM))
+    }
+
+    /// Moves `old_name` in this directory to `new_name` in `new_dir`, which is
+    /// on the same filesystem. The caller has checked that the file replaced
+    /// at `new_name`, if any, is of a compatible type.
+    fn rename(&self, _old_name: &str, _new_dir: &Arc<dyn Inode>, _new_name: &str) -> Result<()> {
+        Err(Error::new(Errno::EPERM))
+    }
+
     fn read_link(&self) -> Result<String>;
     fn write_link(&self, target: &str) -> Result<()>;
 
+    /// Lists the entries of this directory, starting at the position `offset`.
+    ///
+    /// Each entry is passed to `visitor` along with the position of the next
+    /// entry, until `visitor` returns `false` to stop. Returns the position to
+    /// resume from, i.e., that of the first entry not taken.
+    fn readdir(&self, _offset: usize, _visitor: &mut DirVisitor) -> Result<usize> {
+        Err(Error::new(Errno::ENOTDIR))
+    }
+
     fn read_at(&self, offset: usize, writer: VmWriter) -> Result<usize>;
     fn write_at(&self, offset: usize, reader: VmReader) -> Result<usize>;
-    fn metadata(&self) -> &InodeMeta;
+    fn metadata(&self) -> InodeMeta;
     fn size(&self) -> usize;
 
+    /// Truncates or extends the file to `new_size` bytes. The extended part reads as zeros.
+    fn resize(&self, _new_size: usize) -> Result<()> {
+        Err(Error::new(Errno::EPERM))
+    }
+
+    /// Reserves the storage backing `offset..offset + len`, so that writing
+    /// there does not fail for lack of space. The size is left unchanged.
+    fn allocate(&self, _offset: usize, _len: usize) -> Result<()> {
+        Err(Error::new(Errno::EOPNOTSUPP))
+    }
+
     fn typ(&self) -> InodeType;
+
+    /// The inode number, unique within the filesystem.
+    fn ino(&self) -> u64;
+
+    /// The permission bits (e.g., `0o755`) of the inode.
+    fn mode(&self) -> u16 {
+        0o755
+    }
+
+    fn set_mode(&self, _mode: u16) -> Result<()> {
+        Err(Error::new(Errno::EPERM))
+    }
+
+    /// Changes the owner and the group of the file.
+    fn set_owner(&self, _uid: u32, _gid: u32) -> Result<()> {
+        Err(Error::new(Errno::EPERM))
+    }
+
+    /// Prepares the file to be written through an open file or a shared
+    /// mapping, e.g., to copy it up from the lower layer of an overlay.
+    fn prepare_write(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// The page cache holding the file data, if the inode has one.
+    fn page_cache(&self) -> Option<Arc<PageCache>> {
+        None
+    }
+
+    /// The filesystem this inode belongs to.
+    fn fs(&self) -> Option<Arc<dyn FileSystem>> {
+        None
+    }
+
+    /// Lets a filesystem get back its own inode type from `dyn Inode`.
+    fn as_any(&self) -> &dyn Any;
+
+    /// Writes back the file data and the metadata needed to read it back.
+    fn sync_data(&self) -> Result<()> {
+        match self.page_cache() {
+            Some(page_cache) => page_cache.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes back the file data and all of its metadata.
+    fn sync_all(&self) -> Result<()> {
+        self.sync_data()
+    }
+}
+
+/// An entry of a directory, as listed by [`Inode::readdir`].
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    pub ino: u64,
+    pub type_: InodeType,
+    pub name: String,
+}
+
+/// Takes a directory entry and the position of the next one. Returns `false`
+/// if the entry is not taken and listing should stop.
+pub type DirVisitor<'a> = dyn FnMut(DirEntry, usize) -> bool + 'a;
+
+/// Passes the entries at the position `offset` or after on to `visitor`.
+/// `entries` come with their positions, in increasing order. A position must
+/// stay with its entry while the directory changes, so that a listing resumes
+/// where it stopped. Returns the position to resume from.
+pub fn visit_dir_entries(
+    entries: impl Iterator<Item = (usize, DirEntry)>,
+    offset: usize,
+    visitor: &mut DirVisitor,
+) -> usize {
+    let mut next = offset;
+    for (pos, entry) in entries.skip_while(|(pos, _)| *pos < offset) {
+        if !visitor(entry, pos + 1) {
+            break;
+        }
+        next = pos + 1;
+    }
+    next
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -89,15 +413,82 @@ pub enum InodeType {
     File,
     Directory,
     SymbolLink,
+    CharDevice,
+    BlockDevice,
+    NamedPipe,
 }
 
+impl InodeType {
+    /// The file type bits of `st_mode` (e.g., `S_IFREG`).
+    pub fn mode_bits(&self) -> u32 {
+        match self {
+            InodeType::NamedPipe => 0o010000,
+            InodeType::CharDevice => 0o020000,
+            InodeType::BlockDevice => 0o060000,
+            InodeType::Directory => 0o040000,
+            InodeType::File => 0o100000,
+            InodeType::SymbolLink => 0o120000,
+        }
+    }
+}
+
+/// The attributes of an inode, as reported by `stat`.
+#[derive(Debug, Clone)]
 pub struct InodeMeta {
+    pub ino: u64,
+    pub type_: InodeType,
+    /// The permission bits
+    pub mode: u16,
+    /// The number of hard links
+    pub nlink: u32,
+    pub uid: u32,
+    pub gid: u32,
     /// File size
-    size: usize,
+    pub size: usize,
+    /// The number of 512-byte blocks allocated
+    pub blocks: usize,
+    /// The preferred IO size
+    pub blksize: usize,
+    /// The device number, for device files
+    pub rdev: u64,
     /// Last access time
-    atime: Duration,
+    pub atime: Duration,
     /// Last modification time
-    mtime: Duration,
+    pub mtime: Duration,
     /// Last status change time
-    ctime: Duration,
+    pub ctime: Duration,
+}
+
+impl InodeMeta {
+    /// The attributes of an empty inode owned by root with a single link.
+    pub fn new(ino: u64, type_: InodeType, mode: u16) -> Self {
+        Self {
+            ino,
+            type_,
+            mode,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+            size: 0,
+            blocks: 0,
+            blksize: PAGE_SIZE,
+            rdev: 0,
+            atime: Duration::ZERO,
+            mtime: Duration::ZERO,
+            ctime: Duration::ZERO,
+        }
+    }
+}
+
+/// Encodes a device number the way Linux does.
+pub fn makedev(major: u32, minor: u32) -> u64 {
+    let (major, minor) = (major as u64, minor as u64);
+    ((major & !0xfff) << 32) | ((major & 0xfff) << 8) | ((minor & !0xff) << 12) | (minor & 0xff)
+}
+
+/// The major and the minor numbers of a device number.
+pub fn dev_major_minor(dev: u64) -> (u32, u32) {
+    let major = ((dev >> 32) & 0xffff_f000) | ((dev >> 8) & 0xfff);
+    let minor = ((dev >> 12) & 0xffff_ff00) | (dev & 0xff);
+    (major as u32, minor as u32)
 }
@@ -0,0 +1,183 @@
+//! The mount table, which attaches filesystems at directories to form a
+//! single directory tree.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+use log::info;
+use ostd::sync::RwMutex;
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::dentry::Dentry;
+use crate::fs::path::Path;
+use crate::fs::{FileSystem, InodeType, makedev};
+
+/// The mounted filesystems. The first one is the root mount.
+static MOUNTS: RwMutex<Vec<Arc<Mount>>> = RwMutex::new(Vec::new());
+/// The next minor number of the anonymous devices (major 0) that identify mounts.
+static NEXT_ANON_MINOR: AtomicU32 = AtomicU32::new(1);
+
+pub struct Mount {
+    fs: Arc<dyn FileSystem>,
+    root_dentry: Arc<Dentry>,
+    /// The mount and the directory this mount is attached on, `None` for the
+    /// root mount.
+    parent: Option<(Arc<Mount>, Arc<Dentry>)>,
+    /// The device name (e.g., `vda`) or the filesystem name for virtual ones.
+    source: String,
+    /// The absolute path of the mount point.
+    path: String,
+    /// The device number reported as `st_dev` for the files in this mount.
+    dev: u64,
+}
+
+impl Mount {
+    fn new(
+        fs: Arc<dyn FileSystem>,
+        parent: Option<(Arc<Mount>, Arc<Dentry>)>,
+        source: &str,
+        path: &str,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            root_dentry: Dentry::new_root(fs.root_inode()),
+            fs,
+            parent,
+            source: String::from(source),
+            path: String::from(path),
+            dev: makedev(0, NEXT_ANON_MINOR.fetch_add(1, Ordering::Relaxed)),
+        })
+    }
+
+    pub fn fs(&self) -> &Arc<dyn FileSystem> {
+        &self.fs
+    }
+
+    pub fn root_dentry(&self) -> &Arc<Dentry> {
+        &self.root_dentry
+    }
+
+    /// The mount and the directory this mount is attached on.
+    pub fn parent(&self) -> Option<&(Arc<Mount>, Arc<Dentry>)> {
+        self.parent.as_ref()
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn dev(&self) -> u64 {
+        self.dev
+    }
+
+    pub fn root(self: &Arc<Self>) -> Path {
+        Path::new(self.clone(), self.root_dentry.clone())
+    }
+}
+
+/// Makes `fs` the root of the directory tree. Must be called before any other mount.
+pub fn init_root(fs: Arc<dyn FileSystem>, source: &str) {
+    let mut mounts = MOUNTS.write();
+    assert!(mounts.is_empty());
+    mounts.push(Mount::new(fs, None, source, "/"));
+}
+
+/// The root directory of the directory tree.
+pub fn root() -> Path {
+    MOUNTS.read()[0].root()
+}
+
+/// Attaches the virtual filesystem `fs` on the directory `target`.
+pub fn mount(fs: Arc<dyn FileSystem>, source: &str, target: &Path) -> Result<()> {
+    attach(fs, source, target, false)
+}
+
+/// Attaches `fs` on the block device `source` on the directory `target`.
+/// Fails with `EBUSY` if a filesystem on `source` is mounted already, as the
+/// two would not share their caches.
+pub fn mount_device(fs: Arc<dyn FileSystem>, source: &str, target: &Path) -> Result<()> {
+    attach(fs, source, target, true)
+}
+
+fn attach(fs: Arc<dyn FileSystem>, source: &str, target: &Path, exclusive: bool) -> Result<()> {
+    if target.inode().typ() != InodeType::Directory {
+        return Err(Error::new(Errno::ENOTDIR));
+    }
+
+    let path = target.abs_path(&root());
+    let parent = Some((target.mount().clone(), target.dentry().clone()));
+    let mut mounts = MOUNTS.write();
+    if exclusive && mounts.iter().any(|mount| mount.source == source) {
+        return Err(Error::new(Errno::EBUSY));
+    }
+    info!("Mount {} ({}) on {}", source, fs.name(), path);
+    mounts.push(Mount::new(fs, parent, source, &path));
+    Ok(())
+}
+
+/// Detaches the filesystem whose root directory is `target`, then writes back
+/// its data and marks it cleanly unmounted.
+pub fn umount(target: &Path) -> Result<()> {
+    if !target.is_mount_root() {
+        return Err(Error::new(Errno::EINVAL));
+    }
+
+    let mount = {
+        let mut mounts = MOUNTS.write();
+        let idx = mounts
+            .iter()
+            .position(|mount| Arc::ptr_eq(mount, target.mount()))
+            .ok_or(Error::new(Errno::EINVAL))?;
+        // Every open file, working directory and root directory in the mount
+        // holds it through its path, besides the table and `target`.
+        let is_busy = target.mount().parent.is_none()
+            || Arc::strong_count(target.mount()) > 2
+            || mounts.iter().any(|mount| {
+                mount
+                    .parent
+                    .as_ref()
+                    .is_some_and(|(parent, _)| Arc::ptr_eq(parent, target.mount()))
+            });
+        if is_busy {
+            return Err(Error::new(Errno::EBUSY));
+        }
+        mounts.remove(idx)
+    };
+
+    info!("Unmount {} from {}", mount.source, mount.path);
+    mount.root_dentry.evict_all();
+    mount.fs.sync()?;
+    mount.fs.unmount()
+}
+
+/// The most recent mount attached on the directory `path`.
+pub fn mounted_on(path: &Path) -> Option<Arc<Mount>> {
+    MOUNTS
+        .read()
+        .iter()
+        .rev()
+        .find(|mount| {
+            mount.parent.as_ref().is_some_and(|(parent, mountpoint)| {
+                Arc::ptr_eq(parent, path.mount()) && Arc::ptr_eq(mountpoint, path.dentry())
+            })
+        })
+        .cloned()
+}
+
+/// Whether a filesystem on the device `source` is mounted.
+pub fn is_source_mounted(source: &str) -> bool {
+    MOUNTS.read().iter().any(|mount| mount.source == source)
+}
+
+/// All mounted filesystems, in mount order.
+pub fn filesystems() -> Vec<Arc<dyn FileSystem>> {
+    MOUNTS.read().iter().map(|mount| mount.fs.clone()).collect()
+}
+
+/// All mounts, in mount order.
+pub fn mounts() -> Vec<Arc<Mount>> {
+    MOUNTS.read().clone()
+}
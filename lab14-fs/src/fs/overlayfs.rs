@@ -0,0 +1,615 @@
+//! An overlay filesystem, which stacks a ramfs (the upper layer) on a
+//! directory of another filesystem (the lower layer) that is never written.
+//!
+//! A file of the lower layer is copied up to the upper layer the first time
+//! it is changed. A removed lower file is hidden by a whiteout, which is a
+//! character device numbered 0 in the upper layer, as on Linux. A directory
+//! replacing a removed lower one is opaque, i.e., the lower one is not merged
+//! into it.
+
+use alloc::{
+    collections::{btree_map::BTreeMap, btree_set::BTreeSet},
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec,
+    vec::Vec,
+};
+use ostd::{
+    mm::{PAGE_SIZE, VmReader, VmWriter},
+    sync::Mutex,
+};
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::ramfs::RamFS;
+use crate::fs::util::page_cache::PageCache;
+use crate::fs::{
+    DirEntry, DirVisitor, FileSystem, FsStats, Inode, InodeMeta, InodeType, visit_dir_entries,
+};
+use crate::process::current_process;
+
+const OVERLAYFS_SUPER_MAGIC: u64 = 0x794c_7630;
+/// Set in the inode numbers of the files only in the upper layer, so that
+/// they do not clash with those of the lower layer.
+const UPPER_INO_BIT: u64 = 1 << 63;
+
+pub struct OverlayFS {
+    upper: RamFS,
+    root: Arc<OverlayInode>,
+    /// The opaque directories of the upper layer by inode number, as ramfs
+    /// has no extended attributes to mark them with like Linux does.
+    opaque: Mutex<BTreeSet<u64>>,
+    /// The lower inode number of each file copied up, by the upper inode
+    /// number, which Linux records in an extended attribute too.
+    origins: Mutex<BTreeMap<u64, u64>>,
+}
+
+impl OverlayFS {
+    /// Creates an overlay with the comma-separated mount `options`, of which
+    /// `lowerdir`, the path of the lower directory, is required.
+    pub fn new(options: &str) -> Result<Arc<Self>> {
+        let mut lowerdir = None;
+        for option in options.split(',').filter(|option| !option.is_empty()) {
+            let (key, value) = option.split_once('=').ok_or(Error::new(Errno::EINVAL))?;
+            match key {
+                "lowerdir" => lowerdir = Some(value),
+                _ => return Err(Error::new(Errno::EINVAL)),
+            }
+        }
+        let lowerdir = lowerdir.ok_or(Error::new(Errno::EINVAL))?;
+        let lower = current_process().resolver().lookup(lowerdir, true)?;
+        if lower.inode().typ() != InodeType::Directory {
+            return Err(Error::new(Errno::ENOTDIR));
+        }
+        Ok(Self::with_lower(lower.inode().clone()))
+    }
+
+    /// Creates an overlay on the lower directory `lower`.
+    pub fn with_lower(lower: Arc<dyn Inode>) -> Arc<Self> {
+        let upper = RamFS::new();
+        let upper_root = upper.root_inode();
+        Arc::new_cyclic(|fs| OverlayFS {
+            root: OverlayInode::new(fs.clone(), None, lower.ino(), Some(upper_root), Some(lower)),
+            upper,
+            opaque: Mutex::new(BTreeSet::new()),
+            origins: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    fn is_opaque(&self, upper: &Arc<dyn Inode>) -> bool {
+        self.opaque.lock().contains(&upper.ino())
+    }
+}
+
+impl FileSystem for OverlayFS {
+    fn name(&self) -> &str {
+        "overlay"
+    }
+
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+
+    /// The space is that of the upper layer, where everything is written.
+    fn stats(&self) -> FsStats {
+        FsStats {
+            magic: OVERLAYFS_SUPER_MAGIC,
+            ..self.upper.stats()
+        }
+    }
+}
+
+struct OverlayInode {
+    this: Weak<OverlayInode>,
+    fs: Weak<OverlayFS>,
+    /// The directory holding this file and its name there, used to copy it
+    /// up. The root directory has none.
+    parent: Option<(Arc<OverlayInode>, String)>,
+    /// The inode number, that of the lower file if any so that it does not
+    /// change on copy-up.
+    ino: u64,
+    upper: Mutex<Option<Arc<dyn Inode>>>,
+    /// The lower file, unless hidden by the upper one, i.e., for a directory
+    /// merged into the upper one or a file not copied up.
+    lower: Option<Arc<dyn Inode>>,
+}
+
+impl OverlayInode {
+    fn new(
+        fs: Weak<OverlayFS>,
+        parent: Option<(Arc<OverlayInode>, String)>,
+        ino: u64,
+        upper: Option<Arc<dyn Inode>>,
+        lower: Option<Arc<dyn Inode>>,
+    ) -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            this: this.clone(),
+            fs,
+            parent,
+            ino,
+            upper: Mutex::new(upper),
+            lower,
+        })
+    }
+
+    fn overlay(&self) -> Arc<OverlayFS> {
+        self.fs.upgrade().unwrap()
+    }
+
+    fn upper(&self) -> Option<Arc<dyn Inode>> {
+        self.upper.lock().clone()
+    }
+
+    /// The file that is seen, which is the upper one once there is one.
+    fn real(&self) -> Arc<dyn Inode> {
+        self.upper()
+            .or_else(|| self.lower.clone())
+            .expect("an overlay inode is on at least one layer")
+    }
+
+    /// Looks up `name` in the lower directory, if this is merged with one.
+    fn lower_entry(&self, name: &str) -> Result<Option<Arc<dyn Inode>>> {
+        match &self.lower {
+            Some(lower) => lookup_optional(lower, name),
+            None => Ok(None),
+        }
+    }
+
+    fn lookup_child(&self, name: &str) -> Result<Option<Arc<OverlayInode>>> {
+        if self.typ() != InodeType::Directory {
+            return Err(Error::new(Errno::ENOTDIR));
+        }
+
+        let upper = match self.upper() {
+            Some(upper_dir) => lookup_optional(&upper_dir, name)?,
+            None => None,
+        };
+        if upper.as_ref().is_some_and(is_whiteout) {
+            return Ok(None);
+        }
+        let lower = self.lower_entry(name)?;
+        let ino = match (&upper, &lower) {
+            (_, Some(lower)) => lower.ino(),
+            (Some(upper), None) => upper.ino() | UPPER_INO_BIT,
+            (None, None) => return Ok(None),
+        };
+        // The upper file hides the lower one, unless both are directories
+        // and the upper one is not opaque.
+        let lower = match &upper {
+            Some(upper) if upper.typ() != InodeType::Directory => None,
+            Some(upper) if self.overlay().is_opaque(upper) => None,
+            _ => lower.filter(|lower| upper.is_none() || lower.typ() == InodeType::Directory),
+        };
+
+        let parent = (self.this.upgrade().unwrap(), name.to_string());
+        Ok(Some(Self::new(
+            self.fs.clone(),
+            Some(parent),
+            ino,
+            upper,
+            lower,
+        )))
+    }
+
+    /// Copies this file up to the upper layer, along with the directories
+    /// holding it, unless it is there. Returns the upper file.
+    fn copy_up(&self) -> Result<Arc<dyn Inode>> {
+        let mut upper = self.upper.lock();
+        if let Some(upper) = upper.as_ref() {
+            return Ok(upper.clone());
+        }
+
+        let lower = self.lower.as_ref().unwrap();
+        let (parent, name) = self.parent.as_ref().unwrap();
+        let upper_dir = parent.copy_up()?;
+        // Another inode of the same file may have copied it up already. Any
+        // other file there means that this one was removed or replaced.
+        if let Some(existing) = lookup_optional(&upper_dir, name)? {
+            let origin = self.overlay().origins.lock().get(&existing.ino()).copied();
+            if origin != Some(lower.ino()) {
+                return Err(Error::new(Errno::ESTALE));
+            }
+            *upper = Some(existing.clone());
+            return Ok(existing);
+        }
+
+        let meta = lower.metadata();
+        let new = match meta.type_ {
+            InodeType::File | InodeType::Directory => upper_dir.create(name, meta.type_)?,
+            InodeType::SymbolLink => upper_dir.symlink(name, &lower.read_link()?)?,
+            InodeType::CharDevice | InodeType::BlockDevice => {
+                upper_dir.mknod(name, meta.type_, meta.rdev)?
+            }
+            InodeType::NamedPipe => return Err(Error::new(Errno::EOPNOTSUPP)),
+        };
+        if let Err(err) = copy_content(lower, &new, &meta) {
+            let _ = remove(&upper_dir, name, meta.type_);
+            return Err(err);
+        }
+
+        self.overlay().origins.lock().insert(new.ino(), lower.ino());
+        *upper = Some(new.clone());
+        Ok(new)
+    }
+
+    /// The entries of this directory merged from both layers, without `.`
+    /// and `..`, with their positions in increasing order.
+    ///
+    /// An entry of the lower layer is at twice the position after it there,
+    /// even once copied up, and one only in the upper layer at twice the
+    /// position after it there plus one. So the positions stay as the layers
+    /// change.
+    fn entries(&self) -> Result<Vec<(usize, DirEntry)>> {
+        let mut lower_entries: BTreeMap<String, (usize, DirEntry)> = match &self.lower {
+            Some(lower) => read_entries(lower)?
+                .into_iter()
+                .map(|(pos, entry)| (entry.name.clone(), (pos, entry)))
+                .collect(),
+            None => BTreeMap::new(),
+        };
+
+        let mut entries = Vec::new();
+        if let Some(upper) = self.upper() {
+            for (pos, mut entry) in read_entries(&upper)? {
+                let lower = lower_entries.remove(&entry.name);
+                if entry.type_ == InodeType::CharDevice && is_whiteout(&upper.lookup(&entry.name)?)
+                {
+                    continue;
+                }
+                let pos = match lower {
+                    Some((lower_pos, lower)) => {
+                        entry.ino = lower.ino;
+                        lower_pos * 2
+                    }
+                    None => {
+                        entry.ino |= UPPER_INO_BIT;
+                        pos * 2 + 1
+                    }
+                };
+                entries.push((pos, entry));
+            }
+        }
+        entries.extend(
+            lower_entries
+                .into_values()
+                .map(|(pos, entry)| (pos * 2, entry)),
+        );
+        entries.sort_by_key(|(pos, _)| *pos);
+        Ok(entries)
+    }
+
+    /// Creates `name` in the upper directory with `create`, in place of the
+    /// whiteout there if any.
+    fn create_with<F>(&self, name: &str, create: F) -> Result<Arc<dyn Inode>>
+    where
+        F: FnOnce(&Arc<dyn Inode>) -> Result<Arc<dyn Inode>>,
+    {
+        if self.lookup_child(name)?.is_some() {
+            return Err(Error::new(Errno::EEXIST));
+        }
+
+        let upper_dir = self.copy_up()?;
+        let whiteout = remove_whiteout(&upper_dir, name)?;
+        let upper = match create(&upper_dir) {
+            Ok(upper) => upper,
+            Err(err) => {
+                if whiteout {
+                    let _ = make_whiteout(&upper_dir, name);
+                }
+                return Err(err);
+            }
+        };
+
+        // The new directory must not show the removed lower one.
+        let lower = self.lower_entry(name)?;
+        if lower.is_some() && upper.typ() == InodeType::Directory {
+            self.overlay().opaque.lock().insert(upper.ino());
+        }
+        let ino = match &lower {
+            Some(lower) => lower.ino(),
+            None => upper.ino() | UPPER_INO_BIT,
+        };
+        let parent = (self.this.upgrade().unwrap(), name.to_string());
+        Ok(Self::new(
+            self.fs.clone(),
+            Some(parent),
+            ino,
+            Some(upper),
+            None,
+        ))
+    }
+
+    /// Removes the child `name`, leaving a whiteout if there is a lower file
+    /// to hide.
+    fn remove_child(&self, name: &str, child: &OverlayInode) -> Result<()> {
+        let upper_dir = self.copy_up()?;
+        if let Some(upper) = child.upper() {
+            remove(&upper_dir, name, upper.typ())?;
+            self.overlay().opaque.lock().remove(&upper.ino());
+            self.overlay().origins.lock().remove(&upper.ino());
+        }
+        if self.lower_entry(name)?.is_some() {
+            make_whiteout(&upper_dir, name)?;
+        }
+        Ok(())
+    }
+}
+
+impl Inode for OverlayInode {
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
+        let child = self.lookup_child(name)?.ok_or(Error::new(Errno::ENOENT))?;
+        Ok(child)
+    }
+
+    fn create(&self, name: &str, type_: InodeType) -> Result<Arc<dyn Inode>> {
+        self.create_with(name, |upper_dir| upper_dir.create(name, type_))
+    }
+
+    fn mknod(&self, name: &str, type_: InodeType, rdev: u64) -> Result<Arc<dyn Inode>> {
+        self.create_with(name, |upper_dir| upper_dir.mknod(name, type_, rdev))
+    }
+
+    fn symlink(&self, name: &str, target: &str) -> Result<Arc<dyn Inode>> {
+        self.create_with(name, |upper_dir| upper_dir.symlink(name, target))
+    }
+
+    fn link(&self, old: &Arc<dyn Inode>, name: &str) -> Result<()> {
+        let old = old
+            .as_any()
+            .downcast_ref::<OverlayInode>()
+            .ok_or(Error::new(Errno::EXDEV))?;
+        let old_upper = old.copy_up()?;
+        self.create_with(name, |upper_dir| {
+            upper_dir.link(&old_upper, name)?;
+            Ok(old_upper.clone())
+        })?;
+        Ok(())
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        let child = self.lookup_child(name)?.ok_or(Error::new(Errno::ENOENT))?;
+        if child.typ() == InodeType::Directory {
+            return Err(Error::new(Errno::EISDIR));
+        }
+        self.remove_child(name, &child)
+    }
+
+    fn rmdir(&self, name: &str) -> Result<()> {
+        let child = self.lookup_child(name)?.ok_or(Error::new(Errno::ENOENT))?;
+        if child.typ() != InodeType::Directory {
+            return Err(Error::new(Errno::ENOTDIR));
+        }
+        if !child.entries()?.is_empty() {
+            return Err(Error::new(Errno::ENOTEMPTY));
+        }
+        // Only whiteouts may be left in the upper directory.
+        if let Some(upper) = child.upper() {
+            clear_whiteouts(&upper)?;
+        }
+        self.remove_child(name, &child)
+    }
+
+    fn rename(&self, old_name: &str, new_dir: &Arc<dyn Inode>, new_name: &str) -> Result<()> {
+        let new_dir = new_dir
+            .as_any()
+            .downcast_ref::<OverlayInode>()
+            .ok_or(Error::new(Errno::EXDEV))?;
+        let child = self
+            .lookup_child(old_name)?
+            .ok_or(Error::new(Errno::ENOENT))?;
+        // A merged directory would have to be copied up whole, so it is left
+        // to the caller to move entry by entry, as on Linux.
+        if child.typ() == InodeType::Directory && child.lower.is_some() {
+            return Err(Error::new(Errno::EXDEV));
+        }
+        if let Some(target) = new_dir.lookup_child(new_name)? {
+            if target.typ() == InodeType::Directory {
+                if !target.entries()?.is_empty() {
+                    return Err(Error::new(Errno::ENOTEMPTY));
+                }
+                if let Some(upper) = target.upper() {
+                    clear_whiteouts(&upper)?;
+                }
+            }
+        }
+
+        let upper = child.copy_up()?;
+        let old_upper_dir = self.copy_up()?;
+        let new_upper_dir = new_dir.copy_up()?;
+        let whiteout = remove_whiteout(&new_upper_dir, new_name)?;
+        if let Err(err) = old_upper_dir.rename(old_name, &new_upper_dir, new_name) {
+            if whiteout {
+                let _ = make_whiteout(&new_upper_dir, new_name);
+            }
+            return Err(err);
+        }
+
+        if upper.typ() == InodeType::Directory && new_dir.lower_entry(new_name)?.is_some() {
+            self.overlay().opaque.lock().insert(upper.ino());
+        }
+        if self.lower_entry(old_name)?.is_some() {
+            make_whiteout(&old_upper_dir, old_name)?;
+        }
+        Ok(())
+    }
+
+    fn readdir(&self, offset: usize, visitor: &mut DirVisitor) -> Result<usize> {
+        let entries = self.entries()?;
+        // The root directory is its own parent within the filesystem.
+        let parent_ino = self
+            .parent
+            .as_ref()
+            .map_or(self.ino, |(parent, _)| parent.ino);
+        let dots = [(".", self.ino), ("..", parent_ino)].map(|(name, ino)| DirEntry {
+            ino,
+            type_: InodeType::Directory,
+            name: name.to_string(),
+        });
+        Ok(visit_dir_entries(
+            dots.into_iter().enumerate().chain(entries),
+            offset,
+            visitor,
+        ))
+    }
+
+    fn read_link(&self) -> Result<String> {
+        self.real().read_link()
+    }
+
+    fn write_link(&self, target: &str) -> Result<()> {
+        self.copy_up()?.write_link(target)
+    }
+
+    fn read_at(&self, offset: usize, writer: VmWriter) -> Result<usize> {
+        self.real().read_at(offset, writer)
+    }
+
+    fn write_at(&self, offset: usize, reader: VmReader) -> Result<usize> {
+        self.copy_up()?.write_at(offset, reader)
+    }
+
+    fn metadata(&self) -> InodeMeta {
+        InodeMeta {
+            ino: self.ino,
+            ..self.real().metadata()
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.real().size()
+    }
+
+    fn resize(&self, new_size: usize) -> Result<()> {
+        self.copy_up()?.resize(new_size)
+    }
+
+    fn allocate(&self, offset: usize, len: usize) -> Result<()> {
+        self.copy_up()?.allocate(offset, len)
+    }
+
+    fn typ(&self) -> InodeType {
+        self.real().typ()
+    }
+
+    fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn mode(&self) -> u16 {
+        self.real().mode()
+    }
+
+    fn set_mode(&self, mode: u16) -> Result<()> {
+        self.copy_up()?.set_mode(mode)
+    }
+
+    fn set_owner(&self, uid: u32, gid: u32) -> Result<()> {
+        self.copy_up()?.set_owner(uid, gid)
+    }
+
+    /// A regular file is copied up when it is opened or mapped for writing,
+    /// as on Linux, so that a mapping writes to the pages of the upper one.
+    fn prepare_write(&self) -> Result<()> {
+        if self.typ() == InodeType::File {
+            self.copy_up()?;
+        }
+        Ok(())
+    }
+
+    /// Only the pages of the upper file are shared, so that the lower one is
+    /// never written through a mapping.
+    fn page_cache(&self) -> Option<Arc<PageCache>> {
+        self.upper()?.page_cache()
+    }
+
+    fn fs(&self) -> Option<Arc<dyn FileSystem>> {
+        self.fs.upgrade().map(|fs| fs as Arc<dyn FileSystem>)
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    fn sync_data(&self) -> Result<()> {
+        match self.upper() {
+            Some(upper) => upper.sync_data(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Looks up `name` in `dir`, which may not have it.
+fn lookup_optional(dir: &Arc<dyn Inode>, name: &str) -> Result<Option<Arc<dyn Inode>>> {
+    match dir.lookup(name) {
+        Ok(inode) => Ok(Some(inode)),
+        Err(err) if err.code == Errno::ENOENT => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+fn is_whiteout(inode: &Arc<dyn Inode>) -> bool {
+    inode.typ() == InodeType::CharDevice && inode.metadata().rdev == 0
+}
+
+fn make_whiteout(upper_dir: &Arc<dyn Inode>, name: &str) -> Result<()> {
+    upper_dir.mknod(name, InodeType::CharDevice, 0)?;
+    Ok(())
+}
+
+/// Removes the whiteout `name` from `upper_dir`, returning whether there was one.
+fn remove_whiteout(upper_dir: &Arc<dyn Inode>, name: &str) -> Result<bool> {
+    match lookup_optional(upper_dir, name)? {
+        Some(inode) if is_whiteout(&inode) => {
+            upper_dir.unlink(name)?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Removes the whiteouts from `upper_dir`, which holds nothing else, so that
+/// it can be removed.
+fn clear_whiteouts(upper_dir: &Arc<dyn Inode>) -> Result<()> {
+    for (_, entry) in read_entries(upper_dir)? {
+        upper_dir.unlink(&entry.name)?;
+    }
+    Ok(())
+}
+
+fn remove(dir: &Arc<dyn Inode>, name: &str, type_: InodeType) -> Result<()> {
+    match type_ {
+        InodeType::Directory => dir.rmdir(name),
+        _ => dir.unlink(name),
+    }
+}
+
+/// The entries of `dir`, without `.` and `..`. Each comes with the position
+/// of the entry after it, which is above any before it.
+fn read_entries(dir: &Arc<dyn Inode>) -> Result<Vec<(usize, DirEntry)>> {
+    let mut entries = Vec::new();
+    dir.readdir(0, &mut |entry, next| {
+        if entry.name != "." && entry.name != ".." {
+            entries.push((next, entry));
+        }
+        true
+    })?;
+    Ok(entries)
+}
+
+/// Copies the data, the permissions and the owner of the lower file `from`
+/// with the attributes `meta` to the upper file `to`.
+fn copy_content(from: &Arc<dyn Inode>, to: &Arc<dyn Inode>, meta: &InodeMeta) -> Result<()> {
+    if meta.type_ == InodeType::File {
+        let mut buf = vec![0u8; PAGE_SIZE];
+        let mut offset = 0;
+        while offset < meta.size {
+            let len = from.read_at(offset, VmWriter::from(buf.as_mut_slice()).to_fallible())?;
+            if len == 0 {
+                break;
+            }
+            to.write_at(offset, VmReader::from(&buf[..len]).to_fallible())?;
+            offset += len;
+        }
+    }
+    to.set_mode(meta.mode)?;
+    to.set_owner(meta.uid, meta.gid)
+}
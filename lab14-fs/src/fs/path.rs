@@ -0,0 +1,391 @@
+//! Path resolution across mount points and symbolic links.
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::dentry::{Dentry, NAME_MAX};
+use crate::fs::mount::{self, Mount};
+use crate::fs::permission::{Permission, check_permission};
+use crate::fs::{Inode, InodeType};
+use crate::process::Credentials;
+
+/// The max length of a path, including the terminating NUL.
+pub const PATH_MAX: usize = 4096;
+/// The max number of symbolic links followed while resolving one path.
+const MAX_SYMLINKS: usize = 40;
+
+/// A location in the directory tree: a dentry and the mount it is reached through.
+#[derive(Clone)]
+pub struct Path {
+    mount: Arc<Mount>,
+    dentry: Arc<Dentry>,
+}
+
+impl Path {
+    pub fn new(mount: Arc<Mount>, dentry: Arc<Dentry>) -> Self {
+        Self { mount, dentry }
+    }
+
+    pub fn mount(&self) -> &Arc<Mount> {
+        &self.mount
+    }
+
+    pub fn dentry(&self) -> &Arc<Dentry> {
+        &self.dentry
+    }
+
+    pub fn inode(&self) -> &Arc<dyn Inode> {
+        self.dentry.inode()
+    }
+
+    /// Whether this is the root directory of its mount.
+    pub fn is_mount_root(&self) -> bool {
+        Arc::ptr_eq(&self.dentry, self.mount.root_dentry())
+    }
+
+    pub fn ptr_eq(&self, other: &Path) -> bool {
+        Arc::ptr_eq(&self.mount, &other.mount) && Arc::ptr_eq(&self.dentry, &other.dentry)
+    }
+
+    /// Looks up `name` in this directory, entering the filesystem mounted on
+    /// the result if any. `.` and `..` are not handled here.
+    pub fn lookup(&self, name: &str) -> Result<Path> {
+        let dentry = self.dentry.lookup(name)?;
+        Ok(Path::new(self.mount.clone(), dentry).follow_mounts())
+    }
+
+    /// Creates `name` in this directory. Fails with `EEXIST` if it exists.
+    pub fn create(&self, name: &str, type_: InodeType) -> Result<Path> {
+        let dentry = self.dentry.create(name, type_)?;
+        Ok(Path::new(self.mount.clone(), dentry))
+    }
+
+    /// Creates the device file `name` in this directory. Fails with `EEXIST`
+    /// if it exists.
+    pub fn mknod(&self, name: &str, type_: InodeType, rdev: u64) -> Result<Path> {
+        let dentry = self.dentry.mknod(name, type_, rdev)?;
+        Ok(Path::new(self.mount.clone(), dentry))
+    }
+
+    /// Creates the symbolic link `name` to `target` in this directory. Fails
+    /// with `EEXIST` if it exists.
+    pub fn symlink(&self, name: &str, target: &str) -> Result<Path> {
+        let dentry = self.dentry.symlink(name, target)?;
+        Ok(Path::new(self.mount.clone(), dentry))
+    }
+
+    /// Adds `name` to this directory as a hard link to `old`.
+    pub fn link(&self, old: &Path, name: &str) -> Result<Path> {
+        if !Arc::ptr_eq(&self.mount, &old.mount) {
+            return Err(Error::new(Errno::EXDEV));
+        }
+        if name == "." || name == ".." {
+            return Err(Error::new(Errno::EEXIST));
+        }
+        let dentry = self.dentry.link(&old.dentry, name)?;
+        Ok(Path::new(self.mount.clone(), dentry))
+    }
+
+    /// Removes the non-directory `name` from this directory on behalf of
+    /// `credentials`.
+    pub fn unlink(&self, name: &str, credentials: &Credentials) -> Result<()> {
+        if name == "." || name == ".." {
+            return Err(Error::new(Errno::EISDIR));
+        }
+        self.dentry.unlink(name, credentials)
+    }
+
+    /// Removes the empty directory `name` from this directory on behalf of
+    /// `credentials`.
+    pub fn rmdir(&self, name: &str, credentials: &Credentials) -> Result<()> {
+        match name {
+            "." => return Err(Error::new(Errno::EINVAL)),
+            ".." => return Err(Error::new(Errno::ENOTEMPTY)),
+            _ => {}
+        }
+        if self.is_mountpoint(name)? {
+            return Err(Error::new(Errno::EBUSY));
+        }
+        self.dentry.rmdir(name, credentials)
+    }
+
+    /// Moves `old_name` in this directory to `new_name` in `new_dir` on behalf
+    /// of `credentials`. Fails with `EEXIST` if `new_name` exists and
+    /// `noreplace` is set.
+    pub fn rename(
+        &self,
+        old_name: &str,
+        new_dir: &Path,
+        new_name: &str,
+        noreplace: bool,
+        credentials: &Credentials,
+    ) -> Result<()> {
+        if !Arc::ptr_eq(&self.mount, &new_dir.mount) {
+            return Err(Error::new(Errno::EXDEV));
+        }
+        let is_dots = |name: &str| name == "." || name == "..";
+        if is_dots(old_name) || is_dots(new_name) {
+            return Err(Error::new(Errno::EBUSY));
+        }
+        if self.is_mountpoint(old_name)? || new_dir.is_mountpoint(new_name)? {
+            return Err(Error::new(Errno::EBUSY));
+        }
+        self.dentry
+            .rename(old_name, &new_dir.dentry, new_name, noreplace, credentials)
+    }
+
+    /// Whether a filesystem is mounted on `name` in this directory.
+    fn is_mountpoint(&self, name: &str) -> Result<bool> {
+        match self.dentry.lookup(name) {
+            Ok(dentry) => Ok(mount::mounted_on(&Path::new(self.mount.clone(), dentry)).is_some()),
+            Err(err) if err.code == Errno::ENOENT => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// The parent directory, crossing mount points upwards. The root
+    /// directory of the tree is its own parent.
+    pub fn parent(&self) -> Path {
+        let mut current = self.clone();
+        // Step out of the mounts whose root we are at.
+        while current.is_mount_root() {
+            let Some((mount, mountpoint)) = current.mount.parent() else {
+                return current;
+            };
+            current = Path::new(mount.clone(), mountpoint.clone());
+        }
+        let parent = current.dentry.parent().unwrap();
+        Path::new(current.mount, parent)
+    }
+
+    /// The absolute path of this location as seen from `root`.
+    pub fn abs_path(&self, root: &Path) -> String {
+        let mut names = Vec::new();
+        let mut current = self.clone();
+        while !current.ptr_eq(root) {
+            if current.is_mount_root() {
+                // The root of the tree is reached if `root` is not an ancestor.
+                let Some((mount, mountpoint)) = current.mount.parent() else {
+                    break;
+                };
+                current = Path::new(mount.clone(), mountpoint.clone());
+                continue;
+            }
+            names.push(current.dentry.name());
+            let parent = current.dentry.parent().unwrap();
+            current = Path::new(current.mount, parent);
+        }
+
+        let mut path = String::new();
+        for name in names.iter().rev() {
+            path.push('/');
+            path.push_str(name);
+        }
+        if path.is_empty() {
+            path.push('/');
+        }
+        path
+    }
+
+    /// Moves to the root of the filesystem mounted on this directory, if any.
+    fn follow_mounts(self) -> Path {
+        match mount::mounted_on(&self) {
+            Some(mount) => mount.root().follow_mounts(),
+            None => self,
+        }
+    }
+}
+
+/// Resolves paths against a root directory and a working directory.
+#[derive(Clone)]
+pub struct PathResolver {
+    root: Path,
+    cwd: Path,
+    /// The identity whose permissions are checked on the way.
+    credentials: Credentials,
+}
+
+impl PathResolver {
+    /// Creates a resolver with the credentials of the superuser.
+    pub fn new(root: Path, cwd: Path) -> Self {
+        Self {
+            root,
+            cwd,
+            credentials: Credentials::root(),
+        }
+    }
+
+    pub fn with_credentials(self, credentials: Credentials) -> Self {
+        Self {
+            credentials,
+            ..self
+        }
+    }
+
+    /// A resolver whose root and working directory are the root of the tree.
+    pub fn from_root() -> Self {
+        Self::new(mount::root(), mount::root())
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn cwd(&self) -> &Path {
+        &self.cwd
+    }
+
+    pub fn set_root(&mut self, root: Path) {
+        self.root = root;
+    }
+
+    pub fn set_cwd(&mut self, cwd: Path) {
+        self.cwd = cwd;
+    }
+
+    pub fn credentials(&self) -> &Credentials {
+        &self.credentials
+    }
+
+    /// Checks whether the credentials of this resolver grant `permission` on `inode`.
+    pub fn check_permission(&self, inode: &Arc<dyn Inode>, permission: Permission) -> Result<()> {
+        check_permission(inode, &self.credentials, permission)
+    }
+
+    /// Resolves `path`. A symbolic link at the last component is followed if
+    /// `follow` is set or the path ends with a slash.
+    pub fn lookup(&self, path: &str, follow: bool) -> Result<Path> {
+        if path.is_empty() {
+            return Err(Error::new(Errno::ENOENT));
+        }
+        let mut num_links = 0;
+        self.walk(&self.cwd, path, follow, &mut num_links)
+    }
+
+    /// Resolves all but the last component of `path`, which must be a
+    /// directory. Returns it and the last component, which may be `.` or `..`.
+    pub fn lookup_parent(&self, path: &str) -> Result<(Path, String)> {
+        if path.is_empty() {
+            return Err(Error::new(Errno::ENOENT));
+        }
+        let trimmed = path.trim_end_matches('/');
+        let (dir, name) = match trimmed.rsplit_once('/') {
+            Some((dir, name)) => (if dir.is_empty() { "/" } else { dir }, name),
+            // The path is relative and has a single component, or is "/".
+            None if trimmed.is_empty() => ("/", "."),
+            None => (".", trimmed),
+        };
+        if name.len() > NAME_MAX {
+            return Err(Error::new(Errno::ENAMETOOLONG));
+        }
+
+        let mut num_links = 0;
+        let parent = self.walk(&self.cwd, dir, true, &mut num_links)?;
+        if parent.inode().typ() != InodeType::Directory {
+            return Err(Error::new(Errno::ENOTDIR));
+        }
+        Ok((parent, String::from(name)))
+    }
+
+    /// Creates the file at `path` with the permission bits `mode`. Fails with
+    /// `EEXIST` if it exists.
+    pub fn create(&self, path: &str, type_: InodeType, mode: u16) -> Result<Path> {
+        self.create_with(path, type_, mode, |parent, name| parent.create(name, type_))
+    }
+
+    /// Creates the device file at `path` for the device of `type_` numbered
+    /// `rdev`, with the permission bits `mode`. Fails with `EEXIST` if it
+    /// exists.
+    pub fn mknod(&self, path: &str, type_: InodeType, rdev: u64, mode: u16) -> Result<Path> {
+        self.create_with(path, type_, mode, |parent, name| {
+            parent.mknod(name, type_, rdev)
+        })
+    }
+
+    /// Creates the symbolic link at `path` to `target`. Fails with `EEXIST`
+    /// if it exists.
+    pub fn symlink(&self, path: &str, target: &str) -> Result<Path> {
+        self.create_with(path, InodeType::SymbolLink, 0o777, |parent, name| {
+            parent.symlink(name, target)
+        })
+    }
+
+    /// Checks that a file of `type_` may be created at `path`, creates it in
+    /// its parent with `create` and makes the caller its owner, with the
+    /// permission bits `mode`.
+    fn create_with<F>(&self, path: &str, type_: InodeType, mode: u16, create: F) -> Result<Path>
+    where
+        F: FnOnce(&Path, &str) -> Result<Path>,
+    {
+        let (parent, name) = self.lookup_parent(path)?;
+        if name == "." || name == ".." {
+            return Err(Error::new(Errno::EEXIST));
+        }
+        // Only directories may be named with a trailing slash.
+        if path.ends_with('/') && type_ != InodeType::Directory {
+            return Err(Error::new(Errno::EISDIR));
+        }
+        self.check_permission(parent.inode(), Permission::WRITE | Permission::EXEC)?;
+
+        let created = create(&parent, &name)?;
+        // Filesystems without owners (e.g., FAT) keep their fixed one.
+        let _ = created
+            .inode()
+            .set_owner(self.credentials.euid, self.credentials.egid);
+        match created.inode().set_mode(mode & 0o7777) {
+            // Filesystems without permission bits (e.g., FAT) keep their fixed mode.
+            Err(err) if err.code == Errno::EPERM => {}
+            result => result?,
+        }
+        Ok(created)
+    }
+
+    fn walk(&self, start: &Path, path: &str, follow: bool, num_links: &mut usize) -> Result<Path> {
+        let mut current = if path.starts_with('/') {
+            self.root.clone()
+        } else {
+            start.clone()
+        };
+
+        let must_be_dir = path.ends_with('/');
+        let names: Vec<&str> = path.split('/').filter(|name| !name.is_empty()).collect();
+        for (idx, &name) in names.iter().enumerate() {
+            if current.inode().typ() != InodeType::Directory {
+                return Err(Error::new(Errno::ENOTDIR));
+            }
+            if name.len() > NAME_MAX {
+                return Err(Error::new(Errno::ENAMETOOLONG));
+            }
+            self.check_permission(current.inode(), Permission::EXEC)?;
+
+            let next = match name {
+                "." => current.clone(),
+                // Never step out of the root directory.
+                ".." if current.ptr_eq(&self.root) => current.clone(),
+                ".." => current.parent(),
+                _ => current.lookup(name)?,
+            };
+
+            let is_last = idx + 1 == names.len();
+            if next.inode().typ() == InodeType::SymbolLink && (!is_last || follow || must_be_dir) {
+                *num_links += 1;
+                if *num_links > MAX_SYMLINKS {
+                    return Err(Error::new(Errno::ELOOP));
+                }
+                let target = next.inode().read_link()?;
+                if target.is_empty() {
+                    return Err(Error::new(Errno::ENOENT));
+                }
+                // Relative targets are resolved from the directory holding the link.
+                current = self.walk(&current, &target, true, num_links)?;
+            } else {
+                current = next;
+            }
+        }
+
+        if must_be_dir && current.inode().typ() != InodeType::Directory {
+            return Err(Error::new(Errno::ENOTDIR));
+        }
+        Ok(current)
+    }
+}
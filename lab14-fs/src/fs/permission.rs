@@ -0,0 +1,73 @@
+//! Checks of the permission bits of inodes against the process credentials.
+
+use alloc::sync::Arc;
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::{Inode, InodeType};
+use crate::process::Credentials;
+
+bitflags::bitflags! {
+    /// The kinds of access, with the values `access` takes them in.
+    pub struct Permission: u16 {
+        const EXEC = 1;
+        const WRITE = 2;
+        const READ = 4;
+    }
+}
+
+/// Checks whether `credentials` grant `permission` on `inode`.
+///
+/// The owner bits apply to the owner, the group bits to the members of the
+/// group and the other bits to everyone else. A privileged process may do
+/// anything except executing a file without any execute bit.
+pub fn check_permission(
+    inode: &Arc<dyn Inode>,
+    credentials: &Credentials,
+    permission: Permission,
+) -> Result<()> {
+    let meta = inode.metadata();
+    if credentials.is_privileged() {
+        let no_exec_bits = meta.mode & 0o111 == 0 && meta.type_ != InodeType::Directory;
+        if permission.contains(Permission::EXEC) && no_exec_bits {
+            return Err(Error::new(Errno::EACCES));
+        }
+        return Ok(());
+    }
+
+    let granted = if credentials.euid == meta.uid {
+        meta.mode >> 6
+    } else if credentials.in_group(meta.gid) {
+        meta.mode >> 3
+    } else {
+        meta.mode
+    };
+    if !Permission::from_bits_truncate(granted & 0o7).contains(permission) {
+        return Err(Error::new(Errno::EACCES));
+    }
+    Ok(())
+}
+
+/// The mode bit of a directory whose entries may only be removed or renamed
+/// by their owners (e.g., `/tmp`).
+const S_ISVTX: u16 = 0o1000;
+
+/// Checks whether `credentials` may remove or rename `child` in the directory
+/// `dir`, which they may write to. In a sticky directory, only the owner of
+/// the file, the owner of the directory or a privileged process may.
+pub fn check_sticky(
+    dir: &Arc<dyn Inode>,
+    child: &Arc<dyn Inode>,
+    credentials: &Credentials,
+) -> Result<()> {
+    if credentials.is_privileged() {
+        return Ok(());
+    }
+    let dir_meta = dir.metadata();
+    if dir_meta.mode & S_ISVTX == 0
+        || credentials.euid == dir_meta.uid
+        || credentials.euid == child.metadata().uid
+    {
+        return Ok(());
+    }
+    Err(Error::new(Errno::EPERM))
+}
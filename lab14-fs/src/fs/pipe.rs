@@ -1,5 +1,5 @@
 use crate::error::{Errno, Error, Result};
-use crate::fs::FileLike;
+use crate::fs::{FileLike, InodeMeta, InodeType, OpenFlags};
 use alloc::sync::Arc;
 use ostd::mm::{FrameAllocOptions, PAGE_SIZE, Segment, VmIo, VmReader, VmWriter};
 use spin::Mutex;
@@ -43,6 +43,15 @@ impl Pipe {
 
         (reader, writer)
     }
+
+    fn metadata(self: &Arc<Self>) -> InodeMeta {
+        InodeMeta {
+            size: self.inner.lock().current_size,
+            blksize: PAGE_SIZE,
+            // Both ends of a pipe share an inode, identified by the pipe.
+            ..InodeMeta::new(Arc::as_ptr(self) as u64, InodeType::NamedPipe, 0o600)
+        }
+    }
 }
 
 impl FileLike for PipeWriter {
@@ -86,6 +95,14 @@ impl FileLike for PipeWriter {
 
         Ok(total_written)
     }
+
+    fn status_flags(&self) -> OpenFlags {
+        OpenFlags::O_WRONLY
+    }
+
+    fn metadata(&self) -> InodeMeta {
+        self.pipe.metadata()
+    }
 }
 
 impl FileLike for PipeReader {
@@ -129,6 +146,14 @@ impl FileLike for PipeReader {
         Ok(total_read)
     }
 
+    fn status_flags(&self) -> OpenFlags {
+        OpenFlags::empty()
+    }
+
+    fn metadata(&self) -> InodeMeta {
+        self.pipe.metadata()
+    }
+
     fn write(&self, _reader: VmReader) -> Result<usize> {
         Err(Error::new(Errno::EBADF))
     }
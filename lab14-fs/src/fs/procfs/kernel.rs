@@ -0,0 +1,89 @@
+//! The files of `/proc` about the whole kernel.
+
+use core::fmt::Write;
+
+use alloc::{format, string::String};
+use ostd::{mm::PAGE_SIZE, timer::Jiffies};
+
+use crate::fs::mount;
+use crate::process::processes;
+
+#[derive(Debug, Clone, Copy)]
+pub(super) enum KernelFile {
+    Meminfo,
+    Uptime,
+    Mounts,
+}
+
+impl KernelFile {
+    pub(super) const ALL: [KernelFile; 3] =
+        [KernelFile::Meminfo, KernelFile::Uptime, KernelFile::Mounts];
+
+    pub(super) fn name(&self) -> &'static str {
+        match self {
+            KernelFile::Meminfo => "meminfo",
+            KernelFile::Uptime => "uptime",
+            KernelFile::Mounts => "mounts",
+        }
+    }
+
+    pub(super) fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|file| file.name() == name)
+    }
+
+    pub(super) fn content(&self) -> String {
+        match self {
+            KernelFile::Meminfo => meminfo(),
+            KernelFile::Uptime => uptime(),
+            KernelFile::Mounts => mounts(),
+        }
+    }
+}
+
+/// The frame allocator keeps no statistics, so the free memory is estimated
+/// as what the pages mapped by the processes leave. There is no swap.
+fn meminfo() -> String {
+    let total = crate::mm::total_memory() / 1024;
+    let used: usize = processes()
+        .iter()
+        .map(|process| super::process::resident_pages(process) * PAGE_SIZE / 1024)
+        .sum();
+    let free = total.saturating_sub(used);
+
+    let mut content = String::new();
+    for (name, kb) in [
+        ("MemTotal", total),
+        ("MemFree", free),
+        ("MemAvailable", free),
+        ("SwapTotal", 0),
+        ("SwapFree", 0),
+    ] {
+        let _ = writeln!(content, "{:<16}{:>8} kB", format!("{}:", name), kb);
+    }
+    content
+}
+
+/// The time since boot. The idle time is not accounted.
+fn uptime() -> String {
+    let uptime = Jiffies::elapsed().as_duration();
+    format!(
+        "{}.{:02} 0.00\n",
+        uptime.as_secs(),
+        uptime.subsec_millis() / 10
+    )
+}
+
+/// The mounts as listed in `/etc/fstab`. The mount options are not kept.
+fn mounts() -> String {
+    let mut content = String::new();
+    for mount in mount::mounts() {
+        let _ = writeln!(
+            content,
+            "{} {} {} rw 0 0",
+            mount.source(),
+            mount.path(),
+            mount.fs().name()
+        );
+    }
+    content
+}
@@ -0,0 +1,338 @@
+//! The process filesystem mounted on `/proc`.
+//!
+//! Its files are generated from the kernel state each time they are read,
+//! and its directories list the processes alive at the time, so nothing is
+//! stored and the dentry cache is bypassed.
+
+mod kernel;
+mod process;
+
+use alloc::{
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use ostd::mm::{FallibleVmWrite, VmReader, VmWriter};
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::file_table::FileDescriptor;
+use crate::fs::{
+    DirEntry, DirVisitor, FileSystem, FsStats, Inode, InodeMeta, InodeType, Path, dentry::NAME_MAX,
+    visit_dir_entries,
+};
+use crate::process::{Pid, Process, current_process, get_process, processes};
+use kernel::KernelFile;
+use process::ProcessEntry;
+
+const PROC_SUPER_MAGIC: u64 = 0x9fa0;
+
+const ROOT_INO: u64 = 1;
+const SELF_INO: u64 = 2;
+/// The kernel files are numbered from this on.
+const KERNEL_FILE_INO: u64 = 3;
+/// The bit of the inode numbers of the entries of `/proc/<pid>/fd`.
+const FD_INO_BIT: u64 = 0x8000;
+
+pub struct ProcFS {
+    root: Arc<ProcInode>,
+}
+
+impl ProcFS {
+    pub fn new() -> Self {
+        Self {
+            root: Arc::new(ProcInode::new(Node::Root)),
+        }
+    }
+}
+
+impl FileSystem for ProcFS {
+    fn name(&self) -> &str {
+        "proc"
+    }
+
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+
+    fn stats(&self) -> FsStats {
+        FsStats {
+            magic: PROC_SUPER_MAGIC,
+            block_size: ostd::mm::PAGE_SIZE,
+            total_blocks: 0,
+            free_blocks: 0,
+            avail_blocks: 0,
+            total_inodes: 0,
+            free_inodes: 0,
+            name_max: NAME_MAX,
+        }
+    }
+}
+
+enum Node {
+    Root,
+    /// `/proc/self`, which links to the directory of the reader.
+    SelfLink,
+    Kernel(KernelFile),
+    /// `/proc/<pid>`
+    ProcessDir(Pid, Weak<Process>),
+    Process(Pid, Weak<Process>, ProcessEntry),
+    /// `/proc/<pid>/fd/<fd>`, which links to the open file.
+    Fd(Pid, Weak<Process>, FileDescriptor),
+}
+
+struct ProcInode {
+    ino: u64,
+    node: Node,
+}
+
+impl ProcInode {
+    fn new(node: Node) -> Self {
+        let ino = match &node {
+            Node::Root => ROOT_INO,
+            Node::SelfLink => SELF_INO,
+            Node::Kernel(file) => KERNEL_FILE_INO + *file as u64,
+            Node::ProcessDir(pid, _) => (*pid as u64) << 16,
+            Node::Process(pid, _, entry) => ((*pid as u64) << 16) | (*entry as u64 + 1),
+            Node::Fd(pid, _, fd) => ((*pid as u64) << 16) | FD_INO_BIT | *fd as u64,
+        };
+        Self { ino, node }
+    }
+
+    fn process_dir(process: &Arc<Process>) -> Self {
+        Self::new(Node::ProcessDir(process.pid(), Arc::downgrade(process)))
+    }
+
+    /// The process this inode is about, failing with `ESRCH` if it is gone.
+    fn process(&self) -> Result<Option<Arc<Process>>> {
+        let process = match &self.node {
+            Node::ProcessDir(_, process)
+            | Node::Process(_, process, _)
+            | Node::Fd(_, process, _) => process,
+            _ => return Ok(None),
+        };
+        process.upgrade().map(Some).ok_or(Error::new(Errno::ESRCH))
+    }
+
+    /// The entries of this directory, without `.` and `..`.
+    fn entries(&self) -> Result<Vec<DirEntry>> {
+        let entry = |ino, type_, name: &str| DirEntry {
+            ino,
+            type_,
+            name: name.to_string(),
+        };
+        let entries = match &self.node {
+            Node::Root => {
+                let mut entries: Vec<_> = KernelFile::ALL
+                    .iter()
+                    .map(|file| entry(KERNEL_FILE_INO + *file as u64, InodeType::File, file.name()))
+                    .collect();
+                entries.push(entry(SELF_INO, InodeType::SymbolLink, "self"));
+                entries.extend(processes().iter().map(|process| {
+                    let pid = process.pid();
+                    entry((pid as u64) << 16, InodeType::Directory, &pid.to_string())
+                }));
+                entries
+            }
+            Node::ProcessDir(pid, _) => ProcessEntry::ALL
+                .iter()
+                .map(|entry_| {
+                    let ino = ((*pid as u64) << 16) | (*entry_ as u64 + 1);
+                    entry(ino, entry_.typ(), entry_.name())
+                })
+                .collect(),
+            Node::Process(pid, _, ProcessEntry::Fd) => {
+                let process = self.process()?.unwrap();
+                let file_table = process.file_table();
+                file_table
+                    .iter()
+                    .map(|(fd, _)| {
+                        let ino = ((*pid as u64) << 16) | FD_INO_BIT | fd as u64;
+                        entry(ino, InodeType::SymbolLink, &fd.to_string())
+                    })
+                    .collect()
+            }
+            _ => return Err(Error::new(Errno::ENOTDIR)),
+        };
+        Ok(entries)
+    }
+
+    /// The content of this file.
+    fn content(&self) -> Result<Vec<u8>> {
+        match &self.node {
+            Node::Kernel(file) => Ok(file.content().into_bytes()),
+            Node::Process(_, _, entry) => entry.content(&self.process()?.unwrap()),
+            Node::Root | Node::ProcessDir(..) => Err(Error::new(Errno::EISDIR)),
+            Node::SelfLink | Node::Fd(..) => Err(Error::new(Errno::EINVAL)),
+        }
+    }
+}
+
+impl Inode for ProcInode {
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
+        let node = match &self.node {
+            Node::Root => {
+                if name == "self" {
+                    Node::SelfLink
+                } else if let Some(file) = KernelFile::from_name(name) {
+                    Node::Kernel(file)
+                } else {
+                    let process = name
+                        .parse()
+                        .ok()
+                        .and_then(get_process)
+                        .ok_or(Error::new(Errno::ENOENT))?;
+                    return Ok(Arc::new(Self::process_dir(&process)));
+                }
+            }
+            Node::ProcessDir(pid, process) => {
+                let entry = ProcessEntry::from_name(name).ok_or(Error::new(Errno::ENOENT))?;
+                Node::Process(*pid, process.clone(), entry)
+            }
+            Node::Process(pid, process, ProcessEntry::Fd) => {
+                let fd = name.parse().map_err(|_| Error::new(Errno::ENOENT))?;
+                if self.process()?.unwrap().file_table().get(fd).is_none() {
+                    return Err(Error::new(Errno::ENOENT));
+                }
+                Node::Fd(*pid, process.clone(), fd)
+            }
+            _ => return Err(Error::new(Errno::ENOTDIR)),
+        };
+        Ok(Arc::new(Self::new(node)))
+    }
+
+    fn create(&self, _name: &str, _type_: InodeType) -> Result<Arc<dyn Inode>> {
+        match self.typ() {
+            InodeType::Directory => Err(Error::new(Errno::EPERM)),
+            _ => Err(Error::new(Errno::ENOTDIR)),
+        }
+    }
+
+    fn caches_lookups(&self) -> bool {
+        false
+    }
+
+    fn readdir(&self, offset: usize, visitor: &mut DirVisitor) -> Result<usize> {
+        // The inode numbers of the entries are unique and above those of the
+        // dots, so they are the positions, which stay while processes and
+        // files come and go.
+        let mut entries = self.entries()?;
+        entries.sort_by_key(|entry| entry.ino);
+        // The parent of a directory is not known here, so ".." is given the
+        // number of the directory itself, as the root directory does.
+        let dots = [".", ".."].map(|name| DirEntry {
+            ino: self.ino,
+            type_: InodeType::Directory,
+            name: name.to_string(),
+        });
+        let entries = entries.into_iter().map(|entry| (entry.ino as usize, entry));
+        Ok(visit_dir_entries(
+            dots.into_iter().enumerate().chain(entries),
+            offset,
+            visitor,
+        ))
+    }
+
+    fn read_link(&self) -> Result<String> {
+        match &self.node {
+            Node::SelfLink => Ok(current_process().pid().to_string()),
+            Node::Process(_, _, ProcessEntry::Exe) => {
+                let exe = self.process()?.unwrap().exe();
+                exe.map(|path| path_name(&path))
+                    .ok_or(Error::new(Errno::ENOENT))
+            }
+            Node::Fd(_, _, fd) => {
+                let process = self.process()?.unwrap();
+                let file = process
+                    .file_table()
+                    .get(*fd)
+                    .ok_or(Error::new(Errno::ENOENT))?
+                    .file()
+                    .clone();
+                Ok(process::file_name(file.as_ref()))
+            }
+            _ => Err(Error::new(Errno::EINVAL)),
+        }
+    }
+
+    fn write_link(&self, _target: &str) -> Result<()> {
+        Err(Error::new(Errno::EPERM))
+    }
+
+    fn read_at(&self, offset: usize, mut writer: VmWriter) -> Result<usize> {
+        let content = self.content()?;
+        let Some(remain) = content.get(offset..) else {
+            return Ok(0);
+        };
+        writer
+            .write_fallible(&mut VmReader::from(remain).to_fallible())
+            .map_err(|_| Error::new(Errno::EFAULT))
+    }
+
+    fn write_at(&self, _offset: usize, _reader: VmReader) -> Result<usize> {
+        match self.typ() {
+            InodeType::Directory => Err(Error::new(Errno::EISDIR)),
+            _ => Err(Error::new(Errno::EACCES)),
+        }
+    }
+
+    fn metadata(&self) -> InodeMeta {
+        let process = self.process().ok().flatten();
+        let (uid, gid, time) = match &process {
+            Some(process) => {
+                let credentials = process.credentials();
+                (credentials.euid, credentials.egid, process.start_time())
+            }
+            None => (0, 0, core::time::Duration::ZERO),
+        };
+        InodeMeta {
+            nlink: match self.typ() {
+                InodeType::Directory => 2,
+                _ => 1,
+            },
+            uid,
+            gid,
+            atime: time,
+            mtime: time,
+            ctime: time,
+            ..InodeMeta::new(self.ino, self.typ(), self.mode())
+        }
+    }
+
+    /// The files are generated on read, so their size is not known.
+    fn size(&self) -> usize {
+        0
+    }
+
+    fn typ(&self) -> InodeType {
+        match &self.node {
+            Node::Root | Node::ProcessDir(..) => InodeType::Directory,
+            Node::SelfLink | Node::Fd(..) => InodeType::SymbolLink,
+            Node::Kernel(_) => InodeType::File,
+            Node::Process(_, _, entry) => entry.typ(),
+        }
+    }
+
+    fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn mode(&self) -> u16 {
+        match &self.node {
+            Node::Root | Node::ProcessDir(..) => 0o555,
+            Node::SelfLink => 0o777,
+            Node::Kernel(_) => 0o444,
+            Node::Process(_, _, entry) => entry.mode(),
+            Node::Fd(..) => 0o700,
+        }
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
+/// The absolute path of `path` as seen by the reader.
+fn path_name(path: &Path) -> String {
+    let root = current_process().fs().root().clone();
+    path.abs_path(&root)
+}
@@ -0,0 +1,271 @@
+//! The files of `/proc/<pid>` about a process.
+
+use core::fmt::Write;
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use ostd::mm::{PAGE_SIZE, PageFlags};
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::device::{self, DeviceType};
+use crate::fs::{FileLike, InodeType, dev_major_minor};
+use crate::process::{Process, USER_STACK_TOP};
+
+/// The clock ticks per second that the times are given in.
+const USER_HZ: u128 = 100;
+/// The max length of the name of a process, without the terminating NUL.
+const TASK_COMM_LEN: usize = 15;
+
+#[derive(Debug, Clone, Copy)]
+pub(super) enum ProcessEntry {
+    Stat,
+    Status,
+    Cmdline,
+    Maps,
+    Fd,
+    Exe,
+}
+
+impl ProcessEntry {
+    pub(super) const ALL: [ProcessEntry; 6] = [
+        ProcessEntry::Stat,
+        ProcessEntry::Status,
+        ProcessEntry::Cmdline,
+        ProcessEntry::Maps,
+        ProcessEntry::Fd,
+        ProcessEntry::Exe,
+    ];
+
+    pub(super) fn name(&self) -> &'static str {
+        match self {
+            ProcessEntry::Stat => "stat",
+            ProcessEntry::Status => "status",
+            ProcessEntry::Cmdline => "cmdline",
+            ProcessEntry::Maps => "maps",
+            ProcessEntry::Fd => "fd",
+            ProcessEntry::Exe => "exe",
+        }
+    }
+
+    pub(super) fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|entry| entry.name() == name)
+    }
+
+    pub(super) fn typ(&self) -> InodeType {
+        match self {
+            ProcessEntry::Fd => InodeType::Directory,
+            ProcessEntry::Exe => InodeType::SymbolLink,
+            _ => InodeType::File,
+        }
+    }
+
+    pub(super) fn mode(&self) -> u16 {
+        match self {
+            ProcessEntry::Fd => 0o500,
+            ProcessEntry::Exe => 0o777,
+            _ => 0o444,
+        }
+    }
+
+    pub(super) fn content(&self, process: &Arc<Process>) -> Result<Vec<u8>> {
+        let content = match self {
+            ProcessEntry::Stat => stat(process),
+            ProcessEntry::Status => status(process),
+            ProcessEntry::Cmdline => return Ok(cmdline(process)),
+            ProcessEntry::Maps => maps(process),
+            ProcessEntry::Fd => return Err(Error::new(Errno::EISDIR)),
+            ProcessEntry::Exe => return Err(Error::new(Errno::EINVAL)),
+        };
+        Ok(content.into_bytes())
+    }
+}
+
+/// The number of pages mapped in the address space of `process`.
+pub(super) fn resident_pages(process: &Process) -> usize {
+    process
+        .memory_space()
+        .with_areas(|areas| areas.iter().map(|area| area.mappings().len()).sum())
+}
+
+/// The name that `/proc/<pid>/fd/<fd>` links to for `file`.
+pub(super) fn file_name(file: &dyn FileLike) -> String {
+    if let Some(path) = file.as_path() {
+        return super::path_name(&path);
+    }
+
+    // Files not opened from the tree are named after what they are, e.g.,
+    // the console the standard IO is on.
+    let meta = file.metadata();
+    let device =
+        DeviceType::from_inode_type(meta.type_).and_then(|type_| device::get(type_, meta.rdev));
+    match (meta.type_, device) {
+        (_, Some(device)) => format!("/dev/{}", device.name()),
+        (InodeType::NamedPipe, _) => format!("pipe:[{}]", meta.ino),
+        _ => format!("anon_inode:[{}]", meta.ino),
+    }
+}
+
+/// The name of the program, which is that of its file.
+fn comm(process: &Process) -> String {
+    let name = match process.exe() {
+        Some(exe) => exe.dentry().name(),
+        None => process
+            .argv()
+            .first()
+            .map(|arg| arg.rsplit('/').next().unwrap().to_string())
+            .unwrap_or_default(),
+    };
+    name.chars().take(TASK_COMM_LEN).collect()
+}
+
+/// The state of the process, which is either running or a zombie as sleeping
+/// is not told apart.
+fn state(process: &Process) -> (char, &'static str) {
+    if process.is_zombie() {
+        ('Z', "zombie")
+    } else {
+        ('R', "running")
+    }
+}
+
+/// The size of the address space in bytes.
+fn vm_size(process: &Process) -> usize {
+    process
+        .memory_space()
+        .with_areas(|areas| areas.iter().map(|area| area.pages() * PAGE_SIZE).sum())
+}
+
+fn ppid(process: &Process) -> usize {
+    process.parent_process().map_or(0, |parent| parent.pid())
+}
+
+/// The fields of `stat`, as in proc(5). The fields not accounted (e.g.,
+/// the CPU times and the page faults) are zero. There are no process groups
+/// or sessions, so each process leads its own.
+fn stat(process: &Arc<Process>) -> String {
+    let pid = process.pid();
+    let start_time = process.start_time().as_millis() * USER_HZ / 1000;
+    format!(
+        "{} ({}) {} {} {} {} 0 -1 0 0 0 0 0 0 0 0 0 20 0 1 0 {} {} {} {} 0 0 {} \
+         0 0 0 0 0 0 0 0 0 17 0 0 0 0 0 0 0 0 {} 0 0 0 0 {}\n",
+        pid,
+        comm(process),
+        state(process).0,
+        ppid(process),
+        pid,
+        pid,
+        start_time,
+        vm_size(process),
+        resident_pages(process),
+        u64::MAX,
+        USER_STACK_TOP,
+        process.heap().base(),
+        process.exit_code().unwrap_or(0),
+    )
+}
+
+fn status(process: &Arc<Process>) -> String {
+    let pid = process.pid();
+    let (state, state_name) = state(process);
+    let credentials = process.credentials().clone();
+    let groups: Vec<String> = credentials
+        .groups
+        .iter()
+        .map(|gid| gid.to_string())
+        .collect();
+
+    let mut content = String::new();
+    let _ = writeln!(content, "Name:\t{}", comm(process));
+    let _ = writeln!(content, "State:\t{} ({})", state, state_name);
+    let _ = writeln!(content, "Tgid:\t{}", pid);
+    let _ = writeln!(content, "Pid:\t{}", pid);
+    let _ = writeln!(content, "PPid:\t{}", ppid(process));
+    let _ = writeln!(
+        content,
+        "Uid:\t{}\t{}\t{}\t{}",
+        credentials.ruid, credentials.euid, credentials.suid, credentials.euid
+    );
+    let _ = writeln!(
+        content,
+        "Gid:\t{}\t{}\t{}\t{}",
+        credentials.rgid, credentials.egid, credentials.sgid, credentials.egid
+    );
+    let _ = writeln!(content, "Groups:\t{}", groups.join(" "));
+    let _ = writeln!(content, "VmSize:\t{:>8} kB", vm_size(process) / 1024);
+    let _ = writeln!(
+        content,
+        "VmRSS:\t{:>8} kB",
+        resident_pages(process) * PAGE_SIZE / 1024
+    );
+    let _ = writeln!(content, "Threads:\t1");
+    content
+}
+
+/// The arguments, each terminated by a NUL. A zombie has none.
+fn cmdline(process: &Process) -> Vec<u8> {
+    if process.is_zombie() {
+        return Vec::new();
+    }
+    let mut content = Vec::new();
+    for arg in process.argv() {
+        content.extend_from_slice(arg.as_bytes());
+        content.push(0);
+    }
+    content
+}
+
+/// The areas of the address space, with the file mapped into each, if any.
+fn maps(process: &Arc<Process>) -> String {
+    // Naming the files may sleep, so the areas are not locked meanwhile.
+    let areas: Vec<_> = process.memory_space().with_areas(|areas| {
+        areas
+            .iter()
+            .map(|area| {
+                let start = area.base_vaddr();
+                let handler = area.page_fault_handler();
+                let file = handler.file().cloned();
+                (start, area.pages(), area.perms(), handler.is_shared(), file)
+            })
+            .collect()
+    });
+
+    let heap = process.heap().base()..process.heap().current_end();
+    let mut content = String::new();
+    for (start, pages, perms, shared, file) in areas {
+        let end = start + pages * PAGE_SIZE;
+        let perms = [
+            (perms.contains(PageFlags::R), 'r'),
+            (perms.contains(PageFlags::W), 'w'),
+            (perms.contains(PageFlags::X), 'x'),
+        ]
+        .map(|(allowed, perm)| if allowed { perm } else { '-' });
+        let shared = if shared { 's' } else { 'p' };
+
+        let (dev, ino, name) = match file {
+            Some(file) => {
+                let dev = file.as_path().map_or(0, |path| path.mount().dev());
+                (dev, file.metadata().ino, file_name(file.as_ref()))
+            }
+            None if heap.contains(&start) => (0, 0, String::from("[heap]")),
+            None if (start..end).contains(&(USER_STACK_TOP - 1)) => (0, 0, String::from("[stack]")),
+            None => (0, 0, String::new()),
+        };
+        let (major, minor) = dev_major_minor(dev);
+
+        let line = format!(
+            "{:08x}-{:08x} {}{}{}{} {:08x} {:02x}:{:02x} {} ",
+            start, end, perms[0], perms[1], perms[2], shared, 0, major, minor, ino
+        );
+        // The names are aligned, as on Linux.
+        if name.is_empty() {
+            let _ = writeln!(content, "{}", line.trim_end());
+        } else {
+            let _ = writeln!(content, "{:<73}{}", line, name);
+        }
+    }
+    content
+}
@@ -1,107 +1,497 @@
+use core::{
+    sync::atomic::{AtomicU16, AtomicU32, AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+
 use alloc::{
     collections::btree_map::BTreeMap,
     string::{String, ToString},
-    sync::Arc,
-    vec::Vec,
+    sync::{Arc, Weak},
 };
 use ostd::{
-    mm::{FallibleVmRead, FallibleVmWrite, VmReader, VmWriter},
+    mm::PAGE_SIZE,
     sync::{Mutex, RwMutex},
+    timer::Jiffies,
 };
 
 use crate::error::{Errno, Error, Result};
-use crate::fs::{Inode, InodeMeta, InodeType};
+use crate::fs::{
+    DirEntry, DirVisitor, FsStats, Inode, InodeMeta, InodeType, dentry::NAME_MAX,
+    util::page_cache::PageCache, visit_dir_entries,
+};
+
+/// The next inode number to hand out. The root directory gets 1.
+static NEXT_INO: AtomicU64 = AtomicU64::new(1);
+
+const RAMFS_MAGIC: u64 = 0x858458f6;
+const TMPFS_MAGIC: u64 = 0x01021994;
+
+/// The size that each directory entry, including "." and "..", adds to the
+/// size of a directory, as on Linux.
+const DIRENT_SIZE: usize = 20;
+
+/// The pages and inodes that a filesystem may use, and how many it uses.
+///
+/// A file is charged for every page up to its size, holes included, since
+/// reading a hole allocates a page as well, or up to the end of the space
+/// allocated past its size. Symbolic links are charged for their targets.
+pub struct Usage {
+    max_pages: usize,
+    max_inodes: usize,
+    pages: AtomicUsize,
+    inodes: AtomicUsize,
+}
+
+impl Usage {
+    fn new(max_pages: usize, max_inodes: usize) -> Arc<Self> {
+        Arc::new(Self {
+            max_pages,
+            max_inodes,
+            pages: AtomicUsize::new(0),
+            inodes: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn max_pages(&self) -> usize {
+        self.max_pages
+    }
+
+    pub fn used_pages(&self) -> usize {
+        self.pages.load(Ordering::Relaxed)
+    }
+
+    pub fn free_pages(&self) -> usize {
+        self.max_pages.saturating_sub(self.used_pages())
+    }
+
+    pub fn max_inodes(&self) -> usize {
+        self.max_inodes
+    }
+
+    pub fn used_inodes(&self) -> usize {
+        self.inodes.load(Ordering::Relaxed)
+    }
+
+    pub fn free_inodes(&self) -> usize {
+        self.max_inodes.saturating_sub(self.used_inodes())
+    }
+
+    /// Charges up to `num_pages` pages, returning how many are left to charge.
+    fn charge_pages_up_to(&self, num_pages: usize) -> usize {
+        let mut charged = 0;
+        let _ = self
+            .pages
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                charged = num_pages.min(self.max_pages.saturating_sub(used));
+                Some(used + charged)
+            });
+        charged
+    }
+
+    /// Charges the pages that growing from `old_len` to `new_len` bytes takes,
+    /// or uncharges those that shrinking frees.
+    fn recharge(&self, old_len: usize, new_len: usize) -> Result<()> {
+        let (old_pages, new_pages) = (old_len.div_ceil(PAGE_SIZE), new_len.div_ceil(PAGE_SIZE));
+        if new_pages <= old_pages {
+            self.pages
+                .fetch_sub(old_pages - new_pages, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        let charged = self.charge_pages_up_to(new_pages - old_pages);
+        if charged < new_pages - old_pages {
+            self.pages.fetch_sub(charged, Ordering::Relaxed);
+            return Err(Error::new(Errno::ENOSPC));
+        }
+        Ok(())
+    }
+
+    fn charge_inode(&self) -> Result<()> {
+        self.inodes
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                (used < self.max_inodes).then_some(used + 1)
+            })
+            .map(|_| ())
+            .map_err(|_| Error::new(Errno::ENOSPC))
+    }
+}
 
 pub struct RamInode {
+    ino: u64,
     inner: Inner,
-    metadata: InodeMeta,
+    times: Mutex<Times>,
+    /// The permission bits.
+    mode: AtomicU16,
+    uid: AtomicU32,
+    gid: AtomicU32,
+    /// The number of hard links. Directories cannot be linked, so theirs is
+    /// one until they are removed.
+    nlink: AtomicU32,
+    /// The directory holding this one, `None` for the root directory.
+    parent: RwMutex<Weak<RamInode>>,
+    /// The usage of the filesystem, which this inode is charged to.
+    usage: Arc<Usage>,
+    this: Weak<RamInode>,
+}
+
+/// The timestamps of an inode, as time since boot since there is no wall clock.
+struct Times {
+    atime: Duration,
+    mtime: Duration,
+    ctime: Duration,
 }
 
 enum Inner {
-    File(Mutex<Vec<u8>>),
-    Directory(RwMutex<BTreeMap<String, Arc<RamInode>>>),
+    File {
+        pages: Arc<PageCache>,
+        size: Mutex<usize>,
+        /// The length the file is charged for, which is past the size if
+        /// space was allocated there. Only changed with `size` locked.
+        charged: AtomicUsize,
+    },
+    Directory(RwMutex<Entries>),
+    SymbolLink(RwMutex<String>),
+    /// A device file, for the device of the type numbered `rdev`.
+    Device {
+        type_: InodeType,
+        rdev: u64,
+    },
 }
 
 impl RamInode {
-    fn new_file() -> Arc<Self> {
-        Arc::new(RamInode {
-            inner: Inner::File(Mutex::new(Vec::new())),
-            metadata: InodeMeta {
-                size: 0,
-                atime: core::time::Duration::new(0, 0),
-                mtime: core::time::Duration::new(0, 0),
-                ctime: core::time::Duration::new(0, 0),
+    fn new_file(usage: &Arc<Usage>) -> Result<Arc<Self>> {
+        Self::new(
+            Inner::File {
+                pages: PageCache::new(),
+                size: Mutex::new(0),
+                charged: AtomicUsize::new(0),
             },
-        })
+            0o644,
+            usage,
+        )
     }
 
-    fn new_directory() -> Arc<Self> {
-        Arc::new(RamInode {
-            inner: Inner::Directory(RwMutex::new(BTreeMap::new())),
-            metadata: InodeMeta {
-                size: 0,
-                atime: core::time::Duration::new(0, 0),
-                mtime: core::time::Duration::new(0, 0),
-                ctime: core::time::Duration::new(0, 0),
-            },
-        })
+    fn new_directory(usage: &Arc<Usage>) -> Result<Arc<Self>> {
+        Self::new(
+            Inner::Directory(RwMutex::new(Entries::default())),
+            0o755,
+            usage,
+        )
+    }
+
+    fn new_symlink(usage: &Arc<Usage>) -> Result<Arc<Self>> {
+        Self::new(Inner::SymbolLink(RwMutex::new(String::new())), 0o777, usage)
+    }
+
+    fn new_device(type_: InodeType, rdev: u64, usage: &Arc<Usage>) -> Result<Arc<Self>> {
+        Self::new(Inner::Device { type_, rdev }, 0o644, usage)
+    }
+
+    fn new(inner: Inner, mode: u16, usage: &Arc<Usage>) -> Result<Arc<Self>> {
+        usage.charge_inode()?;
+        let now = Jiffies::elapsed().as_duration();
+        Ok(Arc::new_cyclic(|this| RamInode {
+            ino: NEXT_INO.fetch_add(1, Ordering::Relaxed),
+            inner,
+            times: Mutex::new(Times {
+                atime: now,
+                mtime: now,
+                ctime: now,
+            }),
+            mode: AtomicU16::new(mode),
+            uid: AtomicU32::new(0),
+            gid: AtomicU32::new(0),
+            nlink: AtomicU32::new(1),
+            parent: RwMutex::new(Weak::new()),
+            usage: usage.clone(),
+            this: this.clone(),
+        }))
+    }
+
+    /// Updates the modification and the status change time to now.
+    fn touch_modified(&self) {
+        let now = Jiffies::elapsed().as_duration();
+        let mut times = self.times.lock();
+        times.mtime = now;
+        times.ctime = now;
+    }
+
+    fn touch_changed(&self) {
+        self.times.lock().ctime = Jiffies::elapsed().as_duration();
+    }
+
+    fn is_removed(&self) -> bool {
+        self.nlink.load(Ordering::Relaxed) == 0
+    }
+
+    /// Adds the inode made by `new_inode` to this directory as `name`.
+    fn add_child<F>(&self, name: &str, new_inode: F) -> Result<Arc<dyn Inode>>
+    where
+        F: FnOnce() -> Result<Arc<RamInode>>,
+    {
+        let Inner::Directory(ref entries) = self.inner else {
+            return Err(Error::new(Errno::ENOTDIR));
+        };
+        if self.is_removed() {
+            return Err(Error::new(Errno::ENOENT));
+        }
+
+        let inode = new_inode()?;
+        *inode.parent.write() = self.this.clone();
+        entries.write().insert(name.to_string(), inode.clone());
+        self.touch_modified();
+
+        Ok(inode)
+    }
+
+    /// Drops a link to this inode.
+    fn drop_link(&self) {
+        self.nlink.fetch_sub(1, Ordering::Relaxed);
+        self.touch_changed();
+    }
+}
+
+impl Drop for RamInode {
+    fn drop(&mut self) {
+        let len = match &mut self.inner {
+            Inner::File { charged, .. } => *charged.get_mut(),
+            Inner::Directory(_) | Inner::Device { .. } => 0,
+            Inner::SymbolLink(target) => target.get_mut().len(),
+        };
+        let _ = self.usage.recharge(len, 0);
+        self.usage.inodes.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// The entries of a directory. Each one is given a position when it is added,
+/// which it keeps until it is removed, so that a listing resumes where it
+/// stopped whatever is added or removed meanwhile.
+struct Entries {
+    by_name: BTreeMap<String, (usize, Arc<RamInode>)>,
+    by_pos: BTreeMap<usize, String>,
+    /// The position of the next entry added. 0 and 1 are for "." and "..".
+    next_pos: usize,
+}
+
+impl Default for Entries {
+    fn default() -> Self {
+        Self {
+            by_name: BTreeMap::new(),
+            by_pos: BTreeMap::new(),
+            next_pos: 2,
+        }
+    }
+}
+
+impl Entries {
+    fn get(&self, name: &str) -> Option<&Arc<RamInode>> {
+        self.by_name.get(name).map(|(_, inode)| inode)
+    }
+
+    fn contains_key(&self, name: &str) -> bool {
+        self.by_name.contains_key(name)
+    }
+
+    fn len(&self) -> usize {
+        self.by_name.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+
+    fn values(&self) -> impl Iterator<Item = &Arc<RamInode>> {
+        self.by_name.values().map(|(_, inode)| inode)
+    }
+
+    /// Binds `name` to `inode`. A name bound again keeps its position.
+    fn insert(&mut self, name: String, inode: Arc<RamInode>) {
+        if let Some((_, old_inode)) = self.by_name.get_mut(&name) {
+            *old_inode = inode;
+            return;
+        }
+        let pos = self.next_pos;
+        self.next_pos += 1;
+        self.by_pos.insert(pos, name.clone());
+        self.by_name.insert(name, (pos, inode));
+    }
+
+    fn remove(&mut self, name: &str) {
+        if let Some((pos, _)) = self.by_name.remove(name) {
+            self.by_pos.remove(&pos);
+        }
+    }
+
+    /// The entries with their positions, in the order of the positions.
+    fn iter(&self) -> impl Iterator<Item = (usize, &String, &Arc<RamInode>)> {
+        self.by_pos
+            .iter()
+            .map(|(&pos, name)| (pos, name, &self.by_name[name].1))
+    }
+}
+
+/// Binds `name` to `inode`, replacing what `name` is bound to, which must be
+/// an empty directory if it is a directory.
+fn replace_entry(entries: &mut Entries, name: &str, inode: &Arc<RamInode>) -> Result<()> {
+    if let Some(old_inode) = entries.get(name) {
+        if let Inner::Directory(old_entries) = &old_inode.inner {
+            if !old_entries.read().is_empty() {
+                return Err(Error::new(Errno::ENOTEMPTY));
+            }
+            // Directories have a single link.
+            old_inode.nlink.store(0, Ordering::Relaxed);
+        } else {
+            old_inode.drop_link();
+        }
     }
+    entries.insert(name.to_string(), inode.clone());
+    Ok(())
 }
 
 impl Inode for RamInode {
     fn read_at(&self, offset: usize, mut writer: ostd::mm::VmWriter) -> Result<usize> {
-        let Inner::File(data) = &self.inner else {
+        let Inner::File { pages, size, .. } = &self.inner else {
             return Err(Error::new(Errno::EISDIR));
         };
 
-        let data = data.lock();
-        if offset >= data.len() {
+        let size = size.lock();
+        if offset >= *size {
             return Ok(0);
         }
 
-        let read_len = core::cmp::min(data.len() - offset, writer.avail());
-        writer
-            .write_fallible(&mut VmReader::from(
-                &(*data.as_slice())[offset..offset + read_len],
-            ))
-            .unwrap();
-        Ok(read_len)
+        let read_len = core::cmp::min(*size - offset, writer.avail());
+        pages.read(offset, read_len, &mut writer)
     }
 
     fn write_at(&self, offset: usize, mut reader: ostd::mm::VmReader) -> Result<usize> {
-        let Inner::File(data) = &self.inner else {
+        let Inner::File {
+            pages,
+            size,
+            charged: charged_len,
+        } = &self.inner
+        else {
             return Err(Error::new(Errno::EISDIR));
         };
 
-        let mut data = data.lock();
-        if offset > data.len() {
-            // Fill the gap with zeros
-            data.resize(offset, 0);
+        // The gap before `offset`, if any, reads as zeros since new pages are zeroed.
+        let mut size = size.lock();
+        let old_pages = charged_len.load(Ordering::Relaxed).div_ceil(PAGE_SIZE);
+        let end = offset
+            .checked_add(reader.remain())
+            .ok_or(Error::new(Errno::EFBIG))?;
+        // Write as much as fits if the filesystem is about to be full.
+        let num_pages = end.div_ceil(PAGE_SIZE).saturating_sub(old_pages);
+        let charged = self.usage.charge_pages_up_to(num_pages);
+        let end = core::cmp::min(end, (old_pages + charged) * PAGE_SIZE);
+        if end <= offset && reader.has_remain() {
+            self.usage.pages.fetch_sub(charged, Ordering::Relaxed);
+            return Err(Error::new(Errno::ENOSPC));
         }
 
-        // Expand the data vector if necessary
-        if offset + reader.remain() > data.len() {
-            data.resize(offset + reader.remain(), 0);
-        }
-
-        let write_len = core::cmp::min(data.len() - offset, reader.remain());
-        reader
-            .read_fallible(&mut VmWriter::from(
-                &mut (*data.as_mut_slice())[offset..offset + write_len],
-            ))
-            .unwrap();
+        let result = pages.write(offset, end.saturating_sub(offset), &mut reader);
+        let new_size = match result {
+            Ok(write_len) => core::cmp::max(*size, offset + write_len),
+            Err(_) => *size,
+        };
+        // Give back the pages that a short write did not fill.
+        let new_len = new_size.max(charged_len.load(Ordering::Relaxed));
+        let _ = self
+            .usage
+            .recharge((old_pages + charged) * PAGE_SIZE, new_len);
+        charged_len.store(new_len, Ordering::Relaxed);
+        *size = new_size;
+        let write_len = result?;
+        self.touch_modified();
         Ok(write_len)
     }
 
+    fn resize(&self, new_size: usize) -> Result<()> {
+        let Inner::File {
+            pages,
+            size,
+            charged,
+        } = &self.inner
+        else {
+            return Err(Error::new(Errno::EISDIR));
+        };
+
+        let mut size = size.lock();
+        // Truncating frees the space allocated past the new size as well.
+        let old_len = charged.load(Ordering::Relaxed);
+        let new_len = if new_size < *size {
+            new_size
+        } else {
+            new_size.max(old_len)
+        };
+        self.usage.recharge(old_len, new_len)?;
+        charged.store(new_len, Ordering::Relaxed);
+        pages.resize(new_size);
+        *size = new_size;
+        self.touch_modified();
+        Ok(())
+    }
+
+    fn allocate(&self, offset: usize, len: usize) -> Result<()> {
+        let Inner::File { size, charged, .. } = &self.inner else {
+            return Err(Error::new(Errno::EISDIR));
+        };
+
+        // Pages are allocated on write, but charged now so that the writes
+        // cannot run out of space.
+        let _size = size.lock();
+        let end = offset.checked_add(len).ok_or(Error::new(Errno::EFBIG))?;
+        let old_len = charged.load(Ordering::Relaxed);
+        if end > old_len {
+            self.usage.recharge(old_len, end)?;
+            charged.store(end, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
     fn size(&self) -> usize {
         match &self.inner {
-            Inner::File(data) => data.lock().len(),
-            Inner::Directory(_) => 12,
+            Inner::File { size, .. } => *size.lock(),
+            Inner::Directory(entries) => (entries.read().len() + 2) * DIRENT_SIZE,
+            Inner::SymbolLink(target) => target.read().len(),
+            Inner::Device { .. } => 0,
         }
     }
 
-    fn metadata(&self) -> &InodeMeta {
-        &self.metadata
+    fn metadata(&self) -> InodeMeta {
+        let nlink = match &self.inner {
+            Inner::Directory(_) if self.is_removed() => 0,
+            // A directory is linked from its parent, from its own "." and from
+            // the ".." of each subdirectory.
+            Inner::Directory(entries) => {
+                let num_subdirs = entries
+                    .read()
+                    .values()
+                    .filter(|inode| inode.typ() == InodeType::Directory)
+                    .count();
+                2 + num_subdirs as u32
+            }
+            _ => self.nlink.load(Ordering::Relaxed),
+        };
+        let size = self.size();
+        let times = self.times.lock();
+        InodeMeta {
+            nlink,
+            uid: self.uid.load(Ordering::Relaxed),
+            gid: self.gid.load(Ordering::Relaxed),
+            size,
+            blocks: match &self.inner {
+                Inner::File { .. } => size.div_ceil(PAGE_SIZE) * (PAGE_SIZE / 512),
+                _ => 0,
+            },
+            rdev: match &self.inner {
+                Inner::Device { rdev, .. } => *rdev,
+                _ => 0,
+            },
+            atime: times.atime,
+            mtime: times.mtime,
+            ctime: times.ctime,
+            ..InodeMeta::new(self.ino, self.typ(), self.mode())
+        }
     }
 
     fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
@@ -116,55 +506,357 @@ impl Inode for RamInode {
     }
 
     fn create(&self, name: &str, type_: InodeType) -> Result<Arc<dyn Inode>> {
+        self.add_child(name, || match type_ {
+            InodeType::File => RamInode::new_file(&self.usage),
+            InodeType::Directory => RamInode::new_directory(&self.usage),
+            _ => Err(Error::new(Errno::EINVAL)),
+        })
+    }
+
+    fn symlink(&self, name: &str, target: &str) -> Result<Arc<dyn Inode>> {
+        self.add_child(name, || {
+            let inode = RamInode::new_symlink(&self.usage)?;
+            inode.write_link(target)?;
+            Ok(inode)
+        })
+    }
+
+    fn mknod(&self, name: &str, type_: InodeType, rdev: u64) -> Result<Arc<dyn Inode>> {
+        self.add_child(name, || match type_ {
+            InodeType::File => RamInode::new_file(&self.usage),
+            InodeType::CharDevice | InodeType::BlockDevice => {
+                RamInode::new_device(type_, rdev, &self.usage)
+            }
+            _ => Err(Error::new(Errno::EINVAL)),
+        })
+    }
+
+    fn link(&self, old: &Arc<dyn Inode>, name: &str) -> Result<()> {
         let Inner::Directory(ref entries) = self.inner else {
             return Err(Error::new(Errno::ENOTDIR));
         };
+        if self.is_removed() {
+            return Err(Error::new(Errno::ENOENT));
+        }
+        let old = old
+            .as_any()
+            .downcast_ref::<RamInode>()
+            .ok_or(Error::new(Errno::EXDEV))?;
+        if old.typ() == InodeType::Directory {
+            return Err(Error::new(Errno::EPERM));
+        }
 
-        let inode = match type_ {
-            InodeType::File => RamInode::new_file(),
-            InodeType::Directory => RamInode::new_directory(),
-            InodeType::SymbolLink => todo!(),
+        let mut entries = entries.write();
+        if entries.contains_key(name) {
+            return Err(Error::new(Errno::EEXIST));
+        }
+        entries.insert(name.to_string(), old.this.upgrade().unwrap());
+        old.nlink.fetch_add(1, Ordering::Relaxed);
+        old.touch_changed();
+        self.touch_modified();
+        Ok(())
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        let Inner::Directory(ref entries) = self.inner else {
+            return Err(Error::new(Errno::ENOTDIR));
         };
 
-        entries.write().insert(name.to_string(), inode.clone());
+        let mut entries = entries.write();
+        let inode = entries.get(name).ok_or(Error::new(Errno::ENOENT))?;
+        if inode.typ() == InodeType::Directory {
+            return Err(Error::new(Errno::EISDIR));
+        }
+        inode.drop_link();
+        entries.remove(name);
+        self.touch_modified();
+        Ok(())
+    }
 
-        Ok(inode)
+    fn rmdir(&self, name: &str) -> Result<()> {
+        let Inner::Directory(ref entries) = self.inner else {
+            return Err(Error::new(Errno::ENOTDIR));
+        };
+
+        let mut entries = entries.write();
+        let inode = entries.get(name).ok_or(Error::new(Errno::ENOENT))?;
+        let Inner::Directory(child_entries) = &inode.inner else {
+            return Err(Error::new(Errno::ENOTDIR));
+        };
+        // Hold the lock so that nothing is created in the directory meanwhile.
+        let child_entries = child_entries.write();
+        if !child_entries.is_empty() {
+            return Err(Error::new(Errno::ENOTEMPTY));
+        }
+        inode.nlink.store(0, Ordering::Relaxed);
+        inode.touch_changed();
+        drop(child_entries);
+        entries.remove(name);
+        self.touch_modified();
+        Ok(())
+    }
+
+    fn rename(&self, old_name: &str, new_dir: &Arc<dyn Inode>, new_name: &str) -> Result<()> {
+        let new_dir = new_dir
+            .as_any()
+            .downcast_ref::<RamInode>()
+            .ok_or(Error::new(Errno::EXDEV))?;
+        let (Inner::Directory(old_entries), Inner::Directory(new_entries)) =
+            (&self.inner, &new_dir.inner)
+        else {
+            return Err(Error::new(Errno::ENOTDIR));
+        };
+        if new_dir.is_removed() {
+            return Err(Error::new(Errno::ENOENT));
+        }
+
+        let inode = if self.ino == new_dir.ino {
+            let mut entries = old_entries.write();
+            let inode = entries
+                .get(old_name)
+                .cloned()
+                .ok_or(Error::new(Errno::ENOENT))?;
+            // Renaming a file to another link of it does nothing.
+            if entries
+                .get(new_name)
+                .is_some_and(|new_inode| Arc::ptr_eq(new_inode, &inode))
+            {
+                return Ok(());
+            }
+            replace_entry(&mut entries, new_name, &inode)?;
+            entries.remove(old_name);
+            inode
+        } else {
+            // Lock in inode order so that a rename the other way cannot deadlock.
+            let (mut old_entries, mut new_entries) = if self.ino < new_dir.ino {
+                let old_entries = old_entries.write();
+                (old_entries, new_entries.write())
+            } else {
+                let new_entries = new_entries.write();
+                (old_entries.write(), new_entries)
+            };
+            let inode = old_entries
+                .get(old_name)
+                .cloned()
+                .ok_or(Error::new(Errno::ENOENT))?;
+            if new_entries
+                .get(new_name)
+                .is_some_and(|new_inode| Arc::ptr_eq(new_inode, &inode))
+            {
+                return Ok(());
+            }
+            replace_entry(&mut new_entries, new_name, &inode)?;
+            old_entries.remove(old_name);
+            inode
+        };
+
+        *inode.parent.write() = new_dir.this.clone();
+        inode.touch_changed();
+        self.touch_modified();
+        new_dir.touch_modified();
+        Ok(())
+    }
+
+    fn readdir(&self, offset: usize, visitor: &mut DirVisitor) -> Result<usize> {
+        let Inner::Directory(ref entries) = self.inner else {
+            return Err(Error::new(Errno::ENOTDIR));
+        };
+
+        let parent_ino = self
+            .parent
+            .read()
+            .upgrade()
+            .map_or(self.ino, |parent| parent.ino);
+        let dots = [(".", self.ino), ("..", parent_ino)].map(|(name, ino)| DirEntry {
+            ino,
+            type_: InodeType::Directory,
+            name: name.to_string(),
+        });
+        let entries = entries.read();
+        let entries = entries.iter().map(|(pos, name, inode)| {
+            let entry = DirEntry {
+                ino: inode.ino,
+                type_: inode.typ(),
+                name: name.clone(),
+            };
+            (pos, entry)
+        });
+        Ok(visit_dir_entries(
+            dots.into_iter().enumerate().chain(entries),
+            offset,
+            visitor,
+        ))
     }
 
     fn read_link(&self) -> Result<String> {
-        todo!()
+        let Inner::SymbolLink(target) = &self.inner else {
+            return Err(Error::new(Errno::EINVAL));
+        };
+        Ok(target.read().clone())
+    }
+
+    fn write_link(&self, new_target: &str) -> Result<()> {
+        let Inner::SymbolLink(target) = &self.inner else {
+            return Err(Error::new(Errno::EINVAL));
+        };
+        let mut target = target.write();
+        self.usage.recharge(target.len(), new_target.len())?;
+        *target = new_target.to_string();
+        self.touch_modified();
+        Ok(())
     }
 
-    fn write_link(&self, _target: &str) -> Result<()> {
-        todo!()
+    fn ino(&self) -> u64 {
+        self.ino
     }
 
     fn typ(&self) -> InodeType {
         match &self.inner {
             Inner::Directory(_) => InodeType::Directory,
-            Inner::File(_) => InodeType::File,
+            Inner::File { .. } => InodeType::File,
+            Inner::SymbolLink(_) => InodeType::SymbolLink,
+            Inner::Device { type_, .. } => *type_,
         }
     }
+
+    fn mode(&self) -> u16 {
+        self.mode.load(Ordering::Relaxed)
+    }
+
+    fn set_mode(&self, mode: u16) -> Result<()> {
+        self.mode.store(mode & 0o7777, Ordering::Relaxed);
+        self.touch_changed();
+        Ok(())
+    }
+
+    fn set_owner(&self, uid: u32, gid: u32) -> Result<()> {
+        self.uid.store(uid, Ordering::Relaxed);
+        self.gid.store(gid, Ordering::Relaxed);
+        self.touch_changed();
+        Ok(())
+    }
+
+    fn page_cache(&self) -> Option<Arc<PageCache>> {
+        match &self.inner {
+            Inner::File { pages, .. } => Some(pages.clone()),
+            _ => None,
+        }
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
 }
 
+/// An in-memory filesystem.
+///
+/// A ramfs may take all the memory, while a tmpfs is limited in the pages and
+/// inodes it uses, by default to half of the memory each.
 pub struct RamFS {
+    name: &'static str,
     root: Arc<RamInode>,
+    usage: Arc<Usage>,
 }
 
 impl RamFS {
     pub fn new() -> Self {
-        RamFS {
-            root: RamInode::new_directory(),
+        Self::with_limits("ramfs", usize::MAX, usize::MAX, 0o755).unwrap()
+    }
+
+    /// Creates a tmpfs with the comma-separated mount `options`: `size`, the
+    /// max bytes, `nr_blocks`, the max pages, `nr_inodes`, the max inodes, and
+    /// `mode`, the permission bits of the root directory. Sizes may end with
+    /// `k`, `m` or `g`, and zero means no limit.
+    pub fn new_tmpfs(options: &str) -> Result<Self> {
+        let total_pages = crate::mm::total_memory() / PAGE_SIZE;
+        let mut max_pages = total_pages / 2;
+        let mut max_inodes = total_pages / 2;
+        let mut mode = 0o1777;
+        for option in options.split(',').filter(|option| !option.is_empty()) {
+            let (key, value) = option.split_once('=').ok_or(Error::new(Errno::EINVAL))?;
+            match key {
+                "size" => max_pages = parse_size(value)?.div_ceil(PAGE_SIZE),
+                "nr_blocks" => max_pages = parse_size(value)?,
+                "nr_inodes" => max_inodes = parse_size(value)?,
+                "mode" => {
+                    mode = u16::from_str_radix(value, 8).map_err(|_| Error::new(Errno::EINVAL))?
+                        & 0o7777
+                }
+                _ => return Err(Error::new(Errno::EINVAL)),
+            }
         }
+
+        let no_limit = |max: usize| if max == 0 { usize::MAX } else { max };
+        Self::with_limits("tmpfs", no_limit(max_pages), no_limit(max_inodes), mode)
+    }
+
+    fn with_limits(
+        name: &'static str,
+        max_pages: usize,
+        max_inodes: usize,
+        mode: u16,
+    ) -> Result<Self> {
+        let usage = Usage::new(max_pages, max_inodes);
+        let root = RamInode::new_directory(&usage)?;
+        root.mode.store(mode, Ordering::Relaxed);
+        Ok(RamFS { name, root, usage })
+    }
+
+    pub fn usage(&self) -> &Usage {
+        &self.usage
     }
 }
 
+/// Parses a number that may end with `k`, `m` or `g`.
+fn parse_size(size: &str) -> Result<usize> {
+    let (digits, shift) = match size.as_bytes().last() {
+        Some(b'k' | b'K') => (&size[..size.len() - 1], 10),
+        Some(b'm' | b'M') => (&size[..size.len() - 1], 20),
+        Some(b'g' | b'G') => (&size[..size.len() - 1], 30),
+        _ => (size, 0),
+    };
+    let size = digits
+        .parse::<usize>()
+        .map_err(|_| Error::new(Errno::EINVAL))?;
+    size.checked_mul(1 << shift)
+        .ok_or(Error::new(Errno::EINVAL))
+}
+
 impl crate::fs::FileSystem for RamFS {
     fn name(&self) -> &str {
-        "ramfs"
+        self.name
     }
 
     fn root_inode(&self) -> Arc<dyn Inode> {
         self.root.clone()
     }
+
+    fn stats(&self) -> FsStats {
+        // Like Linux, a filesystem without limits reports neither size nor
+        // inodes.
+        let (total_blocks, free_blocks) = if self.usage.max_pages == usize::MAX {
+            (0, 0)
+        } else {
+            (self.usage.max_pages, self.usage.free_pages())
+        };
+        let (total_inodes, free_inodes) = if self.usage.max_inodes == usize::MAX {
+            (0, 0)
+        } else {
+            (self.usage.max_inodes, self.usage.free_inodes())
+        };
+        FsStats {
+            magic: if self.name == "tmpfs" {
+                TMPFS_MAGIC
+            } else {
+                RAMFS_MAGIC
+            },
+            block_size: PAGE_SIZE,
+            total_blocks: total_blocks as u64,
+            free_blocks: free_blocks as u64,
+            avail_blocks: free_blocks as u64,
+            total_inodes: total_inodes as u64,
+            free_inodes: free_inodes as u64,
+            name_max: NAME_MAX,
+        }
+    }
 }
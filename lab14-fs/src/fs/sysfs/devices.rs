@@ -0,0 +1,179 @@
+//! The tree of `/sys`, with the virtio devices under `bus/virtio/devices`
+//! and the block devices under `block`, as on Linux.
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+
+use super::{SysInode, TreeBuilder};
+use crate::drivers::{
+    self,
+    blk::{BlockDevice, BlockStats},
+    partition::PARTITIONS,
+    virtio::{VIRTIO_DEVICES, VirtioDevice},
+};
+
+pub(super) fn build(builder: &mut TreeBuilder) -> Arc<SysInode> {
+    let virtio_devices = VIRTIO_DEVICES.get().unwrap().lock().clone();
+    let virtio = virtio_devices
+        .iter()
+        .enumerate()
+        .map(|(idx, device)| {
+            let dir = virtio_device(builder, device.clone());
+            (format!("virtio{}", idx), dir)
+        })
+        .collect::<Vec<_>>();
+    let virtio = builder.dir(virtio);
+    let virtio = builder.dir([("devices".to_string(), virtio)]);
+    let bus = builder.dir([("virtio".to_string(), virtio)]);
+
+    let disks = drivers::BLOCK_DEVICES.get().unwrap().lock().clone();
+    let block = disks
+        .iter()
+        .enumerate()
+        .map(|(idx, disk)| {
+            let name = drivers::block_device_name(idx);
+            // A disk driven through virtio links to its device.
+            let virtio_idx = virtio_devices
+                .iter()
+                .position(|device| core::ptr::addr_eq(Arc::as_ptr(device), Arc::as_ptr(disk)));
+            let dir = disk_dir(builder, &name, disk, virtio_idx);
+            (name, dir)
+        })
+        .collect::<Vec<_>>();
+    let block = builder.dir(block);
+
+    builder.dir([("bus".to_string(), bus), ("block".to_string(), block)])
+}
+
+/// The attributes of a virtio device, in the formats of Linux.
+fn virtio_device(builder: &mut TreeBuilder, device: Arc<dyn VirtioDevice>) -> Arc<SysInode> {
+    let (id, vendor, features, status) = (device.clone(), device.clone(), device.clone(), device);
+    let entries = [
+        (
+            "device",
+            builder.attr(move || format!("{:#06x}\n", id.transport().device_id())),
+        ),
+        (
+            "vendor",
+            builder.attr(move || format!("{:#010x}\n", vendor.transport().vendor_id())),
+        ),
+        (
+            "features",
+            // One character per feature bit, from bit 0 on.
+            builder.attr(move || {
+                let features = features.features();
+                let mut bits: String = (0..64)
+                    .map(|bit| if features & (1 << bit) != 0 { '1' } else { '0' })
+                    .collect();
+                bits.push('\n');
+                bits
+            }),
+        ),
+        (
+            "status",
+            builder.attr(move || format!("{:#010x}\n", status.transport().device_status().bits())),
+        ),
+    ];
+    builder.dir(entries.map(|(name, inode)| (name.to_string(), inode)))
+}
+
+/// The directory of a disk, which holds those of its partitions.
+fn disk_dir(
+    builder: &mut TreeBuilder,
+    name: &str,
+    disk: &Arc<dyn BlockDevice>,
+    virtio_idx: Option<usize>,
+) -> Arc<SysInode> {
+    let mut entries = block_attrs(builder, disk.clone());
+
+    // The serial number never changes, so it is not asked on each read.
+    let serial = disk.serial().unwrap_or_default();
+    entries.push((
+        "serial".to_string(),
+        builder.attr(move || format!("{}\n", serial)),
+    ));
+    if let Some(idx) = virtio_idx {
+        let target = format!("../../bus/virtio/devices/virtio{}", idx);
+        entries.push(("device".to_string(), builder.link(target)));
+    }
+
+    let partitions = PARTITIONS.get().unwrap().lock().clone();
+    for partition in partitions {
+        if !Arc::ptr_eq(partition.disk(), disk) {
+            continue;
+        }
+        let mut partition_entries = block_attrs(builder, partition.clone());
+        let number = partition.name()[name.len()..].to_string();
+        let start = partition.start();
+        partition_entries.push((
+            "partition".to_string(),
+            builder.attr(move || format!("{}\n", number)),
+        ));
+        partition_entries.push((
+            "start".to_string(),
+            builder.attr(move || format!("{}\n", start)),
+        ));
+        let dir = builder.dir(partition_entries);
+        entries.push((partition.name().to_string(), dir));
+    }
+
+    builder.dir(entries)
+}
+
+/// The attributes shared by disks and partitions.
+fn block_attrs(
+    builder: &mut TreeBuilder,
+    device: Arc<dyn BlockDevice>,
+) -> Vec<(String, Arc<SysInode>)> {
+    let (size, stat, inflight) = (device.clone(), device.clone(), device);
+    Vec::from([
+        (
+            "size".to_string(),
+            builder.attr(move || format!("{}\n", size.num_sectors())),
+        ),
+        (
+            "stat".to_string(),
+            builder.attr(move || format_stat(&stat.stats().unwrap_or_default())),
+        ),
+        (
+            "inflight".to_string(),
+            builder.attr(move || {
+                let [reads, writes] = inflight.stats().unwrap_or_default().in_flight;
+                format!("{:>8} {:>8}\n", reads, writes)
+            }),
+        ),
+    ])
+}
+
+/// The 17 fields of `stat`, as in the block/stat.rst of Linux. Requests are
+/// never merged, and discards and flushes are not accounted, so those fields
+/// are zero. The times are in milliseconds.
+fn format_stat(stats: &BlockStats) -> String {
+    let fields = [
+        stats.read_ios,
+        0,
+        stats.read_sectors,
+        stats.read_time.as_millis() as u64,
+        stats.write_ios,
+        0,
+        stats.write_sectors,
+        stats.write_time.as_millis() as u64,
+        stats.in_flight.iter().sum(),
+        stats.io_time.as_millis() as u64,
+        stats.queue_time.as_millis() as u64,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    ];
+    let fields: Vec<String> = fields.iter().map(|field| format!("{:>8}", field)).collect();
+    let mut line = fields.join(" ");
+    line.push('\n');
+    line
+}
@@ -0,0 +1,230 @@
+//! The device filesystem mounted on `/sys`.
+//!
+//! Its tree is built from the devices found by the drivers when it is
+//! mounted, while its attribute files ask the driver layer each time they
+//! are read, so they show the current state of the devices.
+
+mod devices;
+
+use core::time::Duration;
+
+use alloc::{
+    boxed::Box,
+    collections::btree_map::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+};
+use ostd::{
+    mm::{FallibleVmWrite, VmReader, VmWriter},
+    timer::Jiffies,
+};
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::{
+    DirEntry, DirVisitor, FileSystem, FsStats, Inode, InodeMeta, InodeType, dentry::NAME_MAX,
+    visit_dir_entries,
+};
+
+const SYSFS_MAGIC: u64 = 0x6265_6572;
+
+pub struct SysFS {
+    root: Arc<SysInode>,
+}
+
+impl SysFS {
+    pub fn new() -> Self {
+        let mut builder = TreeBuilder {
+            next_ino: 1,
+            time: Jiffies::elapsed().as_duration(),
+        };
+        Self {
+            root: devices::build(&mut builder),
+        }
+    }
+}
+
+impl FileSystem for SysFS {
+    fn name(&self) -> &str {
+        "sysfs"
+    }
+
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+
+    fn stats(&self) -> FsStats {
+        FsStats {
+            magic: SYSFS_MAGIC,
+            block_size: ostd::mm::PAGE_SIZE,
+            total_blocks: 0,
+            free_blocks: 0,
+            avail_blocks: 0,
+            total_inodes: 0,
+            free_inodes: 0,
+            name_max: NAME_MAX,
+        }
+    }
+}
+
+/// Generates the content of an attribute file.
+type Show = Box<dyn Fn() -> String + Send + Sync>;
+
+enum Inner {
+    Directory(BTreeMap<String, Arc<SysInode>>),
+    Attribute(Show),
+    /// A link to another node, given relative to the link.
+    Link(String),
+}
+
+struct SysInode {
+    ino: u64,
+    inner: Inner,
+    /// The time the filesystem was created, which the nodes never change.
+    time: Duration,
+}
+
+/// Numbers the nodes as the tree is built.
+struct TreeBuilder {
+    next_ino: u64,
+    time: Duration,
+}
+
+impl TreeBuilder {
+    fn node(&mut self, inner: Inner) -> Arc<SysInode> {
+        let ino = self.next_ino;
+        self.next_ino += 1;
+        Arc::new(SysInode {
+            ino,
+            inner,
+            time: self.time,
+        })
+    }
+
+    fn dir(&mut self, entries: impl IntoIterator<Item = (String, Arc<SysInode>)>) -> Arc<SysInode> {
+        self.node(Inner::Directory(entries.into_iter().collect()))
+    }
+
+    fn attr(&mut self, show: impl Fn() -> String + Send + Sync + 'static) -> Arc<SysInode> {
+        self.node(Inner::Attribute(Box::new(show)))
+    }
+
+    fn link(&mut self, target: String) -> Arc<SysInode> {
+        self.node(Inner::Link(target))
+    }
+}
+
+impl Inode for SysInode {
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
+        let Inner::Directory(entries) = &self.inner else {
+            return Err(Error::new(Errno::ENOTDIR));
+        };
+        let inode = entries.get(name).ok_or(Error::new(Errno::ENOENT))?;
+        Ok(inode.clone())
+    }
+
+    fn create(&self, _name: &str, _type_: InodeType) -> Result<Arc<dyn Inode>> {
+        match self.inner {
+            Inner::Directory(_) => Err(Error::new(Errno::EPERM)),
+            _ => Err(Error::new(Errno::ENOTDIR)),
+        }
+    }
+
+    fn readdir(&self, offset: usize, visitor: &mut DirVisitor) -> Result<usize> {
+        let Inner::Directory(entries) = &self.inner else {
+            return Err(Error::new(Errno::ENOTDIR));
+        };
+
+        // The parent of a directory is not known here, so ".." is given the
+        // number of the directory itself, as the root directory does.
+        let dots = [".", ".."].map(|name| DirEntry {
+            ino: self.ino,
+            type_: InodeType::Directory,
+            name: name.to_string(),
+        });
+        let entries = entries.iter().map(|(name, inode)| DirEntry {
+            ino: inode.ino,
+            type_: inode.typ(),
+            name: name.clone(),
+        });
+        Ok(visit_dir_entries(
+            // The tree never changes, so the index of an entry is stable.
+            dots.into_iter().chain(entries).enumerate(),
+            offset,
+            visitor,
+        ))
+    }
+
+    fn read_link(&self) -> Result<String> {
+        match &self.inner {
+            Inner::Link(target) => Ok(target.clone()),
+            _ => Err(Error::new(Errno::EINVAL)),
+        }
+    }
+
+    fn write_link(&self, _target: &str) -> Result<()> {
+        Err(Error::new(Errno::EPERM))
+    }
+
+    fn read_at(&self, offset: usize, mut writer: VmWriter) -> Result<usize> {
+        let content = match &self.inner {
+            Inner::Attribute(show) => show(),
+            Inner::Directory(_) => return Err(Error::new(Errno::EISDIR)),
+            Inner::Link(_) => return Err(Error::new(Errno::EINVAL)),
+        };
+        let Some(remain) = content.as_bytes().get(offset..) else {
+            return Ok(0);
+        };
+        writer
+            .write_fallible(&mut VmReader::from(remain).to_fallible())
+            .map_err(|_| Error::new(Errno::EFAULT))
+    }
+
+    fn write_at(&self, _offset: usize, _reader: VmReader) -> Result<usize> {
+        match self.inner {
+            Inner::Directory(_) => Err(Error::new(Errno::EISDIR)),
+            _ => Err(Error::new(Errno::EACCES)),
+        }
+    }
+
+    fn metadata(&self) -> InodeMeta {
+        InodeMeta {
+            nlink: match self.inner {
+                Inner::Directory(_) => 2,
+                _ => 1,
+            },
+            atime: self.time,
+            mtime: self.time,
+            ctime: self.time,
+            ..InodeMeta::new(self.ino, self.typ(), self.mode())
+        }
+    }
+
+    /// The attributes are generated on read, so their size is not known.
+    fn size(&self) -> usize {
+        0
+    }
+
+    fn typ(&self) -> InodeType {
+        match self.inner {
+            Inner::Directory(_) => InodeType::Directory,
+            Inner::Attribute(_) => InodeType::File,
+            Inner::Link(_) => InodeType::SymbolLink,
+        }
+    }
+
+    fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn mode(&self) -> u16 {
+        match self.inner {
+            Inner::Directory(_) => 0o555,
+            Inner::Attribute(_) => 0o444,
+            Inner::Link(_) => 0o777,
+        }
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
@@ -0,0 +1,387 @@
+//! The block buffer cache.
+//!
+//! Every filesystem reads and writes its on-disk structures through the global
+//! [`BUFFER_CACHE`] instead of issuing sector requests directly. The file data
+//! bypasses it and goes through the page caches. Buffers are `BUFFER_SIZE`
+//! bytes long and keyed by the owning block device and the buffer number on
+//! that device, so a partition is cached apart from its disk. The least
+//! recently used buffers are evicted once the cache is full, and dirty buffers
+//! are written back on eviction or sync.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use alloc::{
+    collections::btree_map::BTreeMap,
+    sync::{Arc, Weak},
+    vec,
+    vec::Vec,
+};
+use log::debug;
+use ostd::{
+    Pod,
+    mm::{FallibleVmRead, FallibleVmWrite, VmIo, VmReader, VmWriter},
+    sync::Mutex,
+};
+
+use crate::drivers::blk::{BioRequest, BlockDevice, SECTOR_SIZE};
+
+/// The size of one cached buffer in bytes.
+pub const BUFFER_SIZE: usize = 4096;
+const SECTORS_PER_BUFFER: usize = BUFFER_SIZE / SECTOR_SIZE;
+/// The maximum number of buffers kept in the cache (4 MiB).
+const MAX_BUFFERS: usize = 1024;
+
+pub static BUFFER_CACHE: BufferCache = BufferCache::new();
+
+/// Identifies a block device by its id.
+type DeviceKey = usize;
+
+fn device_key(device: &Arc<dyn BlockDevice>) -> DeviceKey {
+    device.id()
+}
+
+/// One cached block of a block device.
+pub struct Buffer {
+    device: Weak<dyn BlockDevice>,
+    /// The buffer number, in units of `BUFFER_SIZE`.
+    bid: usize,
+    /// The content, empty until read from the device by [`Self::load`].
+    data: Mutex<Vec<u8>>,
+    dirty: AtomicBool,
+}
+
+impl Buffer {
+    fn new(device: &Arc<dyn BlockDevice>, bid: usize) -> Self {
+        Self {
+            device: Arc::downgrade(device),
+            bid,
+            data: Mutex::new(Vec::new()),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    /// Reads the buffer from the device unless done already. Others getting
+    /// the buffer meanwhile wait on the lock of the data.
+    fn load(&self, device: &Arc<dyn BlockDevice>) {
+        let mut data = self.data.lock();
+        if !data.is_empty() {
+            return;
+        }
+
+        let mut request = BioRequest::new(self.bid * SECTORS_PER_BUFFER, SECTORS_PER_BUFFER);
+        device.read_block(&mut request);
+
+        data.resize(BUFFER_SIZE, 0);
+        for (sector, chunk) in request.data.iter().zip(data.chunks_mut(SECTOR_SIZE)) {
+            sector.read_bytes(0, chunk).unwrap();
+        }
+    }
+
+    pub fn read_bytes(&self, offset: usize, buf: &mut [u8]) {
+        let data = self.data.lock();
+        buf.copy_from_slice(&data[offset..offset + buf.len()]);
+    }
+
+    pub fn write_bytes(&self, offset: usize, buf: &[u8]) {
+        let mut data = self.data.lock();
+        data[offset..offset + buf.len()].copy_from_slice(buf);
+        self.dirty.store(true, Ordering::Release);
+    }
+
+    pub fn read_val<T: Pod>(&self, offset: usize) -> T {
+        let mut val = T::new_zeroed();
+        self.read_bytes(offset, val.as_bytes_mut());
+        val
+    }
+
+    pub fn write_val<T: Pod>(&self, offset: usize, val: &T) {
+        self.write_bytes(offset, val.as_bytes());
+    }
+
+    /// Copies `len` bytes starting at `offset` to `writer`.
+    pub fn read_to_writer(&self, offset: usize, len: usize, writer: &mut VmWriter) -> usize {
+        let data = self.data.lock();
+        writer
+            .write_fallible(&mut VmReader::from(&data[offset..offset + len]))
+            .unwrap_or(0)
+    }
+
+    /// Copies at most `len` bytes from `reader` to the buffer at `offset`.
+    pub fn write_from_reader(&self, offset: usize, len: usize, reader: &mut VmReader) -> usize {
+        let mut data = self.data.lock();
+        let written = reader
+            .read_fallible(&mut VmWriter::from(&mut data[offset..offset + len]))
+            .unwrap_or(0);
+        self.dirty.store(true, Ordering::Release);
+        written
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.load(Ordering::Acquire)
+    }
+
+    /// Writes the buffer back to the device if it is dirty.
+    pub fn write_back(&self) {
+        if !self.dirty.swap(false, Ordering::AcqRel) {
+            return;
+        }
+        let Some(device) = self.device.upgrade() else {
+            return;
+        };
+
+        let mut request = BioRequest::new(self.bid * SECTORS_PER_BUFFER, SECTORS_PER_BUFFER);
+        let data = self.data.lock();
+        for (sector, chunk) in request.data.iter().zip(data.chunks(SECTOR_SIZE)) {
+            sector.write_bytes(0, chunk).unwrap();
+        }
+        drop(data);
+        device.write_block(&mut request);
+        debug!("Buffer cache: wrote back buffer {}", self.bid);
+    }
+}
+
+pub struct BufferCache {
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    /// The cached buffers and the time they were last used.
+    buffers: BTreeMap<(DeviceKey, usize), (Arc<Buffer>, u64)>,
+    /// The use time of each buffer, ordered from the least recently used.
+    lru: BTreeMap<u64, (DeviceKey, usize)>,
+    clock: u64,
+}
+
+impl BufferCache {
+    pub const fn new() -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                buffers: BTreeMap::new(),
+                lru: BTreeMap::new(),
+                clock: 0,
+            }),
+        }
+    }
+
+    /// Gets the buffer `bid` of `device`, reading it from the device on a miss.
+    ///
+    /// The device is accessed without the cache locked: a missing buffer is
+    /// added empty and loaded afterwards, and the buffers to evict are written
+    /// back before being removed.
+    pub fn get(&self, device: &Arc<dyn BlockDevice>, bid: usize) -> Arc<Buffer> {
+        let key = (device_key(device), bid);
+        let mut inner = self.inner.lock();
+        inner.clock += 1;
+        let now = inner.clock;
+
+        let (buffer, victims) = if let Some((buffer, last_use)) = inner.buffers.get_mut(&key) {
+            let buffer = buffer.clone();
+            let old_use = core::mem::replace(last_use, now);
+            inner.lru.remove(&old_use);
+            inner.lru.insert(now, key);
+            (buffer, Vec::new())
+        } else {
+            let buffer = Arc::new(Buffer::new(device, bid));
+            inner.buffers.insert(key, (buffer.clone(), now));
+            inner.lru.insert(now, key);
+            let victims = if inner.buffers.len() > MAX_BUFFERS {
+                inner.evict()
+            } else {
+                Vec::new()
+            };
+            (buffer, victims)
+        };
+        drop(inner);
+
+        if !victims.is_empty() {
+            for (_, victim) in victims.iter() {
+                victim.write_back();
+            }
+            self.inner.lock().remove_written_back(victims);
+        }
+        buffer.load(device);
+        buffer
+    }
+
+    /// Reads `buf.len()` bytes starting at byte `offset` of `device`.
+    pub fn read_bytes(&self, device: &Arc<dyn BlockDevice>, offset: usize, buf: &mut [u8]) {
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = offset + done;
+            let len = (BUFFER_SIZE - pos % BUFFER_SIZE).min(buf.len() - done);
+            self.get(device, pos / BUFFER_SIZE)
+                .read_bytes(pos % BUFFER_SIZE, &mut buf[done..done + len]);
+            done += len;
+        }
+    }
+
+    /// Writes `buf` to byte `offset` of `device`. The data reaches the device on write-back.
+    pub fn write_bytes(&self, device: &Arc<dyn BlockDevice>, offset: usize, buf: &[u8]) {
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = offset + done;
+            let len = (BUFFER_SIZE - pos % BUFFER_SIZE).min(buf.len() - done);
+            self.get(device, pos / BUFFER_SIZE)
+                .write_bytes(pos % BUFFER_SIZE, &buf[done..done + len]);
+            done += len;
+        }
+    }
+
+    pub fn read_val<T: Pod>(&self, device: &Arc<dyn BlockDevice>, offset: usize) -> T {
+        let mut val = T::new_zeroed();
+        self.read_bytes(device, offset, val.as_bytes_mut());
+        val
+    }
+
+    pub fn write_val<T: Pod>(&self, device: &Arc<dyn BlockDevice>, offset: usize, val: &T) {
+        self.write_bytes(device, offset, val.as_bytes());
+    }
+
+    /// Copies `len` bytes starting at byte `offset` of `device` to `writer`.
+    pub fn read_to_writer(
+        &self,
+        device: &Arc<dyn BlockDevice>,
+        offset: usize,
+        len: usize,
+        writer: &mut VmWriter,
+    ) -> usize {
+        let mut done = 0;
+        while done < len {
+            let pos = offset + done;
+            let chunk = (BUFFER_SIZE - pos % BUFFER_SIZE).min(len - done);
+            let copied = self.get(device, pos / BUFFER_SIZE).read_to_writer(
+                pos % BUFFER_SIZE,
+                chunk,
+                writer,
+            );
+            done += copied;
+            if copied < chunk {
+                break;
+            }
+        }
+        done
+    }
+
+    /// Copies at most `len` bytes from `reader` to byte `offset` of `device`.
+    /// The data reaches the device on write-back.
+    pub fn write_from_reader(
+        &self,
+        device: &Arc<dyn BlockDevice>,
+        offset: usize,
+        len: usize,
+        reader: &mut VmReader,
+    ) -> usize {
+        let mut done = 0;
+        while done < len {
+            let pos = offset + done;
+            let chunk = (BUFFER_SIZE - pos % BUFFER_SIZE).min(len - done);
+            let copied = self.get(device, pos / BUFFER_SIZE).write_from_reader(
+                pos % BUFFER_SIZE,
+                chunk,
+                reader,
+            );
+            done += copied;
+            if copied < chunk {
+                break;
+            }
+        }
+        done
+    }
+
+    /// Drops the buffer `bid` of `device` without writing it back, e.g., after
+    /// the filesystem frees the block and may reuse it for uncached data.
+    pub fn discard(&self, device: &Arc<dyn BlockDevice>, bid: usize) {
+        let mut inner = self.inner.lock();
+        if let Some((buffer, last_use)) = inner.buffers.remove(&(device_key(device), bid)) {
+            inner.lru.remove(&last_use);
+            buffer.dirty.store(false, Ordering::Release);
+        }
+    }
+
+    /// Writes back all dirty buffers of `device`.
+    pub fn sync(&self, device: &Arc<dyn BlockDevice>) {
+        let key = device_key(device);
+        let dirty: Vec<Arc<Buffer>> = self
+            .inner
+            .lock()
+            .buffers
+            .range((key, 0)..=(key, usize::MAX))
+            .filter(|(_, (buffer, _))| buffer.is_dirty())
+            .map(|(_, (buffer, _))| buffer.clone())
+            .collect();
+        for buffer in dirty {
+            buffer.write_back();
+        }
+    }
+
+    /// Writes back all dirty buffers of all devices.
+    pub fn sync_all(&self) {
+        let dirty: Vec<Arc<Buffer>> = self
+            .inner
+            .lock()
+            .buffers
+            .values()
+            .filter(|(buffer, _)| buffer.is_dirty())
+            .map(|(buffer, _)| buffer.clone())
+            .collect();
+        for buffer in dirty {
+            buffer.write_back();
+        }
+    }
+}
+
+impl Default for BufferCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Inner {
+    /// Evicts the least recently used buffers that are not in use until the
+    /// cache is back to its capacity.
+    ///
+    /// The clean ones are removed at once. The dirty ones stay cached, so that
+    /// no one reads the stale data from the device, and are returned to be
+    /// written back and then passed to [`Self::remove_written_back`].
+    fn evict(&mut self) -> Vec<(u64, Arc<Buffer>)> {
+        let mut victims = Vec::new();
+        let excess = self.buffers.len() - MAX_BUFFERS;
+        for (&stamp, key) in self.lru.iter() {
+            if victims.len() >= excess {
+                break;
+            }
+            let (buffer, _) = &self.buffers[key];
+            // Someone still holds the buffer, skip it.
+            if Arc::strong_count(buffer) > 1 {
+                continue;
+            }
+            victims.push((stamp, buffer.clone()));
+        }
+
+        victims.retain(|(stamp, buffer)| {
+            if buffer.is_dirty() {
+                return true;
+            }
+            let key = self.lru.remove(stamp).unwrap();
+            self.buffers.remove(&key);
+            false
+        });
+        victims
+    }
+
+    /// Removes the `victims` of [`Self::evict`] once written back, unless they
+    /// have been used or written to meanwhile.
+    fn remove_written_back(&mut self, victims: Vec<(u64, Arc<Buffer>)>) {
+        for (stamp, buffer) in victims {
+            let Some(&key) = self.lru.get(&stamp) else {
+                continue;
+            };
+            // The cache and `victims` are the only holders.
+            if Arc::strong_count(&buffer) > 2 || buffer.is_dirty() {
+                continue;
+            }
+            self.lru.remove(&stamp);
+            self.buffers.remove(&key);
+        }
+    }
+}
@@ -1,125 +1,121 @@
+pub mod buffer_cache;
+pub mod page_cache;
+pub mod readahead;
 pub mod sector_ptr;
 
-use alloc::{string::String, sync::Arc};
+use core::sync::atomic::{AtomicU32, Ordering};
 
-use crate::error::Result;
-use crate::fs::{FileLike, Inode, InodeType};
+use alloc::sync::Arc;
+use ostd::mm::{VmReader, VmWriter};
+use ostd::sync::Mutex;
 
+use crate::error::{Errno, Error, Result};
+use crate::fs::{DirVisitor, FileLike, Inode, InodeMeta, OpenFlags, Path, SeekFrom};
+use readahead::ReadaheadState;
+
+/// A file opened from the directory tree, i.e., an open file description.
+///
+/// The offset and the flags are shared by the file descriptors duplicated
+/// from the same `open`, including those inherited by `fork`.
 pub struct FileInode {
+    path: Path,
     inode: Arc<dyn Inode>,
+    flags: AtomicU32,
+    offset: Mutex<usize>,
+    readahead: ReadaheadState,
 }
 
 impl FileInode {
-    pub fn new(inode: Arc<dyn Inode>) -> Self {
-        Self { inode }
+    pub fn new(path: Path, flags: OpenFlags) -> Self {
+        Self {
+            inode: path.inode().clone(),
+            path,
+            flags: AtomicU32::new((flags - OpenFlags::CREATION_FLAGS).bits()),
+            offset: Mutex::new(0),
+            readahead: ReadaheadState::new(),
+        }
     }
-}
 
-impl FileLike for FileInode {
-    fn read(&self, writer: ostd::mm::VmWriter) -> crate::error::Result<usize> {
-        self.inode.read_at(0, writer)
+    pub fn flags(&self) -> OpenFlags {
+        OpenFlags::from_bits_truncate(self.flags.load(Ordering::Relaxed))
     }
+}
 
-    fn write(&self, reader: ostd::mm::VmReader) -> crate::error::Result<usize> {
-        self.inode.write_at(0, reader)
+impl FileLike for FileInode {
+    fn read(&self, writer: VmWriter) -> Result<usize> {
+        let mut offset = self.offset.lock();
+        let read_len = self.read_at(*offset, writer)?;
+        *offset += read_len;
+        Ok(read_len)
     }
 
-    fn as_inode(&self) -> Option<Arc<dyn Inode>> {
-        Some(self.inode.clone())
+    fn write(&self, reader: VmReader) -> Result<usize> {
+        let mut offset = self.offset.lock();
+        if self.flags().contains(OpenFlags::O_APPEND) {
+            *offset = self.inode.size();
+        }
+        let write_len = self.write_at(*offset, reader)?;
+        *offset += write_len;
+        Ok(write_len)
     }
-}
-
-#[derive(Debug)]
-pub struct PathString {
-    inner: String,
-    location: usize,
-}
 
-impl PathString {
-    pub fn new(mut s: String) -> Self {
-        while s.starts_with('/') {
-            s.remove(0);
+    fn read_at(&self, offset: usize, writer: VmWriter) -> Result<usize> {
+        if !self.flags().is_readable() {
+            return Err(Error::new(Errno::EBADF));
         }
-
-        while s.ends_with('/') {
-            s.pop();
-        }
-
-        Self {
-            inner: s,
-            location: 0,
+        let read_len = self.inode.read_at(offset, writer)?;
+        if let Some(page_cache) = self.inode.page_cache() {
+            self.readahead
+                .on_read(&page_cache, offset, read_len, self.inode.size());
         }
+        Ok(read_len)
     }
 
-    pub fn lookup<'a>(&mut self, start: &'a dyn Inode) -> Result<Arc<dyn Inode>> {
-        let mut current = if self.peek().is_none() {
-            return Ok(start.lookup("")?);
-        } else {
-            start.lookup(&self.next().unwrap())?
-        };
-
-        while let Some(name) = self.next() {
-            let next_inode = current.lookup(&name)?;
-            current = next_inode;
+    fn write_at(&self, offset: usize, reader: VmReader) -> Result<usize> {
+        if !self.flags().is_writable() {
+            return Err(Error::new(Errno::EBADF));
         }
-        Ok(current)
+        self.inode.write_at(offset, reader)
     }
 
-    pub fn create<'a>(&mut self, start: &'a dyn Inode, type_: InodeType) -> Result<Arc<dyn Inode>> {
-        let mut last_name = String::new();
-        let mut current = start;
-        let mut next_inode;
-        while let Some(name) = self.next() {
-            if self.peek().is_none() {
-                last_name = name;
-                break;
-            }
-            next_inode = current.lookup(&name)?;
-            current = next_inode.as_ref();
-        }
+    fn metadata(&self) -> InodeMeta {
+        self.inode.metadata()
+    }
 
-        let new_inode = current.create(&last_name, type_)?;
-        Ok(new_inode)
+    fn readdir(&self, visitor: &mut DirVisitor) -> Result<()> {
+        let mut offset = self.offset.lock();
+        *offset = self.inode.readdir(*offset, visitor)?;
+        Ok(())
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.inner.is_empty()
+    fn seek(&self, pos: SeekFrom) -> Result<usize> {
+        let mut offset = self.offset.lock();
+        let new_offset = match pos {
+            SeekFrom::Start(start) => Some(start),
+            SeekFrom::Current(delta) => offset.checked_add_signed(delta),
+            SeekFrom::End(delta) => self.inode.size().checked_add_signed(delta),
+        };
+        // The offset may go past the end of the file, but not before its start.
+        *offset = new_offset
+            .filter(|&new_offset| new_offset <= isize::MAX as usize)
+            .ok_or(Error::new(Errno::EINVAL))?;
+        Ok(*offset)
     }
 
-    pub fn peek(&self) -> Option<String> {
-        if self.location >= self.inner.len() {
-            return None;
-        }
-        let bytes = self.inner.as_bytes();
-        let mut next_location = self.location;
-        while next_location < bytes.len() && bytes[next_location] != b'/' {
-            next_location += 1;
-        }
-        let part = String::from(&self.inner[self.location..next_location]);
-        Some(part)
+    fn as_inode(&self) -> Option<Arc<dyn Inode>> {
+        Some(self.inode.clone())
     }
-}
 
-impl Iterator for PathString {
-    type Item = String;
+    fn as_path(&self) -> Option<Path> {
+        Some(self.path.clone())
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.location >= self.inner.len() {
-            return None;
-        }
-        let bytes = self.inner.as_bytes();
-        let mut next_location = self.location;
-        while next_location < bytes.len() && bytes[next_location] != b'/' {
-            next_location += 1;
-        }
-        let part = String::from(&self.inner[self.location..next_location]);
-        self.location = next_location + 1; // Skip the '/'
-        Some(part)
+    fn status_flags(&self) -> OpenFlags {
+        self.flags()
     }
-}
 
-impl From<String> for PathString {
-    fn from(s: String) -> Self {
-        PathString::new(s)
+    fn set_status_flags(&self, flags: OpenFlags) {
+        let flags = self.flags().with_status_flags(flags);
+        self.flags.store(flags.bits(), Ordering::Relaxed);
     }
 }
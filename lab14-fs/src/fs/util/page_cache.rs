@@ -0,0 +1,303 @@
+//! The per-inode page cache.
+//!
+//! File data is cached in page frames that are shared by `read_at`, `write_at`
+//! and file-backed memory mappings, so every user of a file sees the same bytes.
+//! Caches with a backing store write dirty pages back through their
+//! [`PageCacheBackend`], and their clean, unmapped pages are dropped when memory
+//! runs low. Caches without a backend (e.g., ramfs) are the only copy of the data
+//! and are never evicted.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use alloc::{
+    collections::btree_map::BTreeMap,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use log::debug;
+use ostd::{
+    mm::{
+        FallibleVmRead, FallibleVmWrite, Frame, FrameAllocOptions, PAGE_SIZE, VmReader, VmWriter,
+        io_util::HasVmReaderWriter,
+    },
+    sync::Mutex,
+};
+
+use crate::error::{Errno, Error, Result};
+
+/// The number of cached pages above which backed caches start to be shrunk.
+const MAX_CACHED_PAGES: usize = 16384;
+
+/// All page caches that can be shrunk.
+static PAGE_CACHES: Mutex<Vec<Weak<PageCache>>> = Mutex::new(Vec::new());
+/// The number of pages held by the caches in `PAGE_CACHES`.
+static CACHED_PAGES: AtomicUsize = AtomicUsize::new(0);
+
+/// The backing store of a page cache.
+pub trait PageCacheBackend: Send + Sync {
+    /// Fills `frame` with the content of page `idx`.
+    fn read_page(&self, idx: usize, frame: &Frame<()>) -> Result<()>;
+    /// Writes `frame` back as the content of page `idx`.
+    fn write_page(&self, idx: usize, frame: &Frame<()>) -> Result<()>;
+}
+
+pub struct PageCache {
+    pages: Mutex<BTreeMap<usize, CachedPage>>,
+    backend: Option<Weak<dyn PageCacheBackend>>,
+}
+
+struct CachedPage {
+    frame: Frame<()>,
+    dirty: bool,
+    /// Bumped each time the page is dirtied, so that a write-back can tell
+    /// whether the page changed meanwhile.
+    version: u64,
+    /// Whether the page is mapped shared and writable, in which case it may
+    /// be changed through the mapping at any time.
+    mapped_writable: bool,
+}
+
+impl PageCache {
+    /// Creates a page cache that is the only copy of the data.
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            pages: Mutex::new(BTreeMap::new()),
+            backend: None,
+        })
+    }
+
+    /// Creates a page cache over `backend`.
+    pub fn with_backend(backend: Weak<dyn PageCacheBackend>) -> Arc<Self> {
+        let cache = Arc::new(Self {
+            pages: Mutex::new(BTreeMap::new()),
+            backend: Some(backend),
+        });
+        PAGE_CACHES.lock().push(Arc::downgrade(&cache));
+        cache
+    }
+
+    /// Whether the pages are loaded from and written back to a backend.
+    pub fn has_backend(&self) -> bool {
+        self.backend.is_some()
+    }
+
+    /// Gets the frame caching page `idx`, loading it from the backend on a miss.
+    pub fn get_page(&self, idx: usize) -> Result<Frame<()>> {
+        if let Some(page) = self.pages.lock().get(&idx) {
+            return Ok(page.frame.clone());
+        }
+
+        // Do not hold the lock here: allocating may shrink this cache.
+        let frame = alloc_page()?;
+        let backend = self.backend();
+        if let Some(backend) = &backend {
+            backend.read_page(idx, &frame)?;
+        }
+
+        let mut pages = self.pages.lock();
+        // Someone else may have loaded the page in the meantime.
+        if let Some(page) = pages.get(&idx) {
+            return Ok(page.frame.clone());
+        }
+        if self.backend.is_some() {
+            CACHED_PAGES.fetch_add(1, Ordering::Relaxed);
+        }
+        pages.insert(
+            idx,
+            CachedPage {
+                frame: frame.clone(),
+                dirty: false,
+                version: 0,
+                mapped_writable: false,
+            },
+        );
+        Ok(frame)
+    }
+
+    /// Marks page `idx` as modified.
+    pub fn mark_dirty(&self, idx: usize) {
+        if let Some(page) = self.pages.lock().get_mut(&idx) {
+            page.dirty = true;
+            page.version += 1;
+        }
+    }
+
+    /// Marks page `idx` as mapped shared and writable. It stays dirty as
+    /// long as it is mapped, since stores through the mapping are not seen.
+    pub fn mark_mapped_writable(&self, idx: usize) {
+        if let Some(page) = self.pages.lock().get_mut(&idx) {
+            page.dirty = true;
+            page.version += 1;
+            page.mapped_writable = true;
+        }
+    }
+
+    /// Copies `len` bytes starting at `offset` to `writer`.
+    pub fn read(&self, offset: usize, len: usize, writer: &mut VmWriter) -> Result<usize> {
+        let mut done = 0;
+        while done < len {
+            let pos = offset + done;
+            let chunk = (PAGE_SIZE - pos % PAGE_SIZE).min(len - done);
+            let frame = self.get_page(pos / PAGE_SIZE)?;
+            let mut reader = frame.reader();
+            reader.skip(pos % PAGE_SIZE).limit(chunk);
+            writer
+                .write_fallible(&mut reader)
+                .map_err(|_| Error::new(Errno::EFAULT))?;
+            done += chunk;
+        }
+        Ok(done)
+    }
+
+    /// Copies `len` bytes from `reader` to the cache at `offset`.
+    pub fn write(&self, offset: usize, len: usize, reader: &mut VmReader) -> Result<usize> {
+        let mut done = 0;
+        while done < len {
+            let pos = offset + done;
+            let chunk = (PAGE_SIZE - pos % PAGE_SIZE).min(len - done);
+            let idx = pos / PAGE_SIZE;
+            let frame = self.get_page(idx)?;
+            let mut writer = frame.writer();
+            writer.skip(pos % PAGE_SIZE).limit(chunk);
+            reader
+                .read_fallible(&mut writer)
+                .map_err(|_| Error::new(Errno::EFAULT))?;
+            self.mark_dirty(idx);
+            done += chunk;
+        }
+        Ok(done)
+    }
+
+    /// Drops the pages past `new_size` and zeroes the tail of the last page,
+    /// so that the file reads as zeros if it grows again.
+    pub fn resize(&self, new_size: usize) {
+        let mut pages = self.pages.lock();
+        let before = pages.len();
+        pages.retain(|&idx, _| idx < new_size.div_ceil(PAGE_SIZE));
+        if self.backend.is_some() {
+            CACHED_PAGES.fetch_sub(before - pages.len(), Ordering::Relaxed);
+        }
+
+        if new_size % PAGE_SIZE != 0 {
+            if let Some(page) = pages.get_mut(&(new_size / PAGE_SIZE)) {
+                let mut writer = page.frame.writer();
+                writer.skip(new_size % PAGE_SIZE);
+                writer.fill_zeros(PAGE_SIZE - new_size % PAGE_SIZE);
+                page.dirty = true;
+                page.version += 1;
+            }
+        }
+    }
+
+    /// Writes all dirty pages back to the backend.
+    pub fn flush(&self) -> Result<()> {
+        let Some(backend) = self.backend() else {
+            return Ok(());
+        };
+
+        let dirty: Vec<(usize, Frame<()>, u64)> = self
+            .pages
+            .lock()
+            .iter()
+            .filter(|(_, page)| page.dirty)
+            .map(|(idx, page)| (*idx, page.frame.clone(), page.version))
+            .collect();
+        for (idx, frame, version) in dirty {
+            debug!("Page cache: write back page {}", idx);
+            // A page that fails to be written stays dirty.
+            backend.write_page(idx, &frame)?;
+            drop(frame);
+
+            let mut pages = self.pages.lock();
+            let Some(page) = pages.get_mut(&idx) else {
+                continue;
+            };
+            // The page is clean only if it was not changed while written,
+            // and cannot be changed through a mapping either. The cache
+            // itself holds the only reference of an unmapped page.
+            if page.version != version {
+                continue;
+            }
+            if page.mapped_writable && page.frame.reference_count() > 1 {
+                continue;
+            }
+            page.dirty = false;
+            page.mapped_writable = false;
+        }
+        Ok(())
+    }
+
+    /// Drops the clean pages that are not mapped anywhere. Returns the number of
+    /// dropped pages.
+    pub fn evict(&self) -> usize {
+        if self.backend.is_none() {
+            return 0;
+        }
+
+        let mut pages = self.pages.lock();
+        let before = pages.len();
+        // The cache itself holds the only reference of an unmapped page.
+        pages.retain(|_, page| page.dirty || page.frame.reference_count() > 1);
+        let evicted = before - pages.len();
+        CACHED_PAGES.fetch_sub(evicted, Ordering::Relaxed);
+        evicted
+    }
+
+    fn backend(&self) -> Option<Arc<dyn PageCacheBackend>> {
+        self.backend.as_ref().and_then(|backend| backend.upgrade())
+    }
+}
+
+impl Drop for PageCache {
+    fn drop(&mut self) {
+        if self.backend.is_some() {
+            CACHED_PAGES.fetch_sub(self.pages.get_mut().len(), Ordering::Relaxed);
+        }
+    }
+}
+
+/// Allocates a zeroed frame for a cache page, shrinking the page caches when
+/// memory is low.
+fn alloc_page() -> Result<Frame<()>> {
+    if CACHED_PAGES.load(Ordering::Relaxed) >= MAX_CACHED_PAGES {
+        shrink();
+    }
+
+    if let Ok(frame) = FrameAllocOptions::new().alloc_frame() {
+        return Ok(frame);
+    }
+    shrink();
+    FrameAllocOptions::new()
+        .alloc_frame()
+        .map_err(|_| Error::new(Errno::ENOMEM))
+}
+
+/// Writes back the dirty pages of all backed page caches.
+pub fn flush_all() -> Result<()> {
+    let caches: Vec<Arc<PageCache>> = PAGE_CACHES
+        .lock()
+        .iter()
+        .filter_map(|cache| cache.upgrade())
+        .collect();
+    for cache in caches {
+        cache.flush()?;
+    }
+    Ok(())
+}
+
+/// Writes back and drops pages of all backed page caches.
+pub fn shrink() {
+    let caches: Vec<Arc<PageCache>> = {
+        let mut caches = PAGE_CACHES.lock();
+        caches.retain(|cache| cache.strong_count() > 0);
+        caches.iter().filter_map(|cache| cache.upgrade()).collect()
+    };
+
+    let mut evicted = 0;
+    for cache in caches {
+        if cache.flush().is_ok() {
+            evicted += cache.evict();
+        }
+    }
+    debug!("Page cache: evicted {} pages", evicted);
+}
@@ -0,0 +1,147 @@
+//! Sequential readahead.
+//!
+//! Each open file keeps a [`ReadaheadState`]. When reads keep continuing where
+//! the previous one ended, the pages after the read are prefetched into the
+//! page cache by a background kernel task, and the prefetch window doubles on
+//! every sequential read up to `MAX_WINDOW` pages. A random access shrinks the
+//! window back to `INIT_WINDOW`.
+
+use alloc::{
+    collections::vec_deque::VecDeque,
+    sync::{Arc, Weak},
+};
+use log::debug;
+use ostd::{
+    mm::PAGE_SIZE,
+    sync::{Mutex, WaitQueue},
+    task::TaskOptions,
+};
+use spin::Once;
+
+use crate::fs::util::page_cache::PageCache;
+
+/// The number of pages prefetched when a sequential access is first detected.
+const INIT_WINDOW: usize = 4;
+/// The maximum number of pages prefetched ahead of the reader.
+const MAX_WINDOW: usize = 64;
+
+pub struct ReadaheadState {
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    /// The page right after the last read. A read starting here is sequential.
+    next_page: usize,
+    /// The page after the last page that has been submitted for prefetching.
+    ahead_end: usize,
+    /// The current window size in pages. Zero means no sequential access yet.
+    window: usize,
+}
+
+impl ReadaheadState {
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                next_page: 0,
+                ahead_end: 0,
+                window: 0,
+            }),
+        }
+    }
+
+    /// Records a read of `len` bytes at `offset` and prefetches the following
+    /// pages of `page_cache` if the access is sequential.
+    pub fn on_read(&self, page_cache: &Arc<PageCache>, offset: usize, len: usize, size: usize) {
+        // Without a backend, there is nothing to load the pages from.
+        if len == 0 || !page_cache.has_backend() {
+            return;
+        }
+
+        let first_page = offset / PAGE_SIZE;
+        let end_page = (offset + len).div_ceil(PAGE_SIZE);
+        let mut inner = self.inner.lock();
+
+        // A read from the start of the file or right after the previous read.
+        let sequential = first_page == inner.next_page
+            || first_page + 1 == inner.next_page
+            || (offset == 0 && inner.window == 0);
+        inner.next_page = end_page;
+        if !sequential {
+            inner.window = 0;
+            inner.ahead_end = end_page;
+            return;
+        }
+
+        // Only submit the next window once the reader has consumed half of the
+        // previous one, so that the device always has work queued ahead.
+        let start = inner.ahead_end.max(end_page);
+        if start - end_page > inner.window / 2 {
+            return;
+        }
+        inner.window = if inner.window == 0 {
+            INIT_WINDOW
+        } else {
+            (inner.window * 2).min(MAX_WINDOW)
+        };
+
+        let end = (end_page + inner.window).min(size.div_ceil(PAGE_SIZE));
+        if start >= end {
+            return;
+        }
+        inner.ahead_end = end;
+        drop(inner);
+
+        submit(Arc::downgrade(page_cache), start, end);
+    }
+}
+
+impl Default for ReadaheadState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A range of pages to prefetch.
+struct Request {
+    page_cache: Weak<PageCache>,
+    start: usize,
+    end: usize,
+}
+
+static REQUESTS: Mutex<VecDeque<Request>> = Mutex::new(VecDeque::new());
+static REQUESTS_QUEUE: WaitQueue = WaitQueue::new();
+static WORKER: Once<()> = Once::new();
+
+fn submit(page_cache: Weak<PageCache>, start: usize, end: usize) {
+    WORKER.call_once(|| {
+        TaskOptions::new(worker).spawn().unwrap();
+    });
+
+    REQUESTS.lock().push_back(Request {
+        page_cache,
+        start,
+        end,
+    });
+    REQUESTS_QUEUE.wake_all();
+}
+
+/// The background task that loads the requested pages into the page caches.
+fn worker() {
+    loop {
+        let request = REQUESTS_QUEUE.wait_until(|| REQUESTS.lock().pop_front());
+        // The file has been closed and dropped in the meantime.
+        let Some(page_cache) = request.page_cache.upgrade() else {
+            continue;
+        };
+        if !page_cache.has_backend() {
+            continue;
+        }
+
+        debug!("Readahead: pages {}..{}", request.start, request.end);
+        for idx in request.start..request.end {
+            if page_cache.get_page(idx).is_err() {
+                break;
+            }
+        }
+    }
+}
@@ -1,8 +1,10 @@
 use alloc::sync::{Arc, Weak};
 use ostd::Pod;
 
-use crate::drivers::blk::BlockDevice;
+use crate::drivers::blk::{BlockDevice, SECTOR_SIZE};
+use crate::fs::util::buffer_cache::BUFFER_CACHE;
 
+/// A typed pointer to an on-disk structure, accessed through the buffer cache.
 pub struct SectorPtr<T: Pod> {
     sector: usize,
     offset: usize,
@@ -25,6 +27,18 @@ impl<T: Pod> SectorPtr<T> {
             .blk_device
             .upgrade()
             .expect("Block device has been dropped");
-        blk_device.read_val_offset::<T>(self.sector, self.offset)
+        BUFFER_CACHE.read_val::<T>(&blk_device, self.byte_offset())
+    }
+
+    pub fn write(&self, val: &T) {
+        let blk_device = self
+            .blk_device
+            .upgrade()
+            .expect("Block device has been dropped");
+        BUFFER_CACHE.write_val::<T>(&blk_device, self.byte_offset(), val);
+    }
+
+    fn byte_offset(&self) -> usize {
+        self.sector * SECTOR_SIZE + self.offset
     }
 }
@@ -3,6 +3,7 @@
 #![feature(fn_traits)]
 #![feature(ascii_char)]
 
+mod cmdline;
 pub mod console;
 mod drivers;
 mod error;
@@ -24,6 +25,18 @@ pub fn main() {
     sched::init();
     fs::init();
 
-    let process = process::Process::new(progs::lookup_progs("init_proc").unwrap());
+    // Prefer the init program of the root filesystem over the built-in one.
+    let resolver = fs::PathResolver::from_root();
+    let (path, (init, exe)) = cmdline::get("init")
+        .into_iter()
+        .chain(["/sbin/init", "/init"])
+        .find_map(|path| Some((path, progs::load_program(&resolver, path).ok()?)))
+        .unwrap_or_else(|| {
+            (
+                "init_proc",
+                progs::load_program(&resolver, "init_proc").unwrap(),
+            )
+        });
+    let process = process::Process::new(&init, exe, alloc::vec![path.into()]);
     process.run();
 }
@@ -2,6 +2,7 @@ use core::fmt::Debug;
 
 use crate::{
     error::{Errno, Error, Result},
+    fs::FileLike,
     mm::VmMapping,
     process::Process,
 };
@@ -42,6 +43,17 @@ impl PageFaultContext<'_> {
 
 pub trait PageFaultHandler: Send + Sync + Debug {
     fn handle_page_fault<'a>(&self, context: PageFaultContext<'a>) -> Result<()>;
+
+    /// Whether the frames mapped by this handler are shared with others instead
+    /// of being private to the address space.
+    fn is_shared(&self) -> bool {
+        false
+    }
+
+    /// The file whose pages are mapped, if any.
+    fn file(&self) -> Option<&Arc<dyn FileLike>> {
+        None
+    }
 }
 
 #[derive(Debug)]
@@ -6,6 +6,7 @@ use alloc::{collections::linked_list::LinkedList, sync::Arc};
 pub use mapping::VmMapping;
 use ostd::{
     arch::cpu::context::CpuExceptionInfo,
+    boot::memory_region::MemoryRegionType,
     mm::{
         CachePolicy, FrameAllocOptions, MAX_USERSPACE_VADDR, PAGE_SIZE, PageProperty, Segment,
         VmSpace, io_util::HasVmReaderWriter,
@@ -16,6 +17,16 @@ use ostd::{
 
 use crate::{mm::area::VmArea, process::Process};
 
+/// The size in bytes of the usable physical memory.
+pub fn total_memory() -> usize {
+    ostd::boot::boot_info()
+        .memory_regions
+        .iter()
+        .filter(|region| region.typ() == MemoryRegionType::Usable)
+        .map(|region| region.len())
+        .sum()
+}
+
 pub fn page_fault_handler(
     process: &Arc<Process>,
     cpu_exception: &CpuExceptionInfo,
@@ -86,6 +97,8 @@ impl MemorySpace {
     }
 
     /// Duplicate self with new phyiscal frames. Also, this will copy the data inside each frame.
+    ///
+    /// Shared areas (e.g., `MAP_SHARED` file mappings) keep mapping the same frames.
     pub fn duplicate(&self) -> Self {
         let new_memory_space = MemorySpace::new();
         let mut new_mappings = new_memory_space.areas.lock();
@@ -100,12 +113,18 @@ impl MemorySpace {
                 area.page_fault_handler().clone(),
             );
 
+            let shared = area.page_fault_handler().is_shared();
             let old_mappings = area.mappings().iter().map(|mapping| mapping);
             for old_mapping in old_mappings {
-                let new_frame = FrameAllocOptions::new().alloc_frame().unwrap();
+                let new_frame = if shared {
+                    old_mapping.frame().clone()
+                } else {
+                    let new_frame = FrameAllocOptions::new().alloc_frame().unwrap();
 
-                // Copy data from old frame to new frame
-                new_frame.writer().write(&mut old_mapping.frame().reader());
+                    // Copy data from old frame to new frame
+                    new_frame.writer().write(&mut old_mapping.frame().reader());
+                    new_frame
+                };
 
                 let mut cursor_mut = new_memory_space
                     .vm_space
@@ -130,6 +149,11 @@ impl MemorySpace {
         new_memory_space
     }
 
+    /// Calls `f` with the areas, e.g., to report them.
+    pub fn with_areas<T>(&self, f: impl FnOnce(&LinkedList<VmArea>) -> T) -> T {
+        f(&self.areas.lock())
+    }
+
     pub fn vm_space(&self) -> &Arc<VmSpace> {
         &self.vm_space
     }
@@ -0,0 +1,205 @@
+//! The user and group identities of a process.
+//!
+//! Permissions are checked against the effective ids. Unprivileged processes
+//! may only switch their ids among the real, effective and saved ones, while
+//! privileged processes (whose effective user id is root) may pick any.
+
+use alloc::vec::Vec;
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::InodeMeta;
+
+pub type Uid = u32;
+pub type Gid = u32;
+
+pub const ROOT_UID: Uid = 0;
+/// The max number of supplementary groups of a process.
+pub const NGROUPS_MAX: usize = 65536;
+
+/// The set-user-ID and set-group-ID bits of a file mode.
+const S_ISUID: u16 = 0o4000;
+const S_ISGID: u16 = 0o2000;
+
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    pub ruid: Uid,
+    pub euid: Uid,
+    pub suid: Uid,
+    pub rgid: Gid,
+    pub egid: Gid,
+    pub sgid: Gid,
+    /// The supplementary groups.
+    pub groups: Vec<Gid>,
+}
+
+impl Credentials {
+    /// The credentials of the superuser, which the first process starts with.
+    pub fn root() -> Self {
+        Self {
+            ruid: ROOT_UID,
+            euid: ROOT_UID,
+            suid: ROOT_UID,
+            rgid: 0,
+            egid: 0,
+            sgid: 0,
+            groups: Vec::new(),
+        }
+    }
+
+    /// Whether the process may bypass permission checks and set any id.
+    pub fn is_privileged(&self) -> bool {
+        self.euid == ROOT_UID
+    }
+
+    /// Whether `gid` is the effective or a supplementary group.
+    pub fn in_group(&self, gid: Gid) -> bool {
+        self.egid == gid || self.groups.contains(&gid)
+    }
+
+    /// The credentials that check permissions with the real ids instead of
+    /// the effective ones, as `access` does.
+    pub fn with_real_ids(&self) -> Self {
+        Self {
+            euid: self.ruid,
+            egid: self.rgid,
+            ..self.clone()
+        }
+    }
+
+    pub fn set_uid(&mut self, uid: Uid) -> Result<()> {
+        if self.is_privileged() {
+            self.ruid = uid;
+            self.suid = uid;
+        } else if uid != self.ruid && uid != self.suid {
+            return Err(Error::new(Errno::EPERM));
+        }
+        self.euid = uid;
+        Ok(())
+    }
+
+    /// Sets the real and the effective user id, leaving those that are `None`.
+    pub fn set_reuid(&mut self, ruid: Option<Uid>, euid: Option<Uid>) -> Result<()> {
+        if !self.is_privileged() {
+            let ruid_ok = ruid.is_none_or(|uid| uid == self.ruid || uid == self.euid);
+            let euid_ok = euid.is_none_or(|uid| [self.ruid, self.euid, self.suid].contains(&uid));
+            if !ruid_ok || !euid_ok {
+                return Err(Error::new(Errno::EPERM));
+            }
+        }
+
+        // The saved id follows the effective one if the real one is set or
+        // the effective one is set to something else than the real one.
+        let old_ruid = self.ruid;
+        self.ruid = ruid.unwrap_or(self.ruid);
+        self.euid = euid.unwrap_or(self.euid);
+        if ruid.is_some() || euid.is_some_and(|uid| uid != old_ruid) {
+            self.suid = self.euid;
+        }
+        Ok(())
+    }
+
+    /// Sets the real, the effective and the saved user id, leaving those that
+    /// are `None`.
+    pub fn set_resuid(
+        &mut self,
+        ruid: Option<Uid>,
+        euid: Option<Uid>,
+        suid: Option<Uid>,
+    ) -> Result<()> {
+        let current = [self.ruid, self.euid, self.suid];
+        if !self.is_privileged()
+            && ![ruid, euid, suid]
+                .into_iter()
+                .flatten()
+                .all(|uid| current.contains(&uid))
+        {
+            return Err(Error::new(Errno::EPERM));
+        }
+
+        self.ruid = ruid.unwrap_or(self.ruid);
+        self.euid = euid.unwrap_or(self.euid);
+        self.suid = suid.unwrap_or(self.suid);
+        Ok(())
+    }
+
+    pub fn set_gid(&mut self, gid: Gid) -> Result<()> {
+        if self.is_privileged() {
+            self.rgid = gid;
+            self.sgid = gid;
+        } else if gid != self.rgid && gid != self.sgid {
+            return Err(Error::new(Errno::EPERM));
+        }
+        self.egid = gid;
+        Ok(())
+    }
+
+    /// Sets the real and the effective group id, leaving those that are `None`.
+    pub fn set_regid(&mut self, rgid: Option<Gid>, egid: Option<Gid>) -> Result<()> {
+        if !self.is_privileged() {
+            let rgid_ok = rgid.is_none_or(|gid| gid == self.rgid || gid == self.egid);
+            let egid_ok = egid.is_none_or(|gid| [self.rgid, self.egid, self.sgid].contains(&gid));
+            if !rgid_ok || !egid_ok {
+                return Err(Error::new(Errno::EPERM));
+            }
+        }
+
+        let old_rgid = self.rgid;
+        self.rgid = rgid.unwrap_or(self.rgid);
+        self.egid = egid.unwrap_or(self.egid);
+        if rgid.is_some() || egid.is_some_and(|gid| gid != old_rgid) {
+            self.sgid = self.egid;
+        }
+        Ok(())
+    }
+
+    /// Sets the real, the effective and the saved group id, leaving those
+    /// that are `None`.
+    pub fn set_resgid(
+        &mut self,
+        rgid: Option<Gid>,
+        egid: Option<Gid>,
+        sgid: Option<Gid>,
+    ) -> Result<()> {
+        let current = [self.rgid, self.egid, self.sgid];
+        if !self.is_privileged()
+            && ![rgid, egid, sgid]
+                .into_iter()
+                .flatten()
+                .all(|gid| current.contains(&gid))
+        {
+            return Err(Error::new(Errno::EPERM));
+        }
+
+        self.rgid = rgid.unwrap_or(self.rgid);
+        self.egid = egid.unwrap_or(self.egid);
+        self.sgid = sgid.unwrap_or(self.sgid);
+        Ok(())
+    }
+
+    pub fn set_groups(&mut self, groups: Vec<Gid>) -> Result<()> {
+        if !self.is_privileged() {
+            return Err(Error::new(Errno::EPERM));
+        }
+        if groups.len() > NGROUPS_MAX {
+            return Err(Error::new(Errno::EINVAL));
+        }
+        self.groups = groups;
+        Ok(())
+    }
+
+    /// Takes the ids a program runs with on `execve`: those of the owner of
+    /// the program file if it is set-user-ID or set-group-ID. The saved ids
+    /// become the effective ones.
+    pub fn exec(&mut self, program: Option<&InodeMeta>) {
+        if let Some(program) = program {
+            if program.mode & S_ISUID != 0 {
+                self.euid = program.uid;
+            }
+            if program.mode & S_ISGID != 0 {
+                self.egid = program.gid;
+            }
+        }
+        self.suid = self.euid;
+        self.sgid = self.egid;
+    }
+}
@@ -9,7 +9,7 @@ use ostd::{
 
 use crate::{
     mm::{MemorySpace, area::VmArea, fault::AllocationPageFaultHandler},
-    process::USER_STACK_SIZE,
+    process::{USER_STACK_SIZE, USER_STACK_TOP},
 };
 
 pub fn load_user_space(program: &[u8], memory_space: &MemorySpace) -> UserContext {
@@ -78,15 +78,15 @@ fn parse_elf(input: &[u8], memory_space: &MemorySpace, user_cpu_state: &mut User
         }
     }
 
-    // Second, init the user stack with addr: 0x40_0000_0000 - 10 * PAGE_SIZE.
-    let stack_low = 0x40_0000_0000 - 10 * PAGE_SIZE - USER_STACK_SIZE;
+    // Second, init the user stack below `USER_STACK_TOP`.
+    let stack_low = USER_STACK_TOP - USER_STACK_SIZE;
     memory_space.add_area(VmArea::new_with_handler(
         stack_low,
         USER_STACK_SIZE / PAGE_SIZE,
         PageFlags::RW,
         Arc::new(AllocationPageFaultHandler),
     ));
-    user_cpu_state.set_stack_pointer(0x40_0000_0000 - 10 * PAGE_SIZE - 32);
+    user_cpu_state.set_stack_pointer(USER_STACK_TOP - 32);
     user_cpu_state.set_instruction_pointer(header.pt2.entry_point() as usize);
 
     // Third, map the 0 address
@@ -1,28 +1,37 @@
+mod credentials;
 mod elf;
 mod heap;
 mod status;
 
 use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
 
 use alloc::boxed::Box;
 use alloc::collections::btree_map::BTreeMap;
+use alloc::string::String;
 use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
 use log::{debug, info};
 use ostd::arch::cpu::context::UserContext;
 use ostd::arch::qemu::{QemuExitCode, exit_qemu};
 use ostd::early_println;
 use ostd::sync::{Mutex, MutexGuard, WaitQueue};
 use ostd::task::{Task, TaskOptions};
+use ostd::timer::Jiffies;
 use ostd::user::{ReturnReason, UserContextApi, UserMode};
 use riscv::register::scause::Exception;
 use spin::Once;
 
 use crate::error::{Errno, Error, Result};
 use crate::fs::file_table::FileTable;
+use crate::fs::{Path, PathResolver};
 use crate::mm::MemorySpace;
 use crate::process::heap::UserHeap;
 use crate::process::status::ProcessStatus;
+pub use credentials::{Credentials, Gid, NGROUPS_MAX, Uid};
 pub const USER_STACK_SIZE: usize = 8192 * 1024; // 8MB
+/// The top of the user stack, which grows down from it.
+pub const USER_STACK_TOP: usize = 0x40_0000_0000 - 10 * ostd::mm::PAGE_SIZE;
 
 static PROCESS_TABLE: Mutex<BTreeMap<Pid, Arc<Process>>> = Mutex::new(BTreeMap::new());
 
@@ -48,6 +57,16 @@ pub struct Process {
     task: Once<Arc<Task>>,
     /// File table
     file_table: Mutex<FileTable>,
+    /// The root and the working directory
+    fs: Mutex<PathResolver>,
+    /// The user and group identities
+    credentials: Mutex<Credentials>,
+    /// The time since boot when the process was created
+    start_time: Duration,
+    /// The program running, which built-in programs have no file for
+    exe: Mutex<Option<Path>>,
+    /// The arguments the program was run with
+    argv: Mutex<Vec<String>>,
 
     // ======================== Memory management ===============================
     memory_space: MemorySpace,
@@ -64,7 +83,7 @@ pub struct Process {
 }
 
 impl Process {
-    pub fn new(user_prog_bin: &[u8]) -> Arc<Self> {
+    pub fn new(user_prog_bin: &[u8], exe: Option<Path>, argv: Vec<String>) -> Arc<Self> {
         let (memory_space, user_context) = elf::create_user_space(user_prog_bin);
 
         let process = Arc::new(Process {
@@ -77,6 +96,11 @@ impl Process {
             children: Mutex::new(BTreeMap::new()),
             wait_children_queue: WaitQueue::new(),
             file_table: Mutex::new(FileTable::new_with_standard_io()),
+            fs: Mutex::new(PathResolver::from_root()),
+            credentials: Mutex::new(Credentials::root()),
+            start_time: Jiffies::elapsed().as_duration(),
+            exe: Mutex::new(exe),
+            argv: Mutex::new(argv),
         });
 
         let task = create_user_task(&process, Box::new(user_context));
@@ -106,6 +130,11 @@ impl Process {
             children: Mutex::new(BTreeMap::new()),
             wait_children_queue: WaitQueue::new(),
             file_table: Mutex::new(self.file_table().duplicate()),
+            fs: Mutex::new(self.fs().clone()),
+            credentials: Mutex::new(self.credentials().clone()),
+            start_time: Jiffies::elapsed().as_duration(),
+            exe: Mutex::new(self.exe()),
+            argv: Mutex::new(self.argv()),
         });
 
         let task = create_user_task(&child_process, Box::new(user_context));
@@ -122,7 +151,10 @@ impl Process {
         child_process
     }
 
-    pub fn exec(&self, binary: &[u8]) -> UserContext {
+    pub fn exec(&self, binary: &[u8], exe: Option<Path>, argv: Vec<String>) -> UserContext {
+        self.file_table().close_on_exec();
+        *self.exe.lock() = exe;
+        *self.argv.lock() = argv;
         self.memory_space.clear();
         elf::load_user_space(binary, &self.memory_space)
     }
@@ -184,6 +216,33 @@ impl Process {
         self.file_table.lock()
     }
 
+    pub fn fs(&self) -> MutexGuard<PathResolver> {
+        self.fs.lock()
+    }
+
+    pub fn credentials(&self) -> MutexGuard<Credentials> {
+        self.credentials.lock()
+    }
+
+    /// A resolver for the paths given by this process, which checks the
+    /// permissions of the directories on the way with its credentials.
+    pub fn resolver(&self) -> PathResolver {
+        let credentials = self.credentials().clone();
+        self.fs().clone().with_credentials(credentials)
+    }
+
+    pub fn start_time(&self) -> Duration {
+        self.start_time
+    }
+
+    pub fn exe(&self) -> Option<Path> {
+        self.exe.lock().clone()
+    }
+
+    pub fn argv(&self) -> Vec<String> {
+        self.argv.lock().clone()
+    }
+
     pub fn is_zombie(&self) -> bool {
         self.status.is_zombie()
     }
@@ -249,6 +308,30 @@ impl Process {
     }
 }
 
+/// The process with `pid`, which may be a zombie not yet waited for.
+pub fn get_process(pid: Pid) -> Option<Arc<Process>> {
+    PROCESS_TABLE.lock().get(&pid).cloned()
+}
+
+/// All the processes, ordered by pid.
+pub fn processes() -> Vec<Arc<Process>> {
+    PROCESS_TABLE.lock().values().cloned().collect()
+}
+
+/// Stops all processes other than `current`, e.g., before the system goes down.
+///
+/// The stopped processes leave their task loop the next time they enter the kernel.
+pub fn stop_all_except(current: &Process) {
+    for process in processes() {
+        if process.pid != current.pid && !process.is_zombie() {
+            process.status.exit(SIGKILL_EXIT_CODE);
+        }
+    }
+}
+
+/// The exit code of a process killed by `SIGKILL`.
+const SIGKILL_EXIT_CODE: u32 = 9;
+
 fn create_user_task(process: &Arc<Process>, user_context: Box<UserContext>) -> Arc<Task> {
     let entry = move |user_ctx| {
         let process = current_process();
@@ -315,7 +398,7 @@ fn create_user_task(process: &Arc<Process>, user_context: Box<UserContext>) -> A
     )
 }
 
-type Pid = usize;
+pub type Pid = usize;
 
 fn alloc_pid() -> Pid {
     static NEXT_PID: AtomicUsize = AtomicUsize::new(1);
@@ -1,9 +1,14 @@
 use crate::error::{Errno, Error, Result};
-use alloc::collections::btree_map::BTreeMap;
+use crate::fs::permission::Permission;
+use crate::fs::{Inode, InodeType, Path, PathResolver};
+use alloc::{borrow::Cow, collections::btree_map::BTreeMap, sync::Arc, vec, vec::Vec};
+use ostd::mm::VmWriter;
 use spin::Once;
 
 mod progs;
 
+pub use progs::INITRAMFS;
+
 pub static USER_PROGS: Once<BTreeMap<&str, &'static [u8]>> = Once::new();
 
 pub fn init() {
@@ -18,3 +23,32 @@ pub fn lookup_progs(prog_name: &str) -> Result<&'static [u8]> {
         .ok_or(Error::new(Errno::ENOENT))
         .copied()
 }
+
+/// Loads the program at `path`, falling back to the built-in program of that
+/// name if there is no such file. Returns the program along with its file,
+/// which a built-in program has none of.
+pub fn load_program(
+    resolver: &PathResolver,
+    path: &str,
+) -> Result<(Cow<'static, [u8]>, Option<Path>)> {
+    match resolver.lookup(path, true) {
+        Ok(path) => {
+            resolver.check_permission(path.inode(), Permission::EXEC)?;
+            let binary = read_all(path.inode())?;
+            Ok((Cow::Owned(binary), Some(path)))
+        }
+        Err(err) if err.code == Errno::ENOENT => Ok((Cow::Borrowed(lookup_progs(path)?), None)),
+        Err(err) => Err(err),
+    }
+}
+
+/// Reads the whole content of a regular file.
+fn read_all(inode: &Arc<dyn Inode>) -> Result<Vec<u8>> {
+    if inode.typ() != InodeType::File {
+        return Err(Error::new(Errno::EACCES));
+    }
+    let mut content = vec![0u8; inode.size()];
+    let len = inode.read_at(0, VmWriter::from(content.as_mut_slice()).to_fallible())?;
+    content.truncate(len);
+    Ok(content)
+}
@@ -1,3 +1,6 @@
+/// The initramfs embedded in the kernel image.
+pub const INITRAMFS: &[u8] =
+    include_bytes_aligned::include_bytes_aligned!(32, "../../target/initramfs.cpio");
 const EXEC: &[u8] =
     include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/exec");
 const FORK: &[u8] =
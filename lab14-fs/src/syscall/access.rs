@@ -0,0 +1,72 @@
+use alloc::sync::Arc;
+use log::debug;
+use ostd::mm::Vaddr;
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::permission::{Permission, check_permission};
+use crate::process::Process;
+use crate::syscall::write::get_file;
+use crate::syscall::{AT_FDCWD, SyscallReturn, read_path, resolver_at};
+
+const AT_SYMLINK_NOFOLLOW: u32 = 0x100;
+/// Check with the effective ids instead of the real ones.
+const AT_EACCESS: u32 = 0x200;
+const AT_EMPTY_PATH: u32 = 0x1000;
+
+pub fn sys_faccessat(
+    dirfd: i32,
+    path: Vaddr,
+    mode: u32,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    sys_faccessat2(dirfd, path, mode, 0, current_process)
+}
+
+/// Checks whether the process may access the file at `path` as `mode` tells,
+/// or whether the file exists if `mode` is `F_OK` (zero).
+pub fn sys_faccessat2(
+    dirfd: i32,
+    path: Vaddr,
+    mode: u32,
+    flags: u32,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    let path = read_path(path, current_process)?;
+    debug!(
+        "[SYS_FACCESSAT2] dirfd: {}, path: {}, mode: {:#o}, flags: {:#x}",
+        dirfd, path, mode, flags
+    );
+
+    if flags & !(AT_SYMLINK_NOFOLLOW | AT_EACCESS | AT_EMPTY_PATH) != 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    let permission = u16::try_from(mode)
+        .ok()
+        .and_then(Permission::from_bits)
+        .ok_or(Error::new(Errno::EINVAL))?;
+    // Set-user-ID programs use `access` to check what their invoker may do.
+    let credentials = current_process.credentials().clone();
+    let credentials = if flags & AT_EACCESS != 0 {
+        credentials
+    } else {
+        credentials.with_real_ids()
+    };
+
+    let inode = if path.is_empty() && flags & AT_EMPTY_PATH != 0 {
+        if dirfd == AT_FDCWD {
+            current_process.fs().cwd().inode().clone()
+        } else {
+            get_file(dirfd, current_process)?
+                .as_inode()
+                .ok_or(Error::new(Errno::EBADF))?
+        }
+    } else {
+        let resolver =
+            resolver_at(dirfd, &path, current_process)?.with_credentials(credentials.clone());
+        let path = resolver.lookup(&path, flags & AT_SYMLINK_NOFOLLOW == 0)?;
+        path.inode().clone()
+    };
+    check_permission(&inode, &credentials, permission)?;
+
+    Ok(SyscallReturn(0))
+}
@@ -0,0 +1,41 @@
+use alloc::sync::Arc;
+use log::debug;
+use ostd::mm::Vaddr;
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::InodeType;
+use crate::fs::permission::Permission;
+use crate::process::Process;
+use crate::syscall::{SyscallReturn, read_path};
+
+pub fn sys_chdir(path: Vaddr, current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    let path = read_path(path, current_process)?;
+    debug!("[SYS_CHDIR] path: {}", path);
+
+    let resolver = current_process.resolver();
+    let dir = resolver.lookup(&path, true)?;
+    if dir.inode().typ() != InodeType::Directory {
+        return Err(Error::new(Errno::ENOTDIR));
+    }
+    resolver.check_permission(dir.inode(), Permission::EXEC)?;
+    current_process.fs().set_cwd(dir);
+    Ok(SyscallReturn(0))
+}
+
+pub fn sys_fchdir(fd: i32, current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_FCHDIR] fd: {}", fd);
+
+    let dir = {
+        let file_table = current_process.file_table();
+        let file = file_table.get(fd).ok_or(Error::new(Errno::EBADF))?.file();
+        file.as_path().ok_or(Error::new(Errno::ENOTDIR))?
+    };
+    if dir.inode().typ() != InodeType::Directory {
+        return Err(Error::new(Errno::ENOTDIR));
+    }
+    current_process
+        .resolver()
+        .check_permission(dir.inode(), Permission::EXEC)?;
+    current_process.fs().set_cwd(dir);
+    Ok(SyscallReturn(0))
+}
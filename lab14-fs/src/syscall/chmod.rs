@@ -0,0 +1,59 @@
+use alloc::sync::Arc;
+use log::debug;
+use ostd::mm::Vaddr;
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::Inode;
+use crate::process::{Credentials, Process};
+use crate::syscall::write::get_file;
+use crate::syscall::{SyscallReturn, read_path, resolver_at};
+
+const S_ISGID: u16 = 0o2000;
+
+pub fn sys_fchmod(fd: i32, mode: u32, current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_FCHMOD] fd: {}, mode: {:#o}", fd, mode);
+
+    // Pipes and the console have no inode to keep the mode in.
+    let inode = get_file(fd, current_process)?
+        .as_inode()
+        .ok_or(Error::new(Errno::EPERM))?;
+    let credentials = current_process.credentials().clone();
+    chmod(&inode, mode, &credentials)?;
+
+    Ok(SyscallReturn(0))
+}
+
+pub fn sys_fchmodat(
+    dirfd: i32,
+    path: Vaddr,
+    mode: u32,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    let path = read_path(path, current_process)?;
+    debug!(
+        "[SYS_FCHMODAT] dirfd: {}, path: {}, mode: {:#o}",
+        dirfd, path, mode
+    );
+
+    let resolver = resolver_at(dirfd, &path, current_process)?;
+    let path = resolver.lookup(&path, true)?;
+    chmod(path.inode(), mode, resolver.credentials())?;
+
+    Ok(SyscallReturn(0))
+}
+
+/// Changes the mode of `inode`, which only its owner may do.
+fn chmod(inode: &Arc<dyn Inode>, mode: u32, credentials: &Credentials) -> Result<()> {
+    let meta = inode.metadata();
+    let mut mode = (mode & 0o7777) as u16;
+    if !credentials.is_privileged() {
+        if credentials.euid != meta.uid {
+            return Err(Error::new(Errno::EPERM));
+        }
+        // Only the members of its group may make a file set-group-ID.
+        if !credentials.in_group(meta.gid) {
+            mode &= !S_ISGID;
+        }
+    }
+    inode.set_mode(mode)
+}
@@ -0,0 +1,105 @@
+use alloc::sync::Arc;
+use log::debug;
+use ostd::mm::Vaddr;
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::{Inode, InodeType};
+use crate::process::{Credentials, Gid, Process, Uid};
+use crate::syscall::write::get_file;
+use crate::syscall::{AT_FDCWD, SyscallReturn, read_path, resolver_at};
+
+const AT_SYMLINK_NOFOLLOW: u32 = 0x100;
+const AT_EMPTY_PATH: u32 = 0x1000;
+
+/// The id that leaves the owner or the group unchanged.
+const UNCHANGED: u32 = u32::MAX;
+
+const S_ISUID: u16 = 0o4000;
+const S_ISGID: u16 = 0o2000;
+const S_IXGRP: u16 = 0o010;
+
+pub fn sys_fchown(
+    fd: i32,
+    uid: Uid,
+    gid: Gid,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_FCHOWN] fd: {}, uid: {}, gid: {}",
+        fd, uid as i32, gid as i32
+    );
+
+    // Pipes and the console have no inode to keep the owner in.
+    let inode = get_file(fd, current_process)?
+        .as_inode()
+        .ok_or(Error::new(Errno::EPERM))?;
+    let credentials = current_process.credentials().clone();
+    chown(&inode, uid, gid, &credentials)?;
+
+    Ok(SyscallReturn(0))
+}
+
+pub fn sys_fchownat(
+    dirfd: i32,
+    path: Vaddr,
+    uid: Uid,
+    gid: Gid,
+    flags: u32,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    let path = read_path(path, current_process)?;
+    debug!(
+        "[SYS_FCHOWNAT] dirfd: {}, path: {}, uid: {}, gid: {}, flags: {:#x}",
+        dirfd, path, uid as i32, gid as i32, flags
+    );
+
+    if flags & !(AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH) != 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    let credentials = current_process.credentials().clone();
+    if path.is_empty() && flags & AT_EMPTY_PATH != 0 {
+        let inode = if dirfd == AT_FDCWD {
+            current_process.fs().cwd().inode().clone()
+        } else {
+            get_file(dirfd, current_process)?
+                .as_inode()
+                .ok_or(Error::new(Errno::EPERM))?
+        };
+        chown(&inode, uid, gid, &credentials)?;
+        return Ok(SyscallReturn(0));
+    }
+
+    let resolver = resolver_at(dirfd, &path, current_process)?;
+    let path = resolver.lookup(&path, flags & AT_SYMLINK_NOFOLLOW == 0)?;
+    chown(path.inode(), uid, gid, &credentials)?;
+
+    Ok(SyscallReturn(0))
+}
+
+/// Changes the owner and the group of `inode`. Only a privileged process may
+/// give a file away, while the owner may change the group to one of its own.
+fn chown(inode: &Arc<dyn Inode>, uid: Uid, gid: Gid, credentials: &Credentials) -> Result<()> {
+    let meta = inode.metadata();
+    let new_uid = if uid == UNCHANGED { meta.uid } else { uid };
+    let new_gid = if gid == UNCHANGED { meta.gid } else { gid };
+    if !credentials.is_privileged()
+        && (new_uid != meta.uid
+            || credentials.euid != meta.uid
+            || (new_gid != meta.gid && !credentials.in_group(new_gid)))
+    {
+        return Err(Error::new(Errno::EPERM));
+    }
+
+    inode.set_owner(new_uid, new_gid)?;
+    // A program does not stay set-user-ID or set-group-ID for its new owner.
+    // Without group execute permission, the set-group-ID bit marks mandatory
+    // locking instead and is kept.
+    if meta.type_ != InodeType::Directory && meta.mode & (S_ISUID | S_ISGID) != 0 {
+        let mut mode = meta.mode & !S_ISUID;
+        if mode & S_IXGRP != 0 {
+            mode &= !S_ISGID;
+        }
+        inode.set_mode(mode)?;
+    }
+    Ok(())
+}
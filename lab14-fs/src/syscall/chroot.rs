@@ -0,0 +1,28 @@
+use alloc::sync::Arc;
+use log::debug;
+use ostd::mm::Vaddr;
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::InodeType;
+use crate::fs::permission::Permission;
+use crate::process::Process;
+use crate::syscall::{SyscallReturn, read_path};
+
+pub fn sys_chroot(path: Vaddr, current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    let path = read_path(path, current_process)?;
+    debug!("[SYS_CHROOT] path: {}", path);
+
+    if !current_process.credentials().is_privileged() {
+        return Err(Error::new(Errno::EPERM));
+    }
+    let resolver = current_process.resolver();
+    let dir = resolver.lookup(&path, true)?;
+    if dir.inode().typ() != InodeType::Directory {
+        return Err(Error::new(Errno::ENOTDIR));
+    }
+    resolver.check_permission(dir.inode(), Permission::EXEC)?;
+    // Like Linux, the working directory is left alone and may now be
+    // outside of the root directory.
+    current_process.fs().set_root(dir);
+    Ok(SyscallReturn(0))
+}
@@ -0,0 +1,19 @@
+use alloc::sync::Arc;
+use log::debug;
+
+use crate::error::{Errno, Error, Result};
+use crate::process::Process;
+use crate::syscall::SyscallReturn;
+
+pub fn sys_close(fd: i32, current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_CLOSE] fd: {}", fd);
+
+    let entry = current_process
+        .file_table()
+        .close(fd)
+        .ok_or(Error::new(Errno::EBADF))?;
+    // The file is released once the last descriptor of it is closed.
+    drop(entry);
+
+    Ok(SyscallReturn(0))
+}
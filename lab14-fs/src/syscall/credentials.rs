@@ -0,0 +1,195 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use log::debug;
+use ostd::mm::Vaddr;
+
+use crate::error::{Errno, Error, Result};
+use crate::process::{Gid, NGROUPS_MAX, Process, Uid};
+use crate::syscall::{SyscallReturn, write_to_user};
+
+/// The id that tells the `setre*id` and `setres*id` syscalls to leave an id unchanged.
+const UNCHANGED: u32 = u32::MAX;
+
+fn id_arg(id: u32) -> Option<u32> {
+    (id != UNCHANGED).then_some(id)
+}
+
+pub fn sys_getuid(current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    Ok(SyscallReturn(current_process.credentials().ruid as _))
+}
+
+pub fn sys_geteuid(current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    Ok(SyscallReturn(current_process.credentials().euid as _))
+}
+
+pub fn sys_getgid(current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    Ok(SyscallReturn(current_process.credentials().rgid as _))
+}
+
+pub fn sys_getegid(current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    Ok(SyscallReturn(current_process.credentials().egid as _))
+}
+
+pub fn sys_setuid(uid: Uid, current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_SETUID] uid: {}", uid);
+
+    current_process.credentials().set_uid(uid)?;
+    Ok(SyscallReturn(0))
+}
+
+pub fn sys_setgid(gid: Gid, current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_SETGID] gid: {}", gid);
+
+    current_process.credentials().set_gid(gid)?;
+    Ok(SyscallReturn(0))
+}
+
+pub fn sys_setreuid(ruid: Uid, euid: Uid, current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_SETREUID] ruid: {}, euid: {}",
+        ruid as i32, euid as i32
+    );
+
+    current_process
+        .credentials()
+        .set_reuid(id_arg(ruid), id_arg(euid))?;
+    Ok(SyscallReturn(0))
+}
+
+pub fn sys_setregid(rgid: Gid, egid: Gid, current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_SETREGID] rgid: {}, egid: {}",
+        rgid as i32, egid as i32
+    );
+
+    current_process
+        .credentials()
+        .set_regid(id_arg(rgid), id_arg(egid))?;
+    Ok(SyscallReturn(0))
+}
+
+pub fn sys_setresuid(
+    ruid: Uid,
+    euid: Uid,
+    suid: Uid,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_SETRESUID] ruid: {}, euid: {}, suid: {}",
+        ruid as i32, euid as i32, suid as i32
+    );
+
+    current_process
+        .credentials()
+        .set_resuid(id_arg(ruid), id_arg(euid), id_arg(suid))?;
+    Ok(SyscallReturn(0))
+}
+
+pub fn sys_setresgid(
+    rgid: Gid,
+    egid: Gid,
+    sgid: Gid,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_SETRESGID] rgid: {}, egid: {}, sgid: {}",
+        rgid as i32, egid as i32, sgid as i32
+    );
+
+    current_process
+        .credentials()
+        .set_resgid(id_arg(rgid), id_arg(egid), id_arg(sgid))?;
+    Ok(SyscallReturn(0))
+}
+
+pub fn sys_getresuid(
+    ruid: Vaddr,
+    euid: Vaddr,
+    suid: Vaddr,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    let credentials = current_process.credentials().clone();
+    write_to_user(ruid, &credentials.ruid, current_process)?;
+    write_to_user(euid, &credentials.euid, current_process)?;
+    write_to_user(suid, &credentials.suid, current_process)?;
+    Ok(SyscallReturn(0))
+}
+
+pub fn sys_getresgid(
+    rgid: Vaddr,
+    egid: Vaddr,
+    sgid: Vaddr,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    let credentials = current_process.credentials().clone();
+    write_to_user(rgid, &credentials.rgid, current_process)?;
+    write_to_user(egid, &credentials.egid, current_process)?;
+    write_to_user(sgid, &credentials.sgid, current_process)?;
+    Ok(SyscallReturn(0))
+}
+
+/// Copies the supplementary groups to `list`, which holds `size` of them.
+/// A `size` of zero only asks for their number.
+pub fn sys_getgroups(
+    size: i32,
+    list: Vaddr,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!("[SYS_GETGROUPS] size: {}, list: {:#x}", size, list);
+
+    if size < 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    let groups = current_process.credentials().groups.clone();
+    if size == 0 {
+        return Ok(SyscallReturn(groups.len() as _));
+    }
+    if (size as usize) < groups.len() {
+        return Err(Error::new(Errno::EINVAL));
+    }
+
+    let mut writer = current_process
+        .memory_space()
+        .vm_space()
+        .writer(list, groups.len() * size_of::<Gid>())
+        .map_err(|_| Error::new(Errno::EFAULT))?;
+    for gid in groups.iter() {
+        writer
+            .write_val(gid)
+            .map_err(|_| Error::new(Errno::EFAULT))?;
+    }
+    Ok(SyscallReturn(groups.len() as _))
+}
+
+pub fn sys_setgroups(
+    size: usize,
+    list: Vaddr,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!("[SYS_SETGROUPS] size: {}, list: {:#x}", size, list);
+
+    if size > NGROUPS_MAX {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    if !current_process.credentials().is_privileged() {
+        return Err(Error::new(Errno::EPERM));
+    }
+
+    let mut groups = Vec::with_capacity(size);
+    if size > 0 {
+        let mut reader = current_process
+            .memory_space()
+            .vm_space()
+            .reader(list, size * size_of::<Gid>())
+            .map_err(|_| Error::new(Errno::EFAULT))?;
+        for _ in 0..size {
+            groups.push(
+                reader
+                    .read_val::<Gid>()
+                    .map_err(|_| Error::new(Errno::EFAULT))?,
+            );
+        }
+    }
+    current_process.credentials().set_groups(groups)?;
+    Ok(SyscallReturn(0))
+}
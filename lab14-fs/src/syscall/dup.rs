@@ -0,0 +1,64 @@
+use alloc::sync::Arc;
+use log::debug;
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::OpenFlags;
+use crate::fs::file_table::{FdFlags, FileEntry, MAX_FDS};
+use crate::process::Process;
+use crate::syscall::SyscallReturn;
+
+pub fn sys_dup(old_fd: i32, current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_DUP] old_fd: {}", old_fd);
+
+    let mut file_table = current_process.file_table();
+    let file = file_table
+        .get(old_fd)
+        .ok_or(Error::new(Errno::EBADF))?
+        .file()
+        .clone();
+    // The new descriptor shares the open file, but not the descriptor flags.
+    let new_fd = file_table.insert_from(0, FileEntry::new(file, FdFlags::empty()))?;
+
+    Ok(SyscallReturn(new_fd as _))
+}
+
+pub fn sys_dup3(
+    old_fd: i32,
+    new_fd: i32,
+    flags: u32,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_DUP3] old_fd: {}, new_fd: {}, flags: {:#x}",
+        old_fd, new_fd, flags
+    );
+
+    let flags = OpenFlags::from_bits(flags).ok_or(Error::new(Errno::EINVAL))?;
+    if !(flags - OpenFlags::O_CLOEXEC).is_empty() {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    let fd_flags = if flags.contains(OpenFlags::O_CLOEXEC) {
+        FdFlags::FD_CLOEXEC
+    } else {
+        FdFlags::empty()
+    };
+
+    let mut file_table = current_process.file_table();
+    let file = file_table
+        .get(old_fd)
+        .ok_or(Error::new(Errno::EBADF))?
+        .file()
+        .clone();
+    if new_fd < 0 || new_fd as usize >= MAX_FDS {
+        return Err(Error::new(Errno::EBADF));
+    }
+    if old_fd == new_fd {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    let old_entry = file_table.put(new_fd, FileEntry::new(file, fd_flags));
+    // Release the replaced file after unlocking the file table.
+    drop(file_table);
+    drop(old_entry);
+
+    Ok(SyscallReturn(new_fd as _))
+}
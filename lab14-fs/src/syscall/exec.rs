@@ -1,14 +1,16 @@
 use core::ffi::CStr;
 
+use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec;
+use alloc::vec::Vec;
 use log::{debug, info};
 use ostd::arch::cpu::context::UserContext;
 use ostd::mm::{FallibleVmRead, Vaddr, VmWriter};
 
-use crate::error::Result;
+use crate::error::{Errno, Error, Result};
 use crate::process::Process;
-use crate::syscall::SyscallReturn;
+use crate::syscall::{SyscallReturn, read_path};
 
 pub fn sys_execve(
     path: Vaddr, /* &[u8] */
@@ -22,7 +24,8 @@ pub fn sys_execve(
         path, argv, envp
     );
 
-    // We ignore the argv and envp for now.
+    // The argv is recorded but not passed to the program yet, and the envp
+    // is ignored.
     // The max file name: 255 bytes + 1(\0)
     const MAX_FILENAME_LENGTH: usize = 256;
     let mut buffer = vec![0u8; MAX_FILENAME_LENGTH];
@@ -40,15 +43,49 @@ pub fn sys_execve(
         .unwrap();
 
     info!("[SYS_EXECVE] Execute program path: {}", exec_name);
+    let args = read_args(argv, current_process)?;
 
-    let binary = crate::progs::lookup_progs(exec_name)?;
+    let resolver = current_process.resolver();
+    let (binary, exe) = crate::progs::load_program(&resolver, exec_name)?;
+    // Set-user-ID and set-group-ID programs run with the ids of their owner.
+    // Built-in programs have no file and keep the ids.
+    let program = exe.as_ref().map(|path| path.inode().metadata());
+    current_process.credentials().exec(program.as_ref());
 
     // Do exec:
     // 1. Cleanup all the memory space, including heap
     // 2. Change the user context to zero
     // 3. Parse ELF and load program
 
-    *user_context = current_process.exec(binary);
+    *user_context = current_process.exec(&binary, exe, args);
 
     Ok(SyscallReturn(0 as _))
 }
+
+/// The max number of arguments of a program.
+const MAX_ARGS: usize = 256;
+
+/// Reads the NULL-terminated array of arguments at `argv`, which may be NULL.
+fn read_args(argv: Vaddr, current_process: &Arc<Process>) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    if argv == 0 {
+        return Ok(args);
+    }
+
+    for idx in 0..=MAX_ARGS {
+        let arg: Vaddr = current_process
+            .memory_space()
+            .vm_space()
+            .reader(argv + idx * size_of::<Vaddr>(), size_of::<Vaddr>())
+            .and_then(|mut reader| reader.read_val())
+            .map_err(|_| Error::new(Errno::EFAULT))?;
+        if arg == 0 {
+            return Ok(args);
+        }
+        if idx == MAX_ARGS {
+            break;
+        }
+        args.push(read_path(arg, current_process)?);
+    }
+    Err(Error::new(Errno::E2BIG))
+}
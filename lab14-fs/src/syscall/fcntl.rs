@@ -0,0 +1,53 @@
+use alloc::sync::Arc;
+use log::debug;
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::OpenFlags;
+use crate::fs::file_table::{FdFlags, FileEntry};
+use crate::process::Process;
+use crate::syscall::SyscallReturn;
+
+const F_DUPFD: u32 = 0;
+const F_GETFD: u32 = 1;
+const F_SETFD: u32 = 2;
+const F_GETFL: u32 = 3;
+const F_SETFL: u32 = 4;
+const F_DUPFD_CLOEXEC: u32 = 1030;
+
+pub fn sys_fcntl(
+    fd: i32,
+    cmd: u32,
+    arg: usize,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!("[SYS_FCNTL] fd: {}, cmd: {}, arg: {:#x}", fd, cmd, arg);
+
+    let mut file_table = current_process.file_table();
+    let entry = file_table.get_mut(fd).ok_or(Error::new(Errno::EBADF))?;
+    match cmd {
+        F_DUPFD | F_DUPFD_CLOEXEC => {
+            let fd_flags = if cmd == F_DUPFD_CLOEXEC {
+                FdFlags::FD_CLOEXEC
+            } else {
+                FdFlags::empty()
+            };
+            let min_fd = i32::try_from(arg).map_err(|_| Error::new(Errno::EINVAL))?;
+            let file = entry.file().clone();
+            let new_fd = file_table.insert_from(min_fd, FileEntry::new(file, fd_flags))?;
+            Ok(SyscallReturn(new_fd as _))
+        }
+        F_GETFD => Ok(SyscallReturn(entry.flags().bits() as _)),
+        F_SETFD => {
+            entry.set_flags(FdFlags::from_bits_truncate(arg as u32));
+            Ok(SyscallReturn(0))
+        }
+        F_GETFL => Ok(SyscallReturn(entry.file().status_flags().bits() as _)),
+        F_SETFL => {
+            entry
+                .file()
+                .set_status_flags(OpenFlags::from_bits_truncate(arg as u32));
+            Ok(SyscallReturn(0))
+        }
+        _ => Err(Error::new(Errno::EINVAL)),
+    }
+}
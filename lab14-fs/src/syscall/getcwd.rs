@@ -0,0 +1,34 @@
+use alloc::sync::Arc;
+use log::debug;
+use ostd::mm::{FallibleVmWrite, Vaddr, VmReader};
+
+use crate::error::{Errno, Error, Result};
+use crate::process::Process;
+use crate::syscall::SyscallReturn;
+
+pub fn sys_getcwd(
+    buf: Vaddr,
+    size: usize,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!("[SYS_GETCWD] buf: {:#x}, size: {}", buf, size);
+
+    let mut cwd = {
+        let fs = current_process.fs();
+        fs.cwd().abs_path(fs.root()).into_bytes()
+    };
+    // The path is returned with the terminating NUL.
+    cwd.push(0);
+    if size < cwd.len() {
+        return Err(Error::new(Errno::ERANGE));
+    }
+
+    current_process
+        .memory_space()
+        .vm_space()
+        .writer(buf, cwd.len())
+        .map_err(|_| Error::new(Errno::EFAULT))?
+        .write_fallible(&mut VmReader::from(cwd.as_slice()))
+        .map_err(|_| Error::new(Errno::EFAULT))?;
+    Ok(SyscallReturn(cwd.len() as _))
+}
@@ -0,0 +1,84 @@
+use alloc::{sync::Arc, vec::Vec};
+use log::debug;
+use ostd::mm::{FallibleVmWrite, Vaddr, VmReader};
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::{DirEntry, InodeType};
+use crate::process::Process;
+use crate::syscall::SyscallReturn;
+use crate::syscall::write::get_file;
+
+const DT_FIFO: u8 = 1;
+const DT_CHR: u8 = 2;
+const DT_DIR: u8 = 4;
+const DT_BLK: u8 = 6;
+const DT_REG: u8 = 8;
+const DT_LNK: u8 = 10;
+
+/// The size of the fixed part of `linux_dirent64`: `d_ino`, `d_off`,
+/// `d_reclen` and `d_type`.
+const DIRENT64_HEADER_SIZE: usize = 19;
+
+pub fn sys_getdents64(
+    fd: i32,
+    buf: Vaddr,
+    count: usize,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_GETDENTS64] fd: {}, buf: {:#x}, count: {}",
+        fd, buf, count
+    );
+
+    let file = get_file(fd, current_process)?;
+    let mut dirents = Vec::new();
+    file.readdir(&mut |entry: DirEntry, next_offset| {
+        // Records are padded so that the next one is 8-byte aligned.
+        let record_len = (DIRENT64_HEADER_SIZE + entry.name.len() + 1).next_multiple_of(8);
+        if dirents.len() + record_len > count {
+            return false;
+        }
+
+        let d_type = match entry.type_ {
+            InodeType::File => DT_REG,
+            InodeType::Directory => DT_DIR,
+            InodeType::SymbolLink => DT_LNK,
+            InodeType::CharDevice => DT_CHR,
+            InodeType::BlockDevice => DT_BLK,
+            InodeType::NamedPipe => DT_FIFO,
+        };
+        dirents.extend_from_slice(&entry.ino.to_ne_bytes());
+        dirents.extend_from_slice(&(next_offset as i64).to_ne_bytes());
+        dirents.extend_from_slice(&(record_len as u16).to_ne_bytes());
+        dirents.push(d_type);
+        dirents.extend_from_slice(entry.name.as_bytes());
+        dirents.resize(
+            dirents.len() + record_len - DIRENT64_HEADER_SIZE - entry.name.len(),
+            0,
+        );
+        true
+    })?;
+
+    // The buffer cannot hold even the first entry.
+    if dirents.is_empty() && count != 0 {
+        // Tell the end of the directory apart from a small buffer.
+        let mut has_more = false;
+        file.readdir(&mut |_, _| {
+            has_more = true;
+            false
+        })?;
+        if has_more {
+            return Err(Error::new(Errno::EINVAL));
+        }
+    }
+
+    current_process
+        .memory_space()
+        .vm_space()
+        .writer(buf, dirents.len())
+        .map_err(|_| Error::new(Errno::EFAULT))?
+        .write_fallible(&mut VmReader::from(dirents.as_slice()))
+        .map_err(|_| Error::new(Errno::EFAULT))?;
+
+    Ok(SyscallReturn(dirents.len() as _))
+}
@@ -0,0 +1,47 @@
+use alloc::sync::Arc;
+use log::debug;
+use ostd::mm::Vaddr;
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::permission::Permission;
+use crate::process::Process;
+use crate::syscall::write::get_file;
+use crate::syscall::{SyscallReturn, read_path, resolver_at};
+
+const AT_SYMLINK_FOLLOW: u32 = 0x400;
+const AT_EMPTY_PATH: u32 = 0x1000;
+
+pub fn sys_linkat(
+    old_dirfd: i32,
+    old_path: Vaddr,
+    new_dirfd: i32,
+    new_path: Vaddr,
+    flags: u32,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    let old_path = read_path(old_path, current_process)?;
+    let new_path = read_path(new_path, current_process)?;
+    debug!(
+        "[SYS_LINKAT] old_dirfd: {}, old_path: {}, new_dirfd: {}, new_path: {}, flags: {:#x}",
+        old_dirfd, old_path, new_dirfd, new_path, flags
+    );
+
+    if flags & !(AT_SYMLINK_FOLLOW | AT_EMPTY_PATH) != 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    let old = if old_path.is_empty() && flags & AT_EMPTY_PATH != 0 {
+        get_file(old_dirfd, current_process)?
+            .as_path()
+            .ok_or(Error::new(Errno::EBADF))?
+    } else {
+        let resolver = resolver_at(old_dirfd, &old_path, current_process)?;
+        resolver.lookup(&old_path, flags & AT_SYMLINK_FOLLOW != 0)?
+    };
+
+    let resolver = resolver_at(new_dirfd, &new_path, current_process)?;
+    let (dir, name) = resolver.lookup_parent(&new_path)?;
+    resolver.check_permission(dir.inode(), Permission::WRITE | Permission::EXEC)?;
+    dir.link(&old, &name)?;
+
+    Ok(SyscallReturn(0))
+}
@@ -0,0 +1,26 @@
+use alloc::sync::Arc;
+use log::debug;
+use ostd::mm::Vaddr;
+
+use crate::error::Result;
+use crate::fs::InodeType;
+use crate::process::Process;
+use crate::syscall::{SyscallReturn, read_path, resolver_at};
+
+pub fn sys_mkdirat(
+    dirfd: i32,
+    path: Vaddr,
+    mode: u32,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    let path = read_path(path, current_process)?;
+    debug!(
+        "[SYS_MKDIRAT] dirfd: {}, path: {}, mode: {:#o}",
+        dirfd, path, mode
+    );
+
+    let resolver = resolver_at(dirfd, &path, current_process)?;
+    resolver.create(&path, InodeType::Directory, mode as u16)?;
+
+    Ok(SyscallReturn(0))
+}
@@ -0,0 +1,49 @@
+use alloc::sync::Arc;
+use log::debug;
+use ostd::mm::Vaddr;
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::InodeType;
+use crate::process::Process;
+use crate::syscall::{SyscallReturn, read_path, resolver_at};
+
+const S_IFMT: u32 = 0o170000;
+const S_IFSOCK: u32 = 0o140000;
+const S_IFREG: u32 = 0o100000;
+const S_IFBLK: u32 = 0o060000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFCHR: u32 = 0o020000;
+const S_IFIFO: u32 = 0o010000;
+
+pub fn sys_mknodat(
+    dirfd: i32,
+    path: Vaddr,
+    mode: u32,
+    dev: u32,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    let path = read_path(path, current_process)?;
+    debug!(
+        "[SYS_MKNODAT] dirfd: {}, path: {}, mode: {:#o}, dev: {:#x}",
+        dirfd, path, mode, dev
+    );
+
+    let type_ = match mode & S_IFMT {
+        0 | S_IFREG => InodeType::File,
+        S_IFCHR => InodeType::CharDevice,
+        S_IFBLK => InodeType::BlockDevice,
+        // Named pipes and sockets cannot be opened from the tree yet.
+        S_IFIFO | S_IFSOCK => return Err(Error::new(Errno::EINVAL)),
+        S_IFDIR => return Err(Error::new(Errno::EPERM)),
+        _ => return Err(Error::new(Errno::EINVAL)),
+    };
+    if type_ != InodeType::File && !current_process.credentials().is_privileged() {
+        return Err(Error::new(Errno::EPERM));
+    }
+
+    let resolver = resolver_at(dirfd, &path, current_process)?;
+    // The 32-bit device number encodes the same way as the 64-bit one.
+    resolver.mknod(&path, type_, dev as u64, mode as u16)?;
+
+    Ok(SyscallReturn(0))
+}
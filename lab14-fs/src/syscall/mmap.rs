@@ -7,13 +7,16 @@ use ostd::mm::io_util::HasVmReaderWriter;
 use ostd::mm::{CachePolicy, FrameAllocOptions, PAGE_SIZE, PageFlags, PageProperty, Vaddr};
 
 use crate::error::{Errno, Error, Result};
-use crate::fs::Inode;
+use crate::fs::{FileLike, Inode};
 use crate::mm::VmMapping;
 use crate::mm::area::VmArea;
 use crate::mm::fault::{PageFaultContext, PageFaultHandler};
 use crate::process::Process;
 use crate::syscall::SyscallReturn;
 
+const MAP_SHARED: u32 = 0x1;
+const MAP_PRIVATE: u32 = 0x2;
+
 bitflags::bitflags! {
     pub struct MMapFlags : u32 {
         const MAP_FIXED           = 0x10;
@@ -43,27 +46,45 @@ pub fn sys_mmap(
     current_process: &Arc<Process>,
 ) -> Result<SyscallReturn> {
     // Current, we only support mmap with file
-    // MAP_PRIVATE, MAP_FIXED, no MAP_ANONYMOUS
+    // MAP_SHARED or MAP_PRIVATE, MAP_FIXED, no MAP_ANONYMOUS
     assert!(vaddr != 0);
     assert!(vaddr.align_down(PAGE_SIZE as _) == vaddr);
     assert!(offset == 0);
-    assert!(flags & 0xf == 0x2);
+    let shared = match flags & 0xf {
+        MAP_SHARED => true,
+        MAP_PRIVATE => false,
+        _ => return Err(Error::new(Errno::EINVAL)),
+    };
     let mmap_flags = MMapFlags::from_bits_truncate(flags as u32);
     assert!(mmap_flags == MMapFlags::MAP_FIXED);
 
     // Now, we can map the file
     let page_flags = PageFlags::from_bits_truncate(perms as _);
-    let inode = current_process
+    let file = current_process
         .file_table()
         .get(fd as _)
-        .unwrap()
+        .ok_or(Error::new(Errno::EBADF))?
         .file()
-        .as_inode()
-        .ok_or(Error::new(Errno::EBADF))?;
+        .clone();
+    let inode = file.as_inode().ok_or(Error::new(Errno::EBADF))?;
+    // The stores to a shared mapping reach the file, so it must be open for
+    // writing as well.
+    let status_flags = file.status_flags();
+    if !status_flags.is_readable()
+        || (shared && page_flags.contains(PageFlags::W) && !status_flags.is_writable())
+    {
+        return Err(Error::new(Errno::EACCES));
+    }
+    // The pages written must be those of the file the writes end up in.
+    if shared && page_flags.contains(PageFlags::W) {
+        inode.prepare_write()?;
+    }
 
     let handler = Arc::new(MMapInodeFaultHandler {
         base_vaddr: vaddr as _,
+        file,
         inode,
+        shared,
     });
 
     let memory_space = current_process.memory_space();
@@ -79,13 +100,17 @@ pub fn sys_mmap(
 
 pub struct MMapInodeFaultHandler {
     base_vaddr: Vaddr,
+    file: Arc<dyn FileLike>,
     inode: Arc<dyn Inode>,
+    /// Whether writes are visible to other mappings of the file (`MAP_SHARED`).
+    shared: bool,
 }
 
 impl Debug for MMapInodeFaultHandler {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("MMapInodeFaultHandler")
             .field("base_vaddr", &self.base_vaddr)
+            .field("shared", &self.shared)
             .finish()
     }
 }
@@ -94,16 +119,44 @@ impl PageFaultHandler for MMapInodeFaultHandler {
     fn handle_page_fault<'a>(&self, context: PageFaultContext<'a>) -> Result<()> {
         let memory_space = context.process.memory_space();
         let vm_space = memory_space.vm_space();
-        let frame = FrameAllocOptions::new().alloc_frame().unwrap();
         let align_down_vaddr = context.vaddr.align_down(PAGE_SIZE);
+        let offset = align_down_vaddr - self.base_vaddr;
 
-        // Read data from Inode
-        self.inode
-            .read_at(
-                align_down_vaddr - self.base_vaddr,
-                frame.writer().to_fallible(),
-            )
-            .unwrap();
+        let frame = if let Some(page_cache) = self.inode.page_cache() {
+            let idx = offset / PAGE_SIZE;
+            let cached = page_cache.get_page(idx)?;
+            if self.shared {
+                // Map the cached frame itself so that every mapping sees the same data.
+                if context.perms.contains(PageFlags::W) {
+                    // Back the page with storage before it is written, as
+                    // `page_mkwrite` does on Linux, or the stores to a hole
+                    // would be dropped on write-back.
+                    let start = idx * PAGE_SIZE;
+                    let len = self.inode.size().saturating_sub(start).min(PAGE_SIZE);
+                    if len > 0 {
+                        match self.inode.allocate(start, len) {
+                            Ok(()) => {}
+                            // The filesystem has no holes.
+                            Err(err) if err.code == Errno::EOPNOTSUPP => {}
+                            Err(err) => return Err(err),
+                        }
+                    }
+                    page_cache.mark_mapped_writable(idx);
+                }
+                cached
+            } else {
+                let frame = FrameAllocOptions::new().alloc_frame().unwrap();
+                frame.writer().write(&mut cached.reader());
+                frame
+            }
+        } else {
+            // Read data from Inode
+            let frame = FrameAllocOptions::new().alloc_frame().unwrap();
+            self.inode
+                .read_at(offset, frame.writer().to_fallible())
+                .unwrap();
+            frame
+        };
 
         let guard = disable_local();
         let mut cursor_mut = vm_space
@@ -120,4 +173,12 @@ impl PageFaultHandler for MMapInodeFaultHandler {
 
         Ok(())
     }
+
+    fn is_shared(&self) -> bool {
+        self.shared
+    }
+
+    fn file(&self) -> Option<&Arc<dyn FileLike>> {
+        Some(&self.file)
+    }
 }
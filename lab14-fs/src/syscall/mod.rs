@@ -1,56 +1,225 @@
+mod access;
 mod brk;
+mod chdir;
+mod chmod;
+mod chown;
+mod chroot;
 mod clone;
+mod close;
+mod credentials;
+mod dup;
 mod exec;
 mod exit;
+mod fcntl;
+mod getcwd;
+mod getdents64;
+mod link;
+mod lseek;
+mod mkdir;
+mod mknod;
 mod mmap;
+mod mount;
 mod open;
 mod pipe;
 mod prlimit;
 mod read;
+mod reboot;
+mod rename;
+mod stat;
+mod statfs;
+mod symlink;
+mod sync;
 mod time;
+mod truncate;
 mod uname;
+mod unlink;
 mod wait4;
 mod write;
 
-use alloc::sync::Arc;
+use alloc::{string::String, sync::Arc, vec};
+use core::ffi::CStr;
 use log::{debug, info};
+use ostd::Pod;
 use ostd::arch::cpu::context::UserContext;
-use ostd::arch::qemu::exit_qemu;
+use ostd::mm::{FallibleVmRead, Vaddr, VmWriter};
 use ostd::task::Task;
 
 use crate::error::{Errno, Error, Result};
+use crate::fs::path::PATH_MAX;
+use crate::fs::{InodeType, PathResolver};
 use crate::process::Process;
+use crate::syscall::access::{sys_faccessat, sys_faccessat2};
 use crate::syscall::brk::sys_brk;
+use crate::syscall::chdir::{sys_chdir, sys_fchdir};
+use crate::syscall::chmod::{sys_fchmod, sys_fchmodat};
+use crate::syscall::chown::{sys_fchown, sys_fchownat};
+use crate::syscall::chroot::sys_chroot;
 use crate::syscall::clone::sys_clone;
+use crate::syscall::close::sys_close;
+use crate::syscall::credentials::{
+    sys_getegid, sys_geteuid, sys_getgid, sys_getgroups, sys_getresgid, sys_getresuid, sys_getuid,
+    sys_setgid, sys_setgroups, sys_setregid, sys_setresgid, sys_setresuid, sys_setreuid,
+    sys_setuid,
+};
+use crate::syscall::dup::{sys_dup, sys_dup3};
 use crate::syscall::exec::sys_execve;
 use crate::syscall::exit::sys_exit;
+use crate::syscall::fcntl::sys_fcntl;
+use crate::syscall::getcwd::sys_getcwd;
+use crate::syscall::getdents64::sys_getdents64;
+use crate::syscall::link::sys_linkat;
+use crate::syscall::lseek::sys_lseek;
+use crate::syscall::mkdir::sys_mkdirat;
+use crate::syscall::mknod::sys_mknodat;
 use crate::syscall::mmap::sys_mmap;
+use crate::syscall::mount::{sys_mount, sys_umount2};
 use crate::syscall::pipe::sys_pipe2;
 use crate::syscall::prlimit::sys_prlimit64;
-use crate::syscall::read::sys_read;
+use crate::syscall::read::{sys_pread64, sys_preadv, sys_read, sys_readv};
+use crate::syscall::reboot::sys_reboot;
+use crate::syscall::rename::sys_renameat2;
+use crate::syscall::stat::{sys_fstat, sys_newfstatat, sys_statx};
+use crate::syscall::statfs::{sys_fstatfs, sys_statfs};
+use crate::syscall::symlink::{sys_readlinkat, sys_symlinkat};
+use crate::syscall::sync::{sys_fdatasync, sys_fsync, sys_sync, sys_syncfs};
 use crate::syscall::time::sys_clock_gettime;
+use crate::syscall::truncate::{sys_fallocate, sys_ftruncate, sys_truncate};
 use crate::syscall::uname::sys_uname;
+use crate::syscall::unlink::sys_unlinkat;
 use crate::syscall::wait4::sys_wait4;
-use crate::syscall::write::{sys_write, sys_writev};
+use crate::syscall::write::{sys_pwrite64, sys_pwritev, sys_write, sys_writev};
 
 pub struct SyscallReturn(pub isize);
 
+/// The `dirfd` telling `*at` syscalls to resolve relative paths from the
+/// working directory.
+pub const AT_FDCWD: i32 = -100;
+
+/// Gets the resolver for a path given to a `*at` syscall, which resolves
+/// relative paths from the directory `dirfd`.
+fn resolver_at(dirfd: i32, path: &str, current_process: &Arc<Process>) -> Result<PathResolver> {
+    let mut resolver = current_process.resolver();
+    if dirfd == AT_FDCWD || path.starts_with('/') {
+        return Ok(resolver);
+    }
+
+    let file_table = current_process.file_table();
+    let file = file_table
+        .get(dirfd)
+        .ok_or(Error::new(Errno::EBADF))?
+        .file();
+    let dir = file.as_path().ok_or(Error::new(Errno::ENOTDIR))?;
+    if dir.inode().typ() != InodeType::Directory {
+        return Err(Error::new(Errno::ENOTDIR));
+    }
+    resolver.set_cwd(dir);
+    Ok(resolver)
+}
+
+/// Reads a NUL-terminated path from the user space.
+fn read_path(vaddr: Vaddr, current_process: &Arc<Process>) -> Result<String> {
+    let mut buffer = vec![0u8; PATH_MAX];
+    let mut reader = current_process
+        .memory_space()
+        .vm_space()
+        .reader(vaddr, PATH_MAX)
+        .map_err(|_| Error::new(Errno::EFAULT))?;
+    // The path may end right before an unmapped page.
+    let len = match reader.read_fallible(&mut VmWriter::from(buffer.as_mut_slice())) {
+        Ok(len) => len,
+        Err((_, len)) => len,
+    };
+
+    let path = CStr::from_bytes_until_nul(&buffer[..len]).map_err(|_| {
+        if len == PATH_MAX {
+            Error::new(Errno::ENAMETOOLONG)
+        } else {
+            Error::new(Errno::EFAULT)
+        }
+    })?;
+    path.to_str()
+        .map(String::from)
+        .map_err(|_| Error::new(Errno::EINVAL))
+}
+
+/// Writes `val` to the user space at `vaddr`.
+fn write_to_user<T: Pod>(vaddr: Vaddr, val: &T, current_process: &Arc<Process>) -> Result<()> {
+    current_process
+        .memory_space()
+        .vm_space()
+        .writer(vaddr, size_of::<T>())
+        .and_then(|mut writer| writer.write_val(val))
+        .map_err(|_| Error::new(Errno::EFAULT))
+}
+
 pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Process>) {
+    const SYS_GETCWD: usize = 17;
+    const SYS_DUP: usize = 23;
+    const SYS_DUP3: usize = 24;
+    const SYS_FCNTL: usize = 25;
+    const SYS_MKNODAT: usize = 33;
+    const SYS_MKDIRAT: usize = 34;
+    const SYS_UNLINKAT: usize = 35;
+    const SYS_SYMLINKAT: usize = 36;
+    const SYS_LINKAT: usize = 37;
+    const SYS_UMOUNT2: usize = 39;
+    const SYS_MOUNT: usize = 40;
+    const SYS_STATFS: usize = 43;
+    const SYS_FSTATFS: usize = 44;
+    const SYS_TRUNCATE: usize = 45;
+    const SYS_FTRUNCATE: usize = 46;
+    const SYS_FALLOCATE: usize = 47;
+    const SYS_FACCESSAT: usize = 48;
+    const SYS_CHDIR: usize = 49;
+    const SYS_FCHDIR: usize = 50;
+    const SYS_CHROOT: usize = 51;
+    const SYS_FCHMOD: usize = 52;
+    const SYS_FCHMODAT: usize = 53;
+    const SYS_FCHOWNAT: usize = 54;
+    const SYS_FCHOWN: usize = 55;
     const SYS_OPENAT: usize = 56;
+    const SYS_CLOSE: usize = 57;
     const SYS_PIPE2: usize = 59;
+    const SYS_GETDENTS64: usize = 61;
 
+    const SYS_LSEEK: usize = 62;
     const SYS_READ: usize = 63;
     const SYS_WRITE: usize = 64;
+    const SYS_READV: usize = 65;
     const SYS_WRITEV: usize = 66;
+    const SYS_PREAD64: usize = 67;
+    const SYS_PWRITE64: usize = 68;
+    const SYS_PREADV: usize = 69;
+    const SYS_PWRITEV: usize = 70;
+    const SYS_READLINKAT: usize = 78;
+    const SYS_NEWFSTATAT: usize = 79;
+    const SYS_FSTAT: usize = 80;
+    const SYS_SYNC: usize = 81;
+    const SYS_FSYNC: usize = 82;
+    const SYS_FDATASYNC: usize = 83;
     const SYS_EXIT: usize = 93;
     const SYS_EXIT_GROUP: usize = 94;
 
     const SYS_CLOCK_GETTIME: usize = 113;
     const SYS_SCHED_YIELD: usize = 124;
     const SYS_REBOOT: usize = 142;
+    const SYS_SETREGID: usize = 143;
+    const SYS_SETGID: usize = 144;
+    const SYS_SETREUID: usize = 145;
+    const SYS_SETUID: usize = 146;
+    const SYS_SETRESUID: usize = 147;
+    const SYS_GETRESUID: usize = 148;
+    const SYS_SETRESGID: usize = 149;
+    const SYS_GETRESGID: usize = 150;
+    const SYS_GETGROUPS: usize = 158;
+    const SYS_SETGROUPS: usize = 159;
     const SYS_NEWUNAME: usize = 160;
     const SYS_GETPID: usize = 172;
     const SYS_GETPPID: usize = 173;
+    const SYS_GETUID: usize = 174;
+    const SYS_GETEUID: usize = 175;
+    const SYS_GETGID: usize = 176;
+    const SYS_GETEGID: usize = 177;
     const SYS_BRK: usize = 214;
     const SYS_CLONE: usize = 220;
     const SYS_EXECVE: usize = 221;
@@ -58,6 +227,10 @@ pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Proc
     const SYS_MPROTECT: usize = 226;
     const SYS_WAIT4: usize = 260;
     const SYS_PRLIMIT64: usize = 261;
+    const SYS_SYNCFS: usize = 267;
+    const SYS_RENAMEAT2: usize = 276;
+    const SYS_STATX: usize = 291;
+    const SYS_FACCESSAT2: usize = 439;
 
     let args = [
         user_context.a0(),
@@ -122,7 +295,13 @@ pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Proc
             current_process,
         ),
         SYS_CLOCK_GETTIME => sys_clock_gettime(args[0] as _, args[1] as _, current_process),
-        SYS_REBOOT => exit_qemu(ostd::arch::qemu::QemuExitCode::Success),
+        SYS_REBOOT => sys_reboot(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            current_process,
+        ),
         SYS_READ => sys_read(args[0] as _, args[1] as _, args[2] as _, current_process),
         SYS_SCHED_YIELD => {
             Task::yield_now();
@@ -130,6 +309,51 @@ pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Proc
         }
 
         SYS_WRITE => sys_write(args[0] as _, args[1] as _, args[2] as _, current_process),
+        SYS_STATFS => sys_statfs(args[0] as _, args[1] as _, current_process),
+        SYS_FSTATFS => sys_fstatfs(args[0] as _, args[1] as _, current_process),
+        SYS_TRUNCATE => sys_truncate(args[0] as _, args[1] as _, current_process),
+        SYS_FTRUNCATE => sys_ftruncate(args[0] as _, args[1] as _, current_process),
+        SYS_FALLOCATE => sys_fallocate(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            current_process,
+        ),
+        SYS_LSEEK => sys_lseek(args[0] as _, args[1] as _, args[2] as _, current_process),
+        SYS_READV => sys_readv(args[0] as _, args[1] as _, args[2] as _, current_process),
+        SYS_PREAD64 => sys_pread64(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            current_process,
+        ),
+        SYS_PWRITE64 => sys_pwrite64(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            current_process,
+        ),
+        SYS_PREADV => sys_preadv(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            current_process,
+        ),
+        SYS_PWRITEV => sys_pwritev(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            current_process,
+        ),
+        SYS_SYNC => sys_sync(current_process),
+        SYS_FSYNC => sys_fsync(args[0] as _, current_process),
+        SYS_FDATASYNC => sys_fdatasync(args[0] as _, current_process),
+        SYS_SYNCFS => sys_syncfs(args[0] as _, current_process),
         SYS_EXIT | SYS_EXIT_GROUP => sys_exit(args[0] as _, current_process),
         SYS_OPENAT => open::sys_openat(
             args[0] as _,
@@ -138,6 +362,106 @@ pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Proc
             args[3] as _,
             current_process,
         ),
+        SYS_GETDENTS64 => sys_getdents64(args[0] as _, args[1] as _, args[2] as _, current_process),
+        SYS_MKNODAT => sys_mknodat(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            current_process,
+        ),
+        SYS_MKDIRAT => sys_mkdirat(args[0] as _, args[1] as _, args[2] as _, current_process),
+        SYS_UNLINKAT => sys_unlinkat(args[0] as _, args[1] as _, args[2] as _, current_process),
+        SYS_SYMLINKAT => sys_symlinkat(args[0] as _, args[1] as _, args[2] as _, current_process),
+        SYS_LINKAT => sys_linkat(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            args[4] as _,
+            current_process,
+        ),
+        SYS_READLINKAT => sys_readlinkat(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            current_process,
+        ),
+        SYS_RENAMEAT2 => sys_renameat2(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            args[4] as _,
+            current_process,
+        ),
+        SYS_FSTAT => sys_fstat(args[0] as _, args[1] as _, current_process),
+        SYS_NEWFSTATAT => sys_newfstatat(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            current_process,
+        ),
+        SYS_STATX => sys_statx(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            args[4] as _,
+            current_process,
+        ),
+        SYS_FACCESSAT => sys_faccessat(args[0] as _, args[1] as _, args[2] as _, current_process),
+        SYS_FACCESSAT2 => sys_faccessat2(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            current_process,
+        ),
+        SYS_FCHMOD => sys_fchmod(args[0] as _, args[1] as _, current_process),
+        SYS_FCHMODAT => sys_fchmodat(args[0] as _, args[1] as _, args[2] as _, current_process),
+        SYS_FCHOWN => sys_fchown(args[0] as _, args[1] as _, args[2] as _, current_process),
+        SYS_FCHOWNAT => sys_fchownat(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            args[4] as _,
+            current_process,
+        ),
+        SYS_GETUID => sys_getuid(current_process),
+        SYS_GETEUID => sys_geteuid(current_process),
+        SYS_GETGID => sys_getgid(current_process),
+        SYS_GETEGID => sys_getegid(current_process),
+        SYS_SETUID => sys_setuid(args[0] as _, current_process),
+        SYS_SETGID => sys_setgid(args[0] as _, current_process),
+        SYS_SETREUID => sys_setreuid(args[0] as _, args[1] as _, current_process),
+        SYS_SETREGID => sys_setregid(args[0] as _, args[1] as _, current_process),
+        SYS_SETRESUID => sys_setresuid(args[0] as _, args[1] as _, args[2] as _, current_process),
+        SYS_GETRESUID => sys_getresuid(args[0] as _, args[1] as _, args[2] as _, current_process),
+        SYS_SETRESGID => sys_setresgid(args[0] as _, args[1] as _, args[2] as _, current_process),
+        SYS_GETRESGID => sys_getresgid(args[0] as _, args[1] as _, args[2] as _, current_process),
+        SYS_GETGROUPS => sys_getgroups(args[0] as _, args[1] as _, current_process),
+        SYS_SETGROUPS => sys_setgroups(args[0] as _, args[1] as _, current_process),
+        SYS_CLOSE => sys_close(args[0] as _, current_process),
+        SYS_DUP => sys_dup(args[0] as _, current_process),
+        SYS_DUP3 => sys_dup3(args[0] as _, args[1] as _, args[2] as _, current_process),
+        SYS_FCNTL => sys_fcntl(args[0] as _, args[1] as _, args[2] as _, current_process),
+        SYS_GETCWD => sys_getcwd(args[0] as _, args[1] as _, current_process),
+        SYS_CHDIR => sys_chdir(args[0] as _, current_process),
+        SYS_FCHDIR => sys_fchdir(args[0] as _, current_process),
+        SYS_CHROOT => sys_chroot(args[0] as _, current_process),
+        SYS_MOUNT => sys_mount(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            args[4] as _,
+            current_process,
+        ),
+        SYS_UMOUNT2 => sys_umount2(args[0] as _, args[1] as _, current_process),
         SYS_MMAP => sys_mmap(
             args[0] as _,
             args[1] as _,
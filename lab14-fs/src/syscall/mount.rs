@@ -0,0 +1,100 @@
+use alloc::{string::String, sync::Arc};
+use log::debug;
+use ostd::mm::Vaddr;
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::mount;
+use crate::process::Process;
+use crate::syscall::{SyscallReturn, read_path};
+
+const MS_REMOUNT: u32 = 1 << 5;
+const MS_BIND: u32 = 1 << 12;
+const MS_MOVE: u32 = 1 << 13;
+
+const MNT_FORCE: u32 = 1 << 0;
+const MNT_DETACH: u32 = 1 << 1;
+const UMOUNT_NOFOLLOW: u32 = 1 << 3;
+
+pub fn sys_mount(
+    source: Vaddr,
+    target: Vaddr,
+    fstype: Vaddr,
+    flags: u32,
+    data: Vaddr,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_MOUNT] source: {:#x}, target: {:#x}, fstype: {:#x}, flags: {:#x}, data: {:#x}",
+        source, target, fstype, flags, data
+    );
+
+    // Remounting, bind mounts and moving mounts are not supported. Other
+    // flags are ignored.
+    if flags & (MS_REMOUNT | MS_BIND | MS_MOVE) != 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    if !current_process.credentials().is_privileged() {
+        return Err(Error::new(Errno::EPERM));
+    }
+
+    let fstype = read_path(fstype, current_process)?;
+    let target = read_path(target, current_process)?;
+    // Virtual filesystems need no source, name them after their type.
+    let source = if source == 0 {
+        fstype.clone()
+    } else {
+        let source = read_path(source, current_process)?;
+        crate::drivers::canonical_block_device_name(&source).unwrap_or(source)
+    };
+    // The filesystem specific data is a string of options for the
+    // filesystems here.
+    let options = if data == 0 {
+        String::new()
+    } else {
+        read_path(data, current_process)?
+    };
+    debug!(
+        "[SYS_MOUNT] source: {}, target: {}, fstype: {}, options: {}",
+        source, target, fstype, options
+    );
+
+    let target_path = current_process.resolver().lookup(&target, true)?;
+    let fs = crate::fs::new_filesystem(&fstype, &source, &options)?;
+
+    // A device cannot be mounted twice. Each mount of a virtual filesystem
+    // is a new one.
+    let is_virtual = matches!(
+        fstype.as_str(),
+        "ramfs" | "tmpfs" | "devtmpfs" | "proc" | "sysfs" | "overlay"
+    );
+    if is_virtual {
+        mount::mount(fs, &source, &target_path)?;
+    } else {
+        mount::mount_device(fs, &source, &target_path)?;
+    }
+    Ok(SyscallReturn(0))
+}
+
+pub fn sys_umount2(
+    target: Vaddr,
+    flags: u32,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!("[SYS_UMOUNT2] target: {:#x}, flags: {:#x}", target, flags);
+
+    // Filesystems are always detached right away, so forced and lazy
+    // unmounts behave like normal ones.
+    if flags & !(MNT_FORCE | MNT_DETACH | UMOUNT_NOFOLLOW) != 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    if !current_process.credentials().is_privileged() {
+        return Err(Error::new(Errno::EPERM));
+    }
+
+    let target = read_path(target, current_process)?;
+    debug!("[SYS_UMOUNT2] target: {}", target);
+    let follow = flags & UMOUNT_NOFOLLOW == 0;
+    let target_path = current_process.resolver().lookup(&target, follow)?;
+    mount::umount(&target_path)?;
+    Ok(SyscallReturn(0))
+}
@@ -1,23 +1,14 @@
-use core::ffi::CStr;
-
-use alloc::string::ToString;
 use alloc::sync::Arc;
-use alloc::vec;
 use log::debug;
-use ostd::mm::{FallibleVmRead, Vaddr, VmWriter};
+use ostd::mm::Vaddr;
 
 use crate::error::{Errno, Error, Result};
-use crate::fs::InodeType;
-use crate::fs::file_table::FileEntry;
-use crate::fs::util::PathString;
+use crate::fs::device::DeviceFile;
+use crate::fs::file_table::{FdFlags, FileEntry};
+use crate::fs::permission::Permission;
+use crate::fs::{FileLike, InodeType, OpenFlags};
 use crate::process::Process;
-use crate::syscall::SyscallReturn;
-
-bitflags::bitflags! {
-    pub struct OpenFlags: u32 {
-        const O_CREAT = 1 << 6;
-    }
-}
+use crate::syscall::{SyscallReturn, read_path, resolver_at};
 
 pub fn sys_openat(
     dfd: usize,
@@ -31,39 +22,68 @@ pub fn sys_openat(
         dfd, file_name, flags, mode
     );
 
-    // The max file name: 255 bytes + 1(\0)
-    const MAX_FILENAME_LENGTH: usize = 256;
-    let mut buffer = vec![0u8; MAX_FILENAME_LENGTH];
-    current_process
-        .memory_space()
-        .vm_space()
-        .reader(file_name, MAX_FILENAME_LENGTH)
-        .unwrap()
-        .read_fallible(&mut VmWriter::from(&mut buffer as &mut [u8]))
-        .unwrap();
-
-    let file_name = CStr::from_bytes_until_nul(&buffer)
-        .unwrap()
-        .to_str()
-        .unwrap();
+    let file_name = read_path(file_name, current_process)?;
+    let flags = OpenFlags::from_bits_truncate(flags as u32);
+    let create = flags.contains(OpenFlags::O_CREAT);
+    let follow = !flags.contains(OpenFlags::O_NOFOLLOW);
+    let resolver = resolver_at(dfd as i32, &file_name, current_process)?;
+    let (open_path, created) = match resolver.lookup(&file_name, follow) {
+        Ok(_) if create && flags.contains(OpenFlags::O_EXCL) => {
+            return Err(Error::new(Errno::EEXIST));
+        }
+        Ok(path) => (path, false),
+        Err(err) if create && err.code == Errno::ENOENT => {
+            let path = resolver.create(&file_name, InodeType::File, mode as u16)?;
+            (path, true)
+        }
+        Err(err) => return Err(err),
+    };
 
-    let create = OpenFlags::from_bits_truncate(flags as u32).contains(OpenFlags::O_CREAT);
-    let mut path_string = PathString::new(file_name.to_string());
-    let current_inode = crate::fs::ROOT.get().unwrap().root_inode();
-    if path_string.is_empty() {
-        return Err(Error::new(Errno::EINVAL));
+    let inode = open_path.inode();
+    match inode.typ() {
+        InodeType::SymbolLink => return Err(Error::new(Errno::ELOOP)),
+        InodeType::Directory if flags.is_writable() => return Err(Error::new(Errno::EISDIR)),
+        InodeType::Directory => {}
+        _ if flags.contains(OpenFlags::O_DIRECTORY) => return Err(Error::new(Errno::ENOTDIR)),
+        _ => {}
+    }
+    // The creator may open the new file however its mode is.
+    if !created {
+        let mut permission = Permission::empty();
+        if flags.is_readable() {
+            permission |= Permission::READ;
+        }
+        if flags.is_writable() || flags.contains(OpenFlags::O_TRUNC) {
+            permission |= Permission::WRITE;
+        }
+        resolver.check_permission(inode, permission)?;
+    }
+    if flags.is_writable() {
+        inode.prepare_write()?;
+    }
+    if flags.contains(OpenFlags::O_TRUNC)
+        && flags.is_writable()
+        && inode.typ() == InodeType::File
+        && inode.size() != 0
+    {
+        inode.resize(0)?;
     }
 
-    let open_inode = if create {
-        path_string.create(current_inode.as_ref(), InodeType::File)?
+    let fd_flags = if flags.contains(OpenFlags::O_CLOEXEC) {
+        FdFlags::FD_CLOEXEC
     } else {
-        path_string.lookup(current_inode.as_ref())?
+        FdFlags::empty()
+    };
+    // Device files go to the driver of the device.
+    let file: Arc<dyn FileLike> = match inode.typ() {
+        InodeType::CharDevice | InodeType::BlockDevice => {
+            Arc::new(DeviceFile::open(open_path, flags)?)
+        }
+        _ => Arc::new(crate::fs::util::FileInode::new(open_path, flags)),
     };
-
-    let file = crate::fs::util::FileInode::new(open_inode);
     let fd = current_process
         .file_table()
-        .insert(FileEntry::new(Arc::new(file)));
+        .insert(FileEntry::new(file, fd_flags))?;
 
     Ok(SyscallReturn(fd as _))
 }
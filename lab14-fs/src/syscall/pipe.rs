@@ -4,7 +4,8 @@ use ostd::Pod;
 use ostd::mm::Vaddr;
 
 use crate::error::Result;
-use crate::fs::file_table::FileEntry;
+use crate::fs::OpenFlags;
+use crate::fs::file_table::{FdFlags, FileEntry};
 use crate::fs::pipe::Pipe;
 use crate::process::Process;
 use crate::syscall::SyscallReturn;
@@ -27,10 +28,21 @@ pub fn sys_pipe2(
     );
 
     let (reader, writer) = Pipe::new_pair();
+    let fd_flags = if OpenFlags::from_bits_truncate(flags as u32).contains(OpenFlags::O_CLOEXEC) {
+        FdFlags::FD_CLOEXEC
+    } else {
+        FdFlags::empty()
+    };
 
     let mut file_table = current_process.file_table();
-    let read_fd = file_table.insert(FileEntry::new(reader));
-    let write_fd = file_table.insert(FileEntry::new(writer));
+    let read_fd = file_table.insert(FileEntry::new(reader, fd_flags))?;
+    let write_fd = match file_table.insert(FileEntry::new(writer, fd_flags)) {
+        Ok(fd) => fd,
+        Err(err) => {
+            file_table.close(read_fd);
+            return Err(err);
+        }
+    };
 
     let vm_space = current_process.memory_space().vm_space();
     let mut writer = vm_space.writer(pipe_address, size_of::<PipeFds>()).unwrap();
@@ -3,9 +3,11 @@ use log::debug;
 use ostd::mm::Vaddr;
 
 use super::SyscallReturn;
+use super::write::{IoVec, get_file, read_io_vecs};
 use crate::error::Result;
 use crate::{
     error::{Errno, Error},
+    fs::FileLike,
     process::Process,
 };
 
@@ -24,11 +26,112 @@ pub fn sys_read(
         .memory_space()
         .vm_space()
         .writer(user_buf_addr, buf_len)
-        .unwrap();
+        .map_err(|_| Error::new(Errno::EFAULT))?;
 
-    let file_table = current_process.file_table();
-    let file = file_table.get(fd).ok_or(Error::new(Errno::EBADF))?;
-    let read_len = file.file().read(writer)?;
+    let file = get_file(fd, current_process)?;
+    let read_len = file.read(writer)?;
+
+    Ok(SyscallReturn(read_len as _))
+}
+
+pub fn sys_pread64(
+    fd: i32,
+    user_buf_addr: Vaddr,
+    buf_len: usize,
+    offset: isize,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_PREAD64] fd: {:?}, user_buf_addr: 0x{:x?}, buf_len: {:?}, offset: {:?}",
+        fd, user_buf_addr, buf_len, offset
+    );
+
+    if offset < 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    let writer = current_process
+        .memory_space()
+        .vm_space()
+        .writer(user_buf_addr, buf_len)
+        .map_err(|_| Error::new(Errno::EFAULT))?;
+
+    let file = get_file(fd, current_process)?;
+    let read_len = file.read_at(offset as usize, writer)?;
+
+    Ok(SyscallReturn(read_len as _))
+}
+
+/// Reads into the buffers in order, at `offset` if given or at the file
+/// offset otherwise. Stops at the first short read.
+fn read_io_vecs_from(
+    file: &Arc<dyn FileLike>,
+    io_vecs: &[IoVec],
+    mut offset: Option<usize>,
+    current_process: &Arc<Process>,
+) -> Result<usize> {
+    let memory_space = current_process.memory_space();
+    let mut total_len = 0;
+    for io_vec in io_vecs.iter().filter(|io_vec| io_vec.len != 0) {
+        let writer = memory_space
+            .vm_space()
+            .writer(io_vec.base, io_vec.len)
+            .map_err(|_| Error::new(Errno::EFAULT))?;
+        let result = match offset {
+            Some(offset) => file.read_at(offset, writer),
+            None => file.read(writer),
+        };
+        let read_len = match result {
+            Ok(read_len) => read_len,
+            // Report the data already read instead of the error.
+            Err(_) if total_len != 0 => break,
+            Err(err) => return Err(err),
+        };
+
+        total_len += read_len;
+        offset = offset.map(|offset| offset + read_len);
+        if read_len < io_vec.len {
+            break;
+        }
+    }
+    Ok(total_len)
+}
+
+pub fn sys_readv(
+    fd: i32,
+    io_vec_ptr: Vaddr,
+    io_vec_count: usize,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_READV] fd: {:?}, vec ptr: {:x?}, vec count: {:?}",
+        fd, io_vec_ptr, io_vec_count
+    );
+
+    let file = get_file(fd, current_process)?;
+    let io_vecs = read_io_vecs(io_vec_ptr, io_vec_count, current_process)?;
+    let read_len = read_io_vecs_from(&file, &io_vecs, None, current_process)?;
+
+    Ok(SyscallReturn(read_len as _))
+}
+
+pub fn sys_preadv(
+    fd: i32,
+    io_vec_ptr: Vaddr,
+    io_vec_count: usize,
+    offset: isize,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_PREADV] fd: {:?}, vec ptr: {:x?}, vec count: {:?}, offset: {:?}",
+        fd, io_vec_ptr, io_vec_count, offset
+    );
+
+    if offset < 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    let file = get_file(fd, current_process)?;
+    let io_vecs = read_io_vecs(io_vec_ptr, io_vec_count, current_process)?;
+    let read_len = read_io_vecs_from(&file, &io_vecs, Some(offset as usize), current_process)?;
 
     Ok(SyscallReturn(read_len as _))
 }
@@ -0,0 +1,88 @@
+use alloc::sync::Arc;
+use log::{debug, info};
+use ostd::{
+    arch::qemu::{QemuExitCode, exit_qemu},
+    early_println,
+    irq::disable_local,
+};
+
+use crate::error::{Errno, Error, Result};
+use crate::process::Process;
+use crate::syscall::SyscallReturn;
+
+const LINUX_REBOOT_MAGIC1: u32 = 0xfee1dead;
+const LINUX_REBOOT_MAGIC2: u32 = 672274793;
+const LINUX_REBOOT_MAGIC2A: u32 = 85072278;
+const LINUX_REBOOT_MAGIC2B: u32 = 369367448;
+const LINUX_REBOOT_MAGIC2C: u32 = 537993216;
+
+const LINUX_REBOOT_CMD_RESTART: u32 = 0x01234567;
+const LINUX_REBOOT_CMD_HALT: u32 = 0xCDEF0123;
+const LINUX_REBOOT_CMD_CAD_ON: u32 = 0x89ABCDEF;
+const LINUX_REBOOT_CMD_CAD_OFF: u32 = 0x00000000;
+const LINUX_REBOOT_CMD_POWER_OFF: u32 = 0x4321FEDC;
+const LINUX_REBOOT_CMD_RESTART2: u32 = 0xA1B2C3D4;
+
+pub fn sys_reboot(
+    magic: u32,
+    magic2: u32,
+    cmd: u32,
+    _arg: usize,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_REBOOT] magic: {:#x}, magic2: {:#x}, cmd: {:#x}",
+        magic, magic2, cmd
+    );
+
+    if magic != LINUX_REBOOT_MAGIC1
+        || ![
+            LINUX_REBOOT_MAGIC2,
+            LINUX_REBOOT_MAGIC2A,
+            LINUX_REBOOT_MAGIC2B,
+            LINUX_REBOOT_MAGIC2C,
+        ]
+        .contains(&magic2)
+    {
+        return Err(Error::new(Errno::EINVAL));
+    }
+
+    if !current_process.credentials().is_privileged() {
+        return Err(Error::new(Errno::EPERM));
+    }
+
+    match cmd {
+        // We do not handle Ctrl-Alt-Del.
+        LINUX_REBOOT_CMD_CAD_ON | LINUX_REBOOT_CMD_CAD_OFF => return Ok(SyscallReturn(0)),
+        LINUX_REBOOT_CMD_RESTART
+        | LINUX_REBOOT_CMD_RESTART2
+        | LINUX_REBOOT_CMD_HALT
+        | LINUX_REBOOT_CMD_POWER_OFF => {}
+        _ => return Err(Error::new(Errno::EINVAL)),
+    }
+
+    crate::process::stop_all_except(current_process);
+    crate::fs::shutdown();
+
+    match cmd {
+        LINUX_REBOOT_CMD_RESTART | LINUX_REBOOT_CMD_RESTART2 => {
+            info!("Restarting system");
+            sbi_rt::system_reset(sbi_rt::ColdReboot, sbi_rt::NoReason);
+        }
+        LINUX_REBOOT_CMD_HALT => {
+            early_println!("System halted.");
+            let _guard = disable_local();
+            // A pending interrupt still wakes the hart, without being taken.
+            loop {
+                riscv::asm::wfi();
+            }
+        }
+        _ => {
+            info!("Power down");
+            sbi_rt::system_reset(sbi_rt::Shutdown, sbi_rt::NoReason);
+        }
+    }
+
+    // The SBI implementation does not support system reset.
+    exit_qemu(QemuExitCode::Success)
+}
@@ -0,0 +1,59 @@
+use alloc::sync::Arc;
+use log::debug;
+use ostd::mm::Vaddr;
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::permission::Permission;
+use crate::process::Process;
+use crate::syscall::{SyscallReturn, read_path, resolver_at};
+
+const RENAME_NOREPLACE: u32 = 1 << 0;
+const RENAME_EXCHANGE: u32 = 1 << 1;
+const RENAME_WHITEOUT: u32 = 1 << 2;
+
+pub fn sys_renameat2(
+    old_dirfd: i32,
+    old_path: Vaddr,
+    new_dirfd: i32,
+    new_path: Vaddr,
+    flags: u32,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    let old_path = read_path(old_path, current_process)?;
+    let new_path = read_path(new_path, current_process)?;
+    debug!(
+        "[SYS_RENAMEAT2] old_dirfd: {}, old_path: {}, new_dirfd: {}, new_path: {}, flags: {:#x}",
+        old_dirfd, old_path, new_dirfd, new_path, flags
+    );
+
+    if flags & !(RENAME_NOREPLACE | RENAME_EXCHANGE | RENAME_WHITEOUT) != 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    // Exchanging and whiteouts are not supported by any filesystem here.
+    if flags & (RENAME_EXCHANGE | RENAME_WHITEOUT) != 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+
+    let old_resolver = resolver_at(old_dirfd, &old_path, current_process)?;
+    let (old_dir, old_name) = old_resolver.lookup_parent(&old_path)?;
+    let new_resolver = resolver_at(new_dirfd, &new_path, current_process)?;
+    let (new_dir, new_name) = new_resolver.lookup_parent(&new_path)?;
+    for dir in [&old_dir, &new_dir] {
+        old_resolver.check_permission(dir.inode(), Permission::WRITE | Permission::EXEC)?;
+    }
+
+    // Only directories may be named with a trailing slash.
+    if old_path.ends_with('/') || new_path.ends_with('/') {
+        old_resolver.lookup(&old_path, true)?;
+    }
+    let noreplace = flags & RENAME_NOREPLACE != 0;
+    old_dir.rename(
+        &old_name,
+        &new_dir,
+        &new_name,
+        noreplace,
+        old_resolver.credentials(),
+    )?;
+
+    Ok(SyscallReturn(0))
+}
@@ -0,0 +1,237 @@
+use core::time::Duration;
+
+use alloc::sync::Arc;
+use log::debug;
+use ostd::{Pod, mm::Vaddr};
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::{FileLike, InodeMeta, dev_major_minor};
+use crate::process::Process;
+use crate::syscall::write::get_file;
+use crate::syscall::{AT_FDCWD, SyscallReturn, read_path, resolver_at, write_to_user};
+
+const AT_SYMLINK_NOFOLLOW: u32 = 0x100;
+const AT_NO_AUTOMOUNT: u32 = 0x800;
+const AT_EMPTY_PATH: u32 = 0x1000;
+/// The `statx` flags telling how to sync with a remote filesystem.
+const AT_STATX_SYNC_TYPE: u32 = 0x6000;
+
+/// `stx_mask` bits of the fields filled by `statx`.
+const STATX_BASIC_STATS: u32 = 0x7ff;
+
+/// The `struct stat` of RISC-V, as defined in `asm-generic/stat.h`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct Stat {
+    dev: u64,
+    ino: u64,
+    mode: u32,
+    nlink: u32,
+    uid: u32,
+    gid: u32,
+    rdev: u64,
+    _pad1: u64,
+    size: i64,
+    blksize: i32,
+    _pad2: i32,
+    blocks: i64,
+    atime: i64,
+    atime_nsec: u64,
+    mtime: i64,
+    mtime_nsec: u64,
+    ctime: i64,
+    ctime_nsec: u64,
+    _unused: [u32; 2],
+}
+
+impl Stat {
+    fn new(meta: &InodeMeta, dev: u64) -> Self {
+        Self {
+            dev,
+            ino: meta.ino,
+            mode: meta.type_.mode_bits() | meta.mode as u32,
+            nlink: meta.nlink,
+            uid: meta.uid,
+            gid: meta.gid,
+            rdev: meta.rdev,
+            _pad1: 0,
+            size: meta.size as i64,
+            blksize: meta.blksize as i32,
+            _pad2: 0,
+            blocks: meta.blocks as i64,
+            atime: meta.atime.as_secs() as i64,
+            atime_nsec: meta.atime.subsec_nanos() as u64,
+            mtime: meta.mtime.as_secs() as i64,
+            mtime_nsec: meta.mtime.subsec_nanos() as u64,
+            ctime: meta.ctime.as_secs() as i64,
+            ctime_nsec: meta.ctime.subsec_nanos() as u64,
+            _unused: [0; 2],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct StatxTimestamp {
+    sec: i64,
+    nsec: u32,
+    _reserved: i32,
+}
+
+impl From<Duration> for StatxTimestamp {
+    fn from(time: Duration) -> Self {
+        Self {
+            sec: time.as_secs() as i64,
+            nsec: time.subsec_nanos(),
+            _reserved: 0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct Statx {
+    mask: u32,
+    blksize: u32,
+    attributes: u64,
+    nlink: u32,
+    uid: u32,
+    gid: u32,
+    mode: u16,
+    _spare0: u16,
+    ino: u64,
+    size: u64,
+    blocks: u64,
+    attributes_mask: u64,
+    atime: StatxTimestamp,
+    btime: StatxTimestamp,
+    ctime: StatxTimestamp,
+    mtime: StatxTimestamp,
+    rdev_major: u32,
+    rdev_minor: u32,
+    dev_major: u32,
+    dev_minor: u32,
+    mnt_id: u64,
+    dio_mem_align: u32,
+    dio_offset_align: u32,
+    _spare3: [u64; 12],
+}
+
+impl Statx {
+    fn new(meta: &InodeMeta, dev: u64) -> Self {
+        let (rdev_major, rdev_minor) = dev_major_minor(meta.rdev);
+        let (dev_major, dev_minor) = dev_major_minor(dev);
+        Self {
+            mask: STATX_BASIC_STATS,
+            blksize: meta.blksize as u32,
+            attributes: 0,
+            nlink: meta.nlink,
+            uid: meta.uid,
+            gid: meta.gid,
+            mode: (meta.type_.mode_bits() | meta.mode as u32) as u16,
+            _spare0: 0,
+            ino: meta.ino,
+            size: meta.size as u64,
+            blocks: meta.blocks as u64,
+            attributes_mask: 0,
+            atime: meta.atime.into(),
+            btime: Duration::ZERO.into(),
+            ctime: meta.ctime.into(),
+            mtime: meta.mtime.into(),
+            rdev_major,
+            rdev_minor,
+            dev_major,
+            dev_minor,
+            mnt_id: 0,
+            dio_mem_align: 0,
+            dio_offset_align: 0,
+            _spare3: [0; 12],
+        }
+    }
+}
+
+/// The attributes of an open file and the device it is on.
+fn file_metadata(file: &Arc<dyn FileLike>) -> (InodeMeta, u64) {
+    let dev = file.as_path().map_or(0, |path| path.mount().dev());
+    (file.metadata(), dev)
+}
+
+/// The attributes of the file at `path` relative to `dirfd`, or of `dirfd`
+/// itself if `path` is empty and `AT_EMPTY_PATH` is given.
+fn metadata_at(
+    dirfd: i32,
+    path: Vaddr,
+    flags: u32,
+    current_process: &Arc<Process>,
+) -> Result<(InodeMeta, u64)> {
+    let path = read_path(path, current_process)?;
+    if path.is_empty() && flags & AT_EMPTY_PATH != 0 {
+        if dirfd == AT_FDCWD {
+            let cwd = current_process.fs().cwd().clone();
+            return Ok((cwd.inode().metadata(), cwd.mount().dev()));
+        }
+        return Ok(file_metadata(&get_file(dirfd, current_process)?));
+    }
+
+    let resolver = resolver_at(dirfd, &path, current_process)?;
+    let path = resolver.lookup(&path, flags & AT_SYMLINK_NOFOLLOW == 0)?;
+    Ok((path.inode().metadata(), path.mount().dev()))
+}
+
+pub fn sys_fstat(
+    fd: i32,
+    stat_buf: Vaddr,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!("[SYS_FSTAT] fd: {}, stat_buf: {:#x}", fd, stat_buf);
+
+    let (meta, dev) = file_metadata(&get_file(fd, current_process)?);
+    write_to_user(stat_buf, &Stat::new(&meta, dev), current_process)?;
+
+    Ok(SyscallReturn(0))
+}
+
+pub fn sys_newfstatat(
+    dirfd: i32,
+    path: Vaddr,
+    stat_buf: Vaddr,
+    flags: u32,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_NEWFSTATAT] dirfd: {}, path: {:#x}, stat_buf: {:#x}, flags: {:#x}",
+        dirfd, path, stat_buf, flags
+    );
+
+    if flags & !(AT_SYMLINK_NOFOLLOW | AT_NO_AUTOMOUNT | AT_EMPTY_PATH) != 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    let (meta, dev) = metadata_at(dirfd, path, flags, current_process)?;
+    write_to_user(stat_buf, &Stat::new(&meta, dev), current_process)?;
+
+    Ok(SyscallReturn(0))
+}
+
+pub fn sys_statx(
+    dirfd: i32,
+    path: Vaddr,
+    flags: u32,
+    mask: u32,
+    statx_buf: Vaddr,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_STATX] dirfd: {}, path: {:#x}, flags: {:#x}, mask: {:#x}, statx_buf: {:#x}",
+        dirfd, path, flags, mask, statx_buf
+    );
+
+    let valid_flags = AT_SYMLINK_NOFOLLOW | AT_NO_AUTOMOUNT | AT_EMPTY_PATH | AT_STATX_SYNC_TYPE;
+    if flags & !valid_flags != 0 || flags & AT_STATX_SYNC_TYPE == AT_STATX_SYNC_TYPE {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    // The basic fields are always filled, whatever `mask` asks for.
+    let (meta, dev) = metadata_at(dirfd, path, flags, current_process)?;
+    write_to_user(statx_buf, &Statx::new(&meta, dev), current_process)?;
+
+    Ok(SyscallReturn(0))
+}
@@ -0,0 +1,78 @@
+use alloc::sync::Arc;
+use log::debug;
+use ostd::{Pod, mm::Vaddr};
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::{FsStats, dev_major_minor};
+use crate::process::Process;
+use crate::syscall::write::get_file;
+use crate::syscall::{SyscallReturn, read_path, write_to_user};
+
+/// The `f_flags` bit telling that the other flags are filled.
+const ST_VALID: i64 = 0x20;
+
+/// The `struct statfs` of RISC-V, as defined in `asm-generic/statfs.h`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct Statfs {
+    type_: i64,
+    bsize: i64,
+    blocks: u64,
+    bfree: u64,
+    bavail: u64,
+    files: u64,
+    ffree: u64,
+    fsid: [i32; 2],
+    namelen: i64,
+    frsize: i64,
+    flags: i64,
+    _spare: [i64; 4],
+}
+
+impl Statfs {
+    /// Fills the statistics of a filesystem mounted with device number `dev`.
+    fn new(stats: &FsStats, dev: u64) -> Self {
+        let (major, minor) = dev_major_minor(dev);
+        Self {
+            type_: stats.magic as i64,
+            bsize: stats.block_size as i64,
+            blocks: stats.total_blocks,
+            bfree: stats.free_blocks,
+            bavail: stats.avail_blocks,
+            files: stats.total_inodes,
+            ffree: stats.free_inodes,
+            fsid: [major as i32, minor as i32],
+            namelen: stats.name_max as i64,
+            frsize: stats.block_size as i64,
+            flags: ST_VALID,
+            _spare: [0; 4],
+        }
+    }
+}
+
+pub fn sys_statfs(
+    path: Vaddr,
+    buf: Vaddr,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    let path = read_path(path, current_process)?;
+    debug!("[SYS_STATFS] path: {}, buf: {:#x}", path, buf);
+
+    let path = current_process.resolver().lookup(&path, true)?;
+    let mount = path.mount();
+    let statfs = Statfs::new(&mount.fs().stats(), mount.dev());
+    write_to_user(buf, &statfs, current_process)?;
+    Ok(SyscallReturn(0))
+}
+
+pub fn sys_fstatfs(fd: i32, buf: Vaddr, current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_FSTATFS] fd: {}, buf: {:#x}", fd, buf);
+
+    // Pipes and standard streams are not on a mounted filesystem.
+    let file = get_file(fd, current_process)?;
+    let path = file.as_path().ok_or(Error::new(Errno::EINVAL))?;
+    let mount = path.mount();
+    let statfs = Statfs::new(&mount.fs().stats(), mount.dev());
+    write_to_user(buf, &statfs, current_process)?;
+    Ok(SyscallReturn(0))
+}
@@ -0,0 +1,66 @@
+use alloc::sync::Arc;
+use log::debug;
+use ostd::mm::{FallibleVmWrite, Vaddr, VmReader};
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::InodeType;
+use crate::process::Process;
+use crate::syscall::{SyscallReturn, read_path, resolver_at};
+
+pub fn sys_symlinkat(
+    target: Vaddr,
+    new_dirfd: i32,
+    link_path: Vaddr,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    let target = read_path(target, current_process)?;
+    let link_path = read_path(link_path, current_process)?;
+    debug!(
+        "[SYS_SYMLINKAT] target: {}, new_dirfd: {}, link_path: {}",
+        target, new_dirfd, link_path
+    );
+
+    if target.is_empty() {
+        return Err(Error::new(Errno::ENOENT));
+    }
+    let resolver = resolver_at(new_dirfd, &link_path, current_process)?;
+    resolver.symlink(&link_path, &target)?;
+
+    Ok(SyscallReturn(0))
+}
+
+pub fn sys_readlinkat(
+    dirfd: i32,
+    path: Vaddr,
+    buf: Vaddr,
+    buf_size: isize,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    let path = read_path(path, current_process)?;
+    debug!(
+        "[SYS_READLINKAT] dirfd: {}, path: {}, buf: {:#x}, buf_size: {}",
+        dirfd, path, buf, buf_size
+    );
+
+    if buf_size <= 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    let resolver = resolver_at(dirfd, &path, current_process)?;
+    let link = resolver.lookup(&path, false)?;
+    if link.inode().typ() != InodeType::SymbolLink {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    let target = link.inode().read_link()?;
+
+    // The target is truncated to fit and is not NUL-terminated.
+    let len = target.len().min(buf_size as usize);
+    current_process
+        .memory_space()
+        .vm_space()
+        .writer(buf, len)
+        .map_err(|_| Error::new(Errno::EFAULT))?
+        .write_fallible(&mut VmReader::from(&target.as_bytes()[..len]))
+        .map_err(|_| Error::new(Errno::EFAULT))?;
+
+    Ok(SyscallReturn(len as _))
+}
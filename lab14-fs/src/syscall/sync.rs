@@ -0,0 +1,44 @@
+use alloc::sync::Arc;
+use log::debug;
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::Inode;
+use crate::process::Process;
+use crate::syscall::SyscallReturn;
+use crate::syscall::write::get_file;
+
+pub fn sys_sync(_current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_SYNC]");
+    crate::fs::sync()?;
+    Ok(SyscallReturn(0))
+}
+
+pub fn sys_fsync(fd: i32, current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_FSYNC] fd: {}", fd);
+    file_inode(fd, current_process)?.sync_all()?;
+    Ok(SyscallReturn(0))
+}
+
+pub fn sys_fdatasync(fd: i32, current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_FDATASYNC] fd: {}", fd);
+    file_inode(fd, current_process)?.sync_data()?;
+    Ok(SyscallReturn(0))
+}
+
+pub fn sys_syncfs(fd: i32, current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_SYNCFS] fd: {}", fd);
+
+    let file = get_file(fd, current_process)?;
+    // Pipes and standard streams have nothing to write back.
+    if let Some(fs) = file.as_inode().and_then(|inode| inode.fs()) {
+        fs.sync()?;
+    }
+    Ok(SyscallReturn(0))
+}
+
+/// Gets the inode behind `fd`. Files without an inode cannot be synced.
+fn file_inode(fd: i32, current_process: &Arc<Process>) -> Result<Arc<dyn Inode>> {
+    get_file(fd, current_process)?
+        .as_inode()
+        .ok_or(Error::new(Errno::EINVAL))
+}
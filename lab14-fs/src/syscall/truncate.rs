@@ -0,0 +1,102 @@
+use alloc::sync::Arc;
+use log::debug;
+use ostd::mm::Vaddr;
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::permission::Permission;
+use crate::fs::{Inode, InodeType};
+use crate::process::Process;
+use crate::syscall::write::get_file;
+use crate::syscall::{SyscallReturn, read_path};
+
+/// Allocates the range without extending the file.
+const FALLOC_FL_KEEP_SIZE: u32 = 0x1;
+
+pub fn sys_truncate(
+    path: Vaddr,
+    len: isize,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    let path = read_path(path, current_process)?;
+    debug!("[SYS_TRUNCATE] path: {}, len: {}", path, len);
+
+    if len < 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    let resolver = current_process.resolver();
+    let path = resolver.lookup(&path, true)?;
+    if path.inode().typ() == InodeType::File {
+        resolver.check_permission(path.inode(), Permission::WRITE)?;
+    }
+    resize_file(path.inode(), len as usize)?;
+
+    Ok(SyscallReturn(0))
+}
+
+pub fn sys_ftruncate(fd: i32, len: isize, current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_FTRUNCATE] fd: {}, len: {}", fd, len);
+
+    if len < 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    let file = get_file(fd, current_process)?;
+    if !file.status_flags().is_writable() {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    let inode = file.as_inode().ok_or(Error::new(Errno::EINVAL))?;
+    resize_file(&inode, len as usize)?;
+
+    Ok(SyscallReturn(0))
+}
+
+pub fn sys_fallocate(
+    fd: i32,
+    mode: u32,
+    offset: isize,
+    len: isize,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_FALLOCATE] fd: {}, mode: {:#x}, offset: {}, len: {}",
+        fd, mode, offset, len
+    );
+
+    if offset < 0 || len <= 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    // Punching holes and the like are not supported.
+    if mode & !FALLOC_FL_KEEP_SIZE != 0 {
+        return Err(Error::new(Errno::EOPNOTSUPP));
+    }
+    let end = (offset as usize)
+        .checked_add(len as usize)
+        .filter(|&end| end <= isize::MAX as usize)
+        .ok_or(Error::new(Errno::EFBIG))?;
+
+    let file = get_file(fd, current_process)?;
+    if !file.status_flags().is_writable() {
+        return Err(Error::new(Errno::EBADF));
+    }
+    let inode = file.as_inode().ok_or(Error::new(Errno::ESPIPE))?;
+    match inode.typ() {
+        InodeType::File => {}
+        InodeType::Directory => return Err(Error::new(Errno::EISDIR)),
+        _ => return Err(Error::new(Errno::ENODEV)),
+    }
+
+    inode.allocate(offset as usize, len as usize)?;
+    if mode & FALLOC_FL_KEEP_SIZE == 0 && end > inode.size() {
+        inode.resize(end)?;
+    }
+
+    Ok(SyscallReturn(0))
+}
+
+/// Resizes the regular file `inode` to `len` bytes.
+fn resize_file(inode: &Arc<dyn Inode>, len: usize) -> Result<()> {
+    match inode.typ() {
+        InodeType::File => inode.resize(len),
+        InodeType::Directory => Err(Error::new(Errno::EISDIR)),
+        _ => Err(Error::new(Errno::EINVAL)),
+    }
+}
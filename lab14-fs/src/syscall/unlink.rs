@@ -0,0 +1,42 @@
+use alloc::sync::Arc;
+use log::debug;
+use ostd::mm::Vaddr;
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::permission::Permission;
+use crate::process::Process;
+use crate::syscall::{SyscallReturn, read_path, resolver_at};
+
+const AT_REMOVEDIR: u32 = 0x200;
+
+pub fn sys_unlinkat(
+    dirfd: i32,
+    path: Vaddr,
+    flags: u32,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    let path = read_path(path, current_process)?;
+    debug!(
+        "[SYS_UNLINKAT] dirfd: {}, path: {}, flags: {:#x}",
+        dirfd, path, flags
+    );
+
+    if flags & !AT_REMOVEDIR != 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    let resolver = resolver_at(dirfd, &path, current_process)?;
+    let (dir, name) = resolver.lookup_parent(&path)?;
+    resolver.check_permission(dir.inode(), Permission::WRITE | Permission::EXEC)?;
+    if flags & AT_REMOVEDIR != 0 {
+        dir.rmdir(&name, resolver.credentials())?;
+    } else {
+        // Only directories may be named with a trailing slash.
+        if path.ends_with('/') {
+            resolver.lookup(&path, true)?;
+            return Err(Error::new(Errno::EISDIR));
+        }
+        dir.unlink(&name, resolver.credentials())?;
+    }
+
+    Ok(SyscallReturn(0))
+}
@@ -1,23 +1,90 @@
-use core::str;
-
-use alloc::{sync::Arc, vec};
+use alloc::{sync::Arc, vec::Vec};
 use log::debug;
-use ostd::{
-    Pod, early_print,
-    mm::{FallibleVmRead, Vaddr, VmWriter},
-};
+use ostd::{Pod, mm::Vaddr};
 
 use crate::{
     error::{Errno, Error, Result},
+    fs::FileLike,
     process::Process,
     syscall::SyscallReturn,
 };
 
+/// The max number of buffers in one vectored IO.
+const IOV_MAX: usize = 1024;
+
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Pod)]
 pub struct IoVec {
-    base: Vaddr,
-    len: usize,
+    pub base: Vaddr,
+    pub len: usize,
+}
+
+/// Reads the array of `count` buffers at `io_vec_ptr` from the user space.
+pub fn read_io_vecs(
+    io_vec_ptr: Vaddr,
+    count: usize,
+    current_process: &Arc<Process>,
+) -> Result<Vec<IoVec>> {
+    if count > IOV_MAX {
+        return Err(Error::new(Errno::EINVAL));
+    }
+
+    let memory_space = current_process.memory_space();
+    let mut io_vecs = Vec::with_capacity(count);
+    for idx in 0..count {
+        let io_vec: IoVec = memory_space
+            .vm_space()
+            .reader(io_vec_ptr + idx * size_of::<IoVec>(), size_of::<IoVec>())
+            .and_then(|mut reader| reader.read_val())
+            .map_err(|_| Error::new(Errno::EFAULT))?;
+        if io_vec.len > isize::MAX as usize {
+            return Err(Error::new(Errno::EINVAL));
+        }
+        io_vecs.push(io_vec);
+    }
+    Ok(io_vecs)
+}
+
+/// Gets the file at `fd`, so that the file table is not locked during IO.
+pub fn get_file(fd: i32, current_process: &Arc<Process>) -> Result<Arc<dyn FileLike>> {
+    let file_table = current_process.file_table();
+    let entry = file_table.get(fd).ok_or(Error::new(Errno::EBADF))?;
+    Ok(entry.file().clone())
+}
+
+/// Writes the buffers in order, at `offset` if given or at the file offset
+/// otherwise. Stops at the first short write.
+fn write_io_vecs(
+    file: &Arc<dyn FileLike>,
+    io_vecs: &[IoVec],
+    mut offset: Option<usize>,
+    current_process: &Arc<Process>,
+) -> Result<usize> {
+    let memory_space = current_process.memory_space();
+    let mut total_len = 0;
+    for io_vec in io_vecs.iter().filter(|io_vec| io_vec.len != 0) {
+        let reader = memory_space
+            .vm_space()
+            .reader(io_vec.base, io_vec.len)
+            .map_err(|_| Error::new(Errno::EFAULT))?;
+        let result = match offset {
+            Some(offset) => file.write_at(offset, reader),
+            None => file.write(reader),
+        };
+        let write_len = match result {
+            Ok(write_len) => write_len,
+            // Report the data already written instead of the error.
+            Err(_) if total_len != 0 => break,
+            Err(err) => return Err(err),
+        };
+
+        total_len += write_len;
+        offset = offset.map(|offset| offset + write_len);
+        if write_len < io_vec.len {
+            break;
+        }
+    }
+    Ok(total_len)
 }
 
 pub fn sys_writev(
@@ -31,34 +98,33 @@ pub fn sys_writev(
         fd, io_vec_ptr, io_vec_count
     );
 
-    let mut total_len = 0;
+    let file = get_file(fd, current_process)?;
+    let io_vecs = read_io_vecs(io_vec_ptr, io_vec_count, current_process)?;
+    let write_len = write_io_vecs(&file, &io_vecs, None, current_process)?;
 
-    let mut current_addr = io_vec_ptr;
-    let memory_space = current_process.memory_space();
-
-    for _ in 0..io_vec_count {
-        let mut reader = memory_space
-            .vm_space()
-            .reader(current_addr, size_of::<IoVec>())
-            .unwrap();
-        let io_vec: IoVec = reader.read_val().unwrap();
+    Ok(SyscallReturn(write_len as _))
+}
 
-        let mut buf = vec![0u8; io_vec.len];
+pub fn sys_pwritev(
+    fd: i32,
+    io_vec_ptr: Vaddr,
+    io_vec_count: usize,
+    offset: isize,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_PWRITEV] Fd: {:?}, vec ptr: {:x?}, vec count: {:?}, offset: {:?}",
+        fd, io_vec_ptr, io_vec_count, offset
+    );
 
-        let mut buffer = memory_space
-            .vm_space()
-            .reader(io_vec.base, io_vec.len)
-            .unwrap();
-        buffer
-            .read_fallible(&mut VmWriter::from(&mut buf as &mut [u8]))
-            .unwrap();
-
-        total_len += io_vec.len;
-        early_print!("{}", str::from_utf8(&buf).unwrap());
-        current_addr += size_of::<IoVec>();
+    if offset < 0 {
+        return Err(Error::new(Errno::EINVAL));
     }
+    let file = get_file(fd, current_process)?;
+    let io_vecs = read_io_vecs(io_vec_ptr, io_vec_count, current_process)?;
+    let write_len = write_io_vecs(&file, &io_vecs, Some(offset as usize), current_process)?;
 
-    Ok(SyscallReturn(total_len as _))
+    Ok(SyscallReturn(write_len as _))
 }
 
 pub fn sys_write(
@@ -76,11 +142,37 @@ pub fn sys_write(
         .memory_space()
         .vm_space()
         .reader(buf, count)
-        .unwrap();
+        .map_err(|_| Error::new(Errno::EFAULT))?;
 
-    let file_table = current_process.file_table();
-    let file = file_table.get(fd).ok_or(Error::new(Errno::EBADF))?;
-    let write_len = file.file().write(reader)?;
+    let file = get_file(fd, current_process)?;
+    let write_len = file.write(reader)?;
+
+    Ok(SyscallReturn(write_len as _))
+}
+
+pub fn sys_pwrite64(
+    fd: i32,
+    buf: Vaddr,
+    count: usize,
+    offset: isize,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_PWRITE64] Fd: {:?}, buf: {:x?}, count: {:?}, offset: {:?}",
+        fd, buf, count, offset
+    );
+
+    if offset < 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    let reader = current_process
+        .memory_space()
+        .vm_space()
+        .reader(buf, count)
+        .map_err(|_| Error::new(Errno::EFAULT))?;
+
+    let file = get_file(fd, current_process)?;
+    let write_len = file.write_at(offset as usize, reader)?;
 
     Ok(SyscallReturn(write_len as _))
 }